@@ -1,26 +1,29 @@
 use std::thread;
+use std::rc::Rc;
 use std::fs::{self, File};
 use std::path::{Path, PathBuf};
-use std::sync::mpsc;
+use std::sync::{mpsc, Arc};
+use std::sync::atomic::{AtomicBool, Ordering};
 use std::process::Command;
 use std::collections::VecDeque;
 use std::time::{Instant, Duration};
 use fnv::FnvHashMap;
-use chrono::Local;
-use framebuffer::{Framebuffer, KoboFramebuffer, UpdateMode};
-use view::{View, Event, EntryId, EntryKind, ViewId};
+use chrono::{Local, DateTime};
+use framebuffer::{Framebuffer, KoboFramebuffer, UpdateMode, coalesce_update};
+use view::{View, Event, EntryId, ViewId};
 use view::{render, render_no_wait, handle_event, fill_crack};
-use view::common::{locate, locate_by_id, overlapping_rectangle};
+use view::common::{locate, locate_by_id, overlapping_rectangle, toggle_preset_menu};
 use view::frontlight::FrontlightWindow;
-use view::menu::{Menu, MenuKind};
+use view::named_input::NamedInput;
 use input::{DeviceEvent, ButtonCode, ButtonStatus};
 use input::{raw_events, device_events, usb_events};
 use gesture::{GestureEvent, gesture_events, BUTTON_HOLD_DELAY};
 use helpers::{load_json, save_json};
 use metadata::{Metadata, METADATA_FILENAME, import};
 use settings::{Settings, SETTINGS_PATH};
-use frontlight::{Frontlight, NaturalFrontlight, StandardFrontlight};
-use lightsensor::{LightSensor, KoboLightSensor};
+use frontlight::{Frontlight, NaturalFrontlight, StandardFrontlight, apply_schedule, apply_warmth_ramp};
+use frontlight::{FlooredFrontlight, Fade, resolve_schedule, wake_fade, DEFAULT_LEVELS};
+use lightsensor::{LightSensor, light_sensor};
 use battery::{Battery, KoboBattery};
 use view::home::Home;
 use view::reader::Reader;
@@ -28,13 +31,16 @@ use view::confirmation::Confirmation;
 use view::intermission::Intermission;
 use view::notification::Notification;
 use device::CURRENT_DEVICE;
-use font::Fonts;
+use geom::Rectangle;
+use font::{Fonts, Hyphenator, patterns_path};
 use errors::*;
 
 pub const APP_NAME: &str = "Plato";
 
-const CLOCK_REFRESH_INTERVAL: Duration = Duration::from_secs(60);
-const BATTERY_REFRESH_INTERVAL: Duration = Duration::from_secs(299);
+// The clock and the battery are refreshed together.
+const STATUS_REFRESH_INTERVAL: Duration = Duration::from_secs(60);
+// The longest time the pending updates can be held back by a stream of events.
+pub const MAX_UPDATE_DELAY: Duration = Duration::from_millis(150);
 
 pub struct Context {
     pub settings: Settings,
@@ -44,6 +50,7 @@ pub struct Context {
     pub frontlight: Box<Frontlight>,
     pub battery: Box<Battery>,
     pub lightsensor: Box<LightSensor>,
+    pub clock: fn() -> DateTime<Local>,
     pub notification_index: u8,
     pub resumed_at: Instant,
     pub inverted: bool,
@@ -51,6 +58,10 @@ pub struct Context {
     pub suspended: bool,
     pub plugged: bool,
     pub mounted: bool,
+    // The fade that follows the resumption of the device.
+    pub wake_fade: Option<Fade>,
+    // The light sensor level of the last guess.
+    pub lightsensor_level: Option<u16>,
 }
 
 impl Context {
@@ -58,13 +69,62 @@ impl Context {
                filename: PathBuf, fonts: Fonts, battery: Box<Battery>,
                frontlight: Box<Frontlight>, lightsensor: Box<LightSensor>) -> Context {
         Context { settings, metadata, filename, fonts, battery,
-                  frontlight, lightsensor, notification_index: 0,
+                  frontlight, lightsensor, clock: Local::now, notification_index: 0,
                   resumed_at: Instant::now(), inverted: false,
                   monochrome: false, suspended: false,
-                  plugged: false, mounted: false }
+                  plugged: false, mounted: false, wake_fade: None,
+                  lightsensor_level: None }
+    }
+
+    // Jumps to the target of the wake fade, if any.
+    pub fn finish_wake_fade(&mut self) {
+        if let Some(fade) = self.wake_fade.take() {
+            fade.cancel();
+            let levels = fade.target();
+            self.frontlight.set_intensity(levels.intensity);
+            self.frontlight.set_warmth(levels.warmth);
+        }
+    }
+
+    // The levels are stashed in the settings while the frontlight is off.
+    pub fn toggle_frontlight(&mut self) {
+        self.finish_wake_fade();
+        self.settings.frontlight = !self.settings.frontlight;
+        if self.settings.frontlight {
+            let mut levels = self.settings.frontlight_levels;
+            // The light might have been dark already when it was turned off.
+            if levels.intensity == 0.0 {
+                levels.intensity = DEFAULT_LEVELS.intensity;
+            }
+            self.frontlight.set_intensity(levels.intensity);
+            self.frontlight.set_warmth(levels.warmth);
+        } else {
+            self.settings.frontlight_levels = self.frontlight.levels();
+            self.frontlight.set_warmth(0.0);
+            self.frontlight.set_intensity(0.0);
+        }
     }
 }
 
+// A context with the built-in fonts and fake devices, for the tests of the views.
+#[cfg(test)]
+pub fn test_context() -> Context {
+    Context::new(Settings::default(), Vec::new(), PathBuf::from(METADATA_FILENAME),
+                 Fonts::load().unwrap(), Box::new(::battery::FakeBattery::new()),
+                 Box::new(::frontlight::LightLevels::default()), Box::new(0u16))
+}
+
+// Sends the pending updates to the framebuffer.
+pub fn flush_updates(fb: &mut Framebuffer, pending_updates: &mut Vec<(Rectangle, UpdateMode)>,
+                     pending_since: &mut Option<Instant>, updating: &mut FnvHashMap<u32, Rectangle>) {
+    for (rect, mode) in pending_updates.drain(..) {
+        if let Ok(tok) = fb.update(&rect, mode) {
+            updating.insert(tok, rect);
+        }
+    }
+    *pending_since = None;
+}
+
 pub fn run() -> Result<()> {
     let path = Path::new(SETTINGS_PATH);
 
@@ -87,9 +147,13 @@ pub fn run() -> Result<()> {
                              .unwrap_or_default();
 
     let mut fb = KoboFramebuffer::new("/dev/fb0").chain_err(|| "Can't create framebuffer.")?;
+    let rotation = CURRENT_DEVICE.rotation;
+    let panel_dims = fb.dims();
+    fb.set_rotation(rotation);
+    fb.set_gamma(settings.text_gamma);
     let paths = vec!["/dev/input/event0".to_string(),
                      "/dev/input/event1".to_string()];
-    let touch_screen = gesture_events(device_events(raw_events(paths), fb.dims()));
+    let touch_screen = gesture_events(device_events(raw_events(paths), panel_dims, rotation));
     let usb_port = usb_events();
 
     let (tx, rx) = mpsc::channel();
@@ -111,22 +175,49 @@ pub fn run() -> Result<()> {
     let tx4 = tx.clone();
     thread::spawn(move || {
         loop {
-            thread::sleep(CLOCK_REFRESH_INTERVAL);
-            tx4.send(Event::ClockTick).unwrap();
+            thread::sleep(STATUS_REFRESH_INTERVAL);
+            tx4.send(Event::RefreshStatus).unwrap();
         }
     });
 
-    let tx5 = tx.clone();
-    thread::spawn(move || {
-        loop {
-            thread::sleep(BATTERY_REFRESH_INTERVAL);
-            tx5.send(Event::BatteryTick).unwrap();
-        }
-    });
+    // The sensor belongs to the context: the thread only asks the main loop to read it.
+    // A tick isn't sent while the previous one is pending, and the thread stops once
+    // the main loop has dropped the pending flag.
+    let lightsensor_pending = Arc::new(AtomicBool::new(false));
+    if settings.lightsensor_poll_interval > 0 {
+        let tx5 = tx.clone();
+        let pending = Arc::downgrade(&lightsensor_pending);
+        let interval = Duration::from_millis(settings.lightsensor_poll_interval);
+        thread::spawn(move || {
+            loop {
+                thread::sleep(interval);
+                let pending = match pending.upgrade() {
+                    Some(pending) => pending,
+                    None => break,
+                };
+                if !pending.swap(true, Ordering::Relaxed) && tx5.send(Event::LightSensorTick).is_err() {
+                    break;
+                }
+            }
+        });
+    }
 
     let fb_rect = fb.rect();
 
-    let fonts = Fonts::load().chain_err(|| "Can't load fonts.")?;
+    let mut fonts = Fonts::load().chain_err(|| "Can't load fonts.")?;
+    if let Some(ref user_fonts) = settings.user_fonts {
+        fonts.load_user_fonts(user_fonts);
+    }
+    fonts.set_glyph_cache_capacity(settings.glyph_cache_capacity);
+    if !settings.fallback_fonts.is_empty() {
+        fonts.set_fallbacks(&settings.fallback_fonts)
+             .map_err(|e| eprintln!("Can't load fallback fonts: {}", e)).ok();
+    }
+    if let Some(ref language) = settings.hyphenation_language {
+        fonts.hyphenator = Hyphenator::load(patterns_path(language))
+                                      .map_err(|e| eprintln!("Can't load hyphenation patterns: {}", e))
+                                      .ok().map(Rc::new);
+    }
 
     if settings.wifi {
         Command::new("scripts/wifi-enable.sh").spawn().ok();
@@ -135,13 +226,14 @@ pub fn run() -> Result<()> {
     }
 
     let levels = settings.frontlight_levels;
-    let mut frontlight = if CURRENT_DEVICE.has_natural_light() {
+    let frontlight = if CURRENT_DEVICE.has_natural_light() {
         Box::new(NaturalFrontlight::new(levels.intensity, levels.warmth)
                                    .chain_err(|| "Can't create natural frontlight.")?) as Box<Frontlight>
     } else {
         Box::new(StandardFrontlight::new(levels.intensity)
                                     .chain_err(|| "Can't create standard frontlight.")?) as Box<Frontlight>
     };
+    let mut frontlight = Box::new(FlooredFrontlight::new(frontlight, settings.frontlight_min_intensity)) as Box<Frontlight>;
 
     if settings.frontlight {
         frontlight.set_intensity(levels.intensity);
@@ -153,11 +245,7 @@ pub fn run() -> Result<()> {
 
     let battery = Box::new(KoboBattery::new().chain_err(|| "Can't create battery.")?) as Box<Battery>;
 
-    let lightsensor = if CURRENT_DEVICE.has_lightsensor() {
-        Box::new(KoboLightSensor::new().chain_err(|| "Can't create light sensor.")?) as Box<LightSensor>
-    } else {
-        Box::new(0u16) as Box<LightSensor>
-    };
+    let lightsensor = light_sensor(&settings);
 
     let mut context = Context::new(settings, metadata, PathBuf::from(METADATA_FILENAME),
                                    fonts, battery, frontlight, lightsensor);
@@ -165,6 +253,9 @@ pub fn run() -> Result<()> {
     let mut view: Box<View> = Box::new(Home::new(fb_rect, &tx, &mut context)?);
 
     let mut updating = FnvHashMap::default();
+    let mut pending_updates = Vec::new();
+    let mut pending_since = None;
+    let mut settings_snapshot = context.settings.snapshot();
 
     println!("{} is running on a Kobo {}.", APP_NAME,
                                             CURRENT_DEVICE.model);
@@ -173,7 +264,25 @@ pub fn run() -> Result<()> {
 
     let mut bus = VecDeque::with_capacity(4);
 
-    while let Ok(evt) = rx.recv() {
+    loop {
+        // The pending updates are sent once all the queued events are handled,
+        // or when they've waited too long.
+        let evt = match rx.try_recv() {
+            Ok(evt) => {
+                if pending_since.map_or(false, |t: Instant| t.elapsed() >= MAX_UPDATE_DELAY) {
+                    flush_updates(&mut fb, &mut pending_updates, &mut pending_since, &mut updating);
+                }
+                evt
+            },
+            Err(..) => {
+                flush_updates(&mut fb, &mut pending_updates, &mut pending_since, &mut updating);
+                match rx.recv() {
+                    Ok(evt) => evt,
+                    Err(..) => break,
+                }
+            },
+        };
+
         match evt {
             Event::Device(de) => {
                 match de {
@@ -214,7 +323,7 @@ pub fn run() -> Result<()> {
                                                         "Mount onboard and external cards?".to_string(),
                                                         &mut context.fonts);
                         tx.send(Event::Render(*confirm.rect(), UpdateMode::Gui)).unwrap();
-                        tx.send(Event::BatteryTick).unwrap();
+                        tx.send(Event::RefreshStatus).unwrap();
                         view.children_mut().push(Box::new(confirm) as Box<View>);
                     },
                     DeviceEvent::Unplug => {
@@ -261,7 +370,7 @@ pub fn run() -> Result<()> {
                             view.handle_event(&Event::Reseed, &tx, &mut bus, &mut context);
                         } else {
                             context.plugged = false;
-                            tx.send(Event::BatteryTick).unwrap();
+                            tx.send(Event::RefreshStatus).unwrap();
                         }
                     },
                     _ => {
@@ -270,10 +379,18 @@ pub fn run() -> Result<()> {
                 }
             },
             Event::Suspend => {
+                context.finish_wake_fade();
                 context.suspended = true;
+                flush_updates(&mut fb, &mut pending_updates, &mut pending_since, &mut updating);
                 updating.retain(|tok, _| fb.wait(*tok).is_err());
                 let path = Path::new(SETTINGS_PATH);
                 save_json(&context.settings, path).map_err(|e| eprintln!("Can't save settings: {}", e)).ok();
+                let snapshot = context.settings.snapshot();
+                let changes = settings_snapshot.diff(&snapshot);
+                if !changes.is_empty() {
+                    tx.send(Event::SettingsChanged(changes)).unwrap();
+                }
+                settings_snapshot = snapshot;
                 let path = context.settings.library_path.join(&context.filename);
                 save_json(&context.metadata, path).map_err(|e| eprintln!("Can't save metadata: {}", e)).ok();
                 if context.settings.frontlight {
@@ -287,6 +404,7 @@ pub fn run() -> Result<()> {
                             .ok();
                 }
                 println!("{}", Local::now().format("Went to sleep on %B %d, %Y at %H:%M."));
+                let suspended_at = Instant::now();
                 Command::new("scripts/suspend.sh")
                         .status()
                         .ok();
@@ -308,14 +426,36 @@ pub fn run() -> Result<()> {
                 }
                 if context.settings.frontlight {
                     let levels = context.settings.frontlight_levels;
+                    let asleep = context.resumed_at.duration_since(suspended_at);
+                    let settings = &context.settings.frontlight_fade;
+                    if let Some(fade) = wake_fade(&levels, asleep, settings) {
+                        context.frontlight.set_warmth(levels.warmth);
+                        context.frontlight.set_intensity(0.0);
+                        let tx2 = tx.clone();
+                        fade.run(Duration::from_millis(settings.wake_duration),
+                                 move || tx2.send(Event::WakeFadeTick).is_ok());
+                        context.wake_fade = Some(fade);
+                    } else {
+                        context.frontlight.set_intensity(levels.intensity);
+                        context.frontlight.set_warmth(levels.warmth);
+                    }
+                }
+                tx.send(Event::RefreshStatus).unwrap();
+            },
+            Event::WakeFadeTick => {
+                if let Some(levels) = context.wake_fade.as_mut().and_then(|fade| fade.next()) {
                     context.frontlight.set_intensity(levels.intensity);
                     context.frontlight.set_warmth(levels.warmth);
                 }
-                tx.send(Event::ClockTick).unwrap();
-                tx.send(Event::BatteryTick).unwrap();
+                // The schedule, held back during the fade, is applied once it ends.
+                if context.wake_fade.as_ref().map_or(false, |fade| fade.progress() >= 1.0) {
+                    context.wake_fade = None;
+                    tx.send(Event::RefreshStatus).unwrap();
+                }
             },
             Event::Mount => {
                 if !context.mounted {
+                    context.finish_wake_fade();
                     while let Some(v) = history.pop() {
                         view.handle_event(&Event::Back, &tx, &mut bus, &mut context);
                         view = v;
@@ -346,6 +486,7 @@ pub fn run() -> Result<()> {
                             eprintln!("Couldn't create the poweroff file: {}", e);
                         }).ok();
                         let interm = Intermission::new(fb_rect, "Powered off".to_string(), true);
+                        flush_updates(&mut fb, &mut pending_updates, &mut pending_since, &mut updating);
                         updating.retain(|tok, _| fb.wait(*tok).is_err());
                         interm.render(&mut fb, &mut context.fonts);
                         fb.update(interm.rect(), UpdateMode::Full).ok();
@@ -356,35 +497,26 @@ pub fn run() -> Result<()> {
                     },
                 }
             },
-            Event::ToggleFrontlight => {
-                context.settings.frontlight = !context.settings.frontlight;
-                if context.settings.frontlight {
-                    let levels = context.settings.frontlight_levels;
-                    context.frontlight.set_intensity(levels.intensity);
-                    context.frontlight.set_warmth(levels.warmth);
-                } else {
-                    context.settings.frontlight_levels = context.frontlight.levels();
-                    context.frontlight.set_warmth(0.0);
-                    context.frontlight.set_intensity(0.0);
+            Event::ToggleFrontlight | Event::Select(EntryId::ToggleFrontlight) => {
+                context.toggle_frontlight();
+                if let Event::Select(..) = evt {
+                    handle_event(view.as_mut(), &evt, &tx, &mut bus, &mut context);
                 }
             },
             Event::Render(mut rect, mode) => {
                 render(view.as_ref(), &mut rect, &mut fb, &mut context.fonts, &mut updating);
-                if let Ok(tok) = fb.update(&rect, mode) {
-                    updating.insert(tok, rect);
-                }
+                coalesce_update(&mut pending_updates, rect, mode);
+                pending_since.get_or_insert_with(Instant::now);
             },
             Event::RenderNoWait(mut rect, mode) => {
                 render_no_wait(view.as_ref(), &mut rect, &mut fb, &mut context.fonts, &mut updating);
-                if let Ok(tok) = fb.update(&rect, mode) {
-                    updating.insert(tok, rect);
-                }
+                coalesce_update(&mut pending_updates, rect, mode);
+                pending_since.get_or_insert_with(Instant::now);
             },
             Event::Expose(mut rect) => {
                 fill_crack(view.as_ref(), &mut rect, &mut fb, &mut context.fonts, &mut updating);
-                if let Ok(tok) = fb.update(&rect, UpdateMode::Gui) {
-                    updating.insert(tok, rect);
-                }
+                coalesce_update(&mut pending_updates, rect, UpdateMode::Gui);
+                pending_since.get_or_insert_with(Instant::now);
             },
             Event::Open(info) => {
                 let info2 = info.clone();
@@ -400,6 +532,31 @@ pub fn run() -> Result<()> {
                 history.push(view as Box<View>);
                 view = Box::new(r) as Box<View>;
             },
+            Event::LightSensorTick => {
+                lightsensor_pending.store(false, Ordering::Relaxed);
+                if context.suspended {
+                    continue;
+                }
+                if let (Ok(raw), Ok(level)) = (context.lightsensor.raw_level(), context.lightsensor.level()) {
+                    handle_event(view.as_mut(), &Event::LightSensorReading(raw, level), &tx, &mut bus, &mut context);
+                }
+            },
+            Event::RefreshStatus => {
+                // The status is also refreshed when the device resumes.
+                // The levels of the opened book take precedence over the schedule.
+                // The schedule waits for the end of the wake fade.
+                let overridden = view.downcast_ref::<Reader>().map_or(false, |r| r.overrides_frontlight());
+                if context.settings.frontlight && !overridden && context.wake_fade.is_none() &&
+                   locate::<FrontlightWindow>(view.as_ref()).is_none() {
+                    let now = (context.clock)();
+                    let schedule = resolve_schedule(&context.settings.frontlight_schedule, now.date().naive_local(),
+                                                    context.settings.location.as_ref(), now.offset().local_minus_utc());
+                    apply_schedule(context.frontlight.as_mut(), &schedule, &now);
+                    apply_warmth_ramp(context.frontlight.as_mut(), context.settings.frontlight_warmth_ramp.as_ref(),
+                                      &context.settings.night_hours, &now);
+                }
+                handle_event(view.as_mut(), &evt, &tx, &mut bus, &mut context);
+            },
             Event::Back => {
                 if let Some(v) = history.pop() {
                     view = v;
@@ -407,34 +564,86 @@ pub fn run() -> Result<()> {
                 }
             },
             Event::TogglePresetMenu(rect, index) => {
-                if let Some(index) = locate_by_id(view.as_ref(), ViewId::PresetMenu) {
-                    let rect = *view.child(index).rect();
-                    view.children_mut().remove(index);
-                    tx.send(Event::Expose(rect)).unwrap();
-                } else {
-                    let preset_menu = Menu::new(rect, ViewId::PresetMenu, MenuKind::Contextual,
-                                                vec![EntryKind::Command("Remove".to_string(),
-                                                                        EntryId::RemovePreset(index))],
-                                                &mut context.fonts);
-                    tx.send(Event::Render(*preset_menu.rect(), UpdateMode::Gui)).unwrap();
-                    view.children_mut().push(Box::new(preset_menu) as Box<View>);
-                }
+                toggle_preset_menu(view.as_mut(), rect, index, &tx, &mut context);
             },
-            Event::Show(ViewId::Frontlight) => {
+            Event::Show(ViewId::Frontlight) | Event::ShowNear(ViewId::Frontlight, _) => {
                 if !context.settings.frontlight {
                     continue;
                 }
-                let flw = FrontlightWindow::new(&mut context);
+                context.finish_wake_fade();
+                let anchor = match evt {
+                    Event::ShowNear(_, pt) if context.settings.frontlight_anchored => Some(pt),
+                    _ => None,
+                };
+                // Let the warmth slider reflect the ramp.
+                let now = (context.clock)();
+                apply_warmth_ramp(context.frontlight.as_mut(), context.settings.frontlight_warmth_ramp.as_ref(),
+                                  &context.settings.night_hours, &now);
+                let flw = FrontlightWindow::new(&mut context, anchor);
                 tx.send(Event::Render(*flw.rect(), UpdateMode::Gui)).unwrap();
                 view.children_mut().push(Box::new(flw) as Box<View>);
             },
+            Event::Show(ViewId::ConfirmRemovePreset) => {
+                let confirm = Confirmation::new(ViewId::ConfirmRemovePreset,
+                                                Event::RemovePreset,
+                                                "Remove this preset?".to_string(),
+                                                &mut context.fonts);
+                tx.send(Event::Render(*confirm.rect(), UpdateMode::Gui)).unwrap();
+                view.children_mut().push(Box::new(confirm) as Box<View>);
+            },
+            Event::Show(ViewId::ConfirmSkipDuplicates) => {
+                let confirm = Confirmation::new(ViewId::ConfirmSkipDuplicates,
+                                                Event::SkipDuplicatePresets(true),
+                                                "Skip the duplicated presets?".to_string(),
+                                                &mut context.fonts)
+                                          .cancel_event(Event::SkipDuplicatePresets(false));
+                tx.send(Event::Render(*confirm.rect(), UpdateMode::Gui)).unwrap();
+                view.children_mut().push(Box::new(confirm) as Box<View>);
+            },
+            Event::PresetsMerged(count) => {
+                let msg = if count == 1 {
+                    "Merged one duplicated preset.".to_string()
+                } else {
+                    format!("Merged {} duplicated presets.", count)
+                };
+                let notif = Notification::new(ViewId::MergedPresetsNotif,
+                                              msg,
+                                              &mut context.notification_index,
+                                              &mut context.fonts,
+                                              &tx);
+                view.children_mut().push(Box::new(notif) as Box<View>);
+            },
+            Event::Show(ViewId::RenamePreset) => {
+                let rename_preset = NamedInput::new("Rename preset".to_string(),
+                                                    ViewId::RenamePreset,
+                                                    ViewId::RenamePresetInput,
+                                                    12,
+                                                    &mut context.fonts);
+                tx.send(Event::Render(*rename_preset.rect(), UpdateMode::Gui)).unwrap();
+                tx.send(Event::Focus(Some(ViewId::RenamePresetInput))).unwrap();
+                view.children_mut().push(Box::new(rename_preset) as Box<View>);
+            },
             Event::Close(ViewId::Frontlight) => {
                 if let Some(index) = locate::<FrontlightWindow>(view.as_ref()) {
+                    let adjusted = view.child_mut(index).downcast_mut::<FrontlightWindow>()
+                                       .map_or(false, |flw| flw.close(&tx, &mut context));
+                    if let Some(r) = view.downcast_mut::<Reader>() {
+                        r.frontlight_adjusted |= adjusted;
+                    }
                     let rect = *view.child(index).rect();
                     view.children_mut().remove(index);
                     tx.send(Event::Expose(rect)).unwrap();
                 }
             },
+            Event::Close(ViewId::RenamePreset) => {
+                // Let the frontlight window know that the renaming is over.
+                handle_event(view.as_mut(), &evt, &tx, &mut bus, &mut context);
+                if let Some(index) = locate_by_id(view.as_ref(), ViewId::RenamePreset) {
+                    let rect = overlapping_rectangle(view.child(index));
+                    tx.send(Event::Expose(rect)).unwrap();
+                    view.children_mut().remove(index);
+                }
+            },
             Event::Close(id) => {
                 if let Some(index) = locate_by_id(view.as_ref(), id) {
                     let rect = overlapping_rectangle(view.child(index));
@@ -442,6 +651,12 @@ pub fn run() -> Result<()> {
                     view.children_mut().remove(index);
                 }
             },
+            Event::SettingsChanged(ref changes) => {
+                for change in changes {
+                    println!("Settings changed: {}.", change);
+                }
+                handle_event(view.as_mut(), &evt, &tx, &mut bus, &mut context);
+            },
             Event::Select(EntryId::ToggleInverted) => {
                 fb.toggle_inverted();
                 context.inverted = !context.inverted;
@@ -496,6 +711,8 @@ pub fn run() -> Result<()> {
         }
     }
 
+    context.finish_wake_fade();
+
     if context.settings.frontlight {
         context.settings.frontlight_levels = context.frontlight.levels();
     }
@@ -24,6 +24,8 @@ pub const TEXT_NORMAL: [u8; 3] = [WHITE, BLACK, GRAY08];
 pub const TEXT_BUMP_SMALL: [u8; 3] = [GRAY14, BLACK, GRAY07];
 pub const TEXT_BUMP_LARGE: [u8; 3] = [GRAY11, BLACK, BLACK];
 
+pub const TEXT_MUTED: [u8; 3] = [WHITE, GRAY06, GRAY08];
+
 pub const TEXT_INVERTED_SOFT: [u8; 3] = [GRAY05, WHITE, WHITE];
 pub const TEXT_INVERTED_HARD: [u8; 3] = [BLACK, WHITE, GRAY06];
 
@@ -36,3 +38,4 @@ pub const BATTERY_FILL: u8 = GRAY12;
 pub const PROGRESS_FULL: u8 = GRAY05;
 pub const PROGRESS_EMPTY: u8 = GRAY13;
 pub const PROGRESS_VALUE: u8 = GRAY06;
+pub const PROGRESS_FLOOR: u8 = GRAY02;
@@ -5,6 +5,7 @@ use std::fmt;
 use std::collections::HashMap;
 use unit::scale_by_dpi;
 use input::TouchProto;
+use framebuffer::Rotation;
 
 #[derive(Debug, Copy, Clone, Eq, PartialEq)]
 pub enum Model {
@@ -44,8 +45,10 @@ pub struct Device {
     pub model: Model,
     pub proto: TouchProto,
     pub mirrored_x: bool,
+    // The dimensions of the drawing space, they're swapped by quarter turns.
     pub dims: (u32, u32),
     pub dpi: u16,
+    pub rotation: Rotation,
 }
 
 impl Default for Device {
@@ -56,6 +59,7 @@ impl Default for Device {
             mirrored_x: true,
             dims: (600, 800),
             dpi: 167,
+            rotation: Rotation::default(),
         }
     }
 }
@@ -68,24 +72,39 @@ impl Device {
         }
     }
 
+    // The number of gray levels that the panel can display.
+    // Every supported panel, Pearl or Carta, has 16 levels.
+    pub fn gray_levels(&self) -> u16 {
+        16
+    }
+
     pub fn has_lightsensor(&self) -> bool {
         match self.model {
             Model::AuraONE => true,
             _ => false,
         }
     }
+
+    // The fraction of the ambient light that reaches the light sensor, if known.
+    pub fn lightsensor_transmittance(&self) -> Option<f32> {
+        match self.model {
+            Model::AuraONE => Some(1.0),
+            _ => None,
+        }
+    }
 }
 
 lazy_static! {
     pub static ref CURRENT_DEVICE: Device = {
         let product = env::var("PRODUCT").unwrap_or_default();
-        match product.as_ref() {
+        let mut device = match product.as_ref() {
             "kraken" => Device {
                 model: Model::Glo,
                 proto: TouchProto::Single,
                 mirrored_x: true,
                 dims: (758, 1024),
                 dpi: 212,
+                rotation: Rotation::default(),
             },
             "pixie" => Device {
                 model: Model::Mini,
@@ -93,6 +112,7 @@ lazy_static! {
                 mirrored_x: true,
                 dims: (600, 800),
                 dpi: 200,
+                rotation: Rotation::default(),
             },
             "dragon" => Device {
                 model: Model::AuraHD,
@@ -100,6 +120,7 @@ lazy_static! {
                 mirrored_x: true,
                 dims: (1080, 1440),
                 dpi: 265,
+                rotation: Rotation::default(),
             },
             "phoenix" => Device {
                 model: Model::Aura,
@@ -107,6 +128,7 @@ lazy_static! {
                 mirrored_x: true,
                 dims: (758, 1024),
                 dpi: 212,
+                rotation: Rotation::default(),
             },
             "dahlia" => Device {
                 model: Model::AuraH2O,
@@ -114,6 +136,7 @@ lazy_static! {
                 mirrored_x: true,
                 dims: (1080, 1440),
                 dpi: 265,
+                rotation: Rotation::default(),
             },
             "alyssum" => Device {
                 model: Model::GloHD,
@@ -121,6 +144,7 @@ lazy_static! {
                 mirrored_x: true,
                 dims: (1072, 1448),
                 dpi: 300,
+                rotation: Rotation::default(),
             },
             "pika" => Device {
                 model: Model::Touch2,
@@ -128,6 +152,7 @@ lazy_static! {
                 mirrored_x: true,
                 dims: (600, 800),
                 dpi: 167,
+                rotation: Rotation::default(),
             },
             "daylight" => Device {
                 model: Model::AuraONE,
@@ -135,6 +160,7 @@ lazy_static! {
                 mirrored_x: true,
                 dims: (1404, 1872),
                 dpi: 300,
+                rotation: Rotation::default(),
             },
             "star" => Device {
                 model: Model::AuraEdition2,
@@ -142,6 +168,7 @@ lazy_static! {
                 mirrored_x: true,
                 dims: (758, 1024),
                 dpi: 212,
+                rotation: Rotation::default(),
             },
             "snow" => Device {
                 model: Model::AuraH2OEdition2,
@@ -149,9 +176,18 @@ lazy_static! {
                 mirrored_x: false,
                 dims: (1080, 1440),
                 dpi: 265,
+                rotation: Rotation::default(),
             },
             _ => Device::default(),
+        };
+        // The rotation is given in degrees.
+        if let Some(rotation) = env::var("PLATO_ROTATION").ok()
+                                   .and_then(|v| v.parse().ok())
+                                   .and_then(Rotation::from_degrees) {
+            device.rotation = rotation;
+            device.dims = rotation.dims(device.dims);
         }
+        device
     };
 
 // Tuples of the form
@@ -160,13 +196,20 @@ lazy_static! {
 // HEIGHT = 3 * SMALL_HEIGHT + k * BIG_HEIGHT where k > 3
 // BIG_HEIGHT / SMALL_HEIGHT is as close as possible to 83/63
 // SMALL_HEIGHT / DPI * 2.54 is as close as possible to 1 cm
-pub static ref BAR_SIZES: HashMap<(u32, u16), (u32, u32)> =
-    [((1872, 300), (126, 166)),
-     ((1448, 300), (121, 155)),
-     ((1440, 265), (104, 141)),
-     ((1024, 212), ( 87, 109)),
-     (( 800, 167), ( 66,  86)),
-     (( 800, 200), ( 80, 112))].iter().cloned().collect();
+pub static ref BAR_SIZES: HashMap<(u32, u16), (u32, u32)> = {
+    let mut sizes: HashMap<(u32, u16), (u32, u32)> =
+        [((1872, 300), (126, 166)),
+         ((1448, 300), (121, 155)),
+         ((1440, 265), (104, 141)),
+         ((1024, 212), ( 87, 109)),
+         (( 800, 167), ( 66,  86)),
+         (( 800, 200), ( 80, 112))].iter().cloned().collect();
+    // Rotated devices need the sizes for their width.
+    let (_, height) = CURRENT_DEVICE.dims;
+    let dpi = CURRENT_DEVICE.dpi;
+    sizes.entry((height, dpi)).or_insert_with(|| optimal_bars_setup(height, dpi));
+    sizes
+};
 }
 
 pub fn optimal_bars_setup(height: u32, dpi: u16) -> (u32, u32) {
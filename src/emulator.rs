@@ -45,24 +45,15 @@ mod symbolic_path;
 mod trash;
 mod app;
 
-mod errors {
-    error_chain!{
-        foreign_links {
-            Io(::std::io::Error);
-            ParseInt(::std::num::ParseIntError);
-        }
-        links {
-            Font(::font::Error, ::font::ErrorKind);
-        }
-    }
-}
+mod errors;
 
 use std::thread;
+use std::rc::Rc;
 use std::fs::File;
 use std::sync::mpsc;
 use std::collections::VecDeque;
 use std::path::{Path, PathBuf};
-use std::time::Duration;
+use std::time::{Instant, Duration};
 use fnv::FnvHashMap;
 use chrono::Local;
 use png::HasParameters;
@@ -72,17 +63,18 @@ use sdl2::render::{WindowCanvas, BlendMode};
 use sdl2::pixels::{Color as SdlColor, PixelFormatEnum};
 use sdl2::rect::Point as SdlPoint;
 use sdl2::rect::Rect as SdlRect;
-use framebuffer::{Framebuffer, UpdateMode};
+use framebuffer::{Framebuffer, UpdateMode, Rotation, ClipStack, Snapshot, coalesce_update, gamma_table};
 use input::{DeviceEvent, FingerStatus};
-use view::{View, Event, ViewId, EntryId, EntryKind};
+use view::{View, Event, ViewId, EntryId};
 use view::{render, render_no_wait, handle_event, fill_crack};
 use view::home::Home;
 use view::reader::Reader;
 use view::notification::Notification;
 use view::frontlight::FrontlightWindow;
 use view::keyboard::Keyboard;
-use view::menu::{Menu, MenuKind};
-use view::common::{locate, locate_by_id, overlapping_rectangle};
+use view::named_input::NamedInput;
+use view::confirmation::Confirmation;
+use view::common::{locate, locate_by_id, overlapping_rectangle, toggle_preset_menu};
 use geom::Rectangle;
 use gesture::gesture_events;
 use device::CURRENT_DEVICE;
@@ -90,15 +82,16 @@ use helpers::{load_json, save_json};
 use metadata::{Metadata, METADATA_FILENAME};
 use settings::{Settings, SETTINGS_PATH};
 use battery::{Battery, FakeBattery};
-use frontlight::{Frontlight, LightLevels};
+use frontlight::{Frontlight, LightLevels, apply_schedule, apply_warmth_ramp};
+use frontlight::{FlooredFrontlight, resolve_schedule};
 use lightsensor::LightSensor;
-use font::Fonts;
-use app::Context;
+use font::{Fonts, Hyphenator, patterns_path};
+use app::{Context, flush_updates, MAX_UPDATE_DELAY};
 use errors::*;
 
 pub const APP_NAME: &str = "Plato";
 
-const CLOCK_REFRESH_INTERVAL: Duration = Duration::from_secs(60);
+const STATUS_REFRESH_INTERVAL: Duration = Duration::from_secs(60);
 
 pub fn build_context() -> Result<Context> {
     let settings = load_json::<Settings, _>(SETTINGS_PATH)?;
@@ -106,8 +99,22 @@ pub fn build_context() -> Result<Context> {
     let metadata = load_json::<Metadata, _>(path)?;
     let battery = Box::new(FakeBattery::new()) as Box<Battery>;
     let frontlight = Box::new(LightLevels::default()) as Box<Frontlight>;
+    let frontlight = Box::new(FlooredFrontlight::new(frontlight, settings.frontlight_min_intensity)) as Box<Frontlight>;
     let lightsensor = Box::new(0u16) as Box<LightSensor>;
-    let fonts = Fonts::load()?;
+    let mut fonts = Fonts::load().chain_err(|| "Can't load fonts.")?;
+    if let Some(ref user_fonts) = settings.user_fonts {
+        fonts.load_user_fonts(user_fonts);
+    }
+    fonts.set_glyph_cache_capacity(settings.glyph_cache_capacity);
+    if !settings.fallback_fonts.is_empty() {
+        fonts.set_fallbacks(&settings.fallback_fonts)
+             .map_err(|e| eprintln!("Can't load fallback fonts: {}", e)).ok();
+    }
+    if let Some(ref language) = settings.hyphenation_language {
+        fonts.hyphenator = Hyphenator::load(patterns_path(language))
+                                      .map_err(|e| eprintln!("Can't load hyphenation patterns: {}", e))
+                                      .ok().map(Rc::new);
+    }
     Ok(Context::new(settings, metadata, PathBuf::from(METADATA_FILENAME),
                     fonts, battery, frontlight, lightsensor))
 }
@@ -139,22 +146,46 @@ pub fn device_event(event: SdlEvent) -> Option<DeviceEvent> {
     }
 }
 
-impl Framebuffer for WindowCanvas {
+// The canvas of the window and the state it can't hold.
+pub struct EmulatorFramebuffer {
+    canvas: WindowCanvas,
+    gamma_table: [f32; 256],
+    clips: ClipStack,
+}
+
+impl EmulatorFramebuffer {
+    pub fn new(mut canvas: WindowCanvas) -> EmulatorFramebuffer {
+        canvas.set_blend_mode(BlendMode::Blend);
+        EmulatorFramebuffer {
+            canvas,
+            gamma_table: gamma_table(1.0),
+            clips: ClipStack::default(),
+        }
+    }
+}
+
+impl Framebuffer for EmulatorFramebuffer {
     fn set_pixel(&mut self, x: u32, y: u32, color: u8) {
-        self.set_draw_color(SdlColor::RGB(color, color, color));
-        self.draw_point(SdlPoint::new(x as i32, y as i32)).unwrap();
+        if !self.clips.includes(x, y) {
+            return;
+        }
+        self.canvas.set_draw_color(SdlColor::RGB(color, color, color));
+        self.canvas.draw_point(SdlPoint::new(x as i32, y as i32)).unwrap();
     }
 
     fn set_blended_pixel(&mut self, x: u32, y: u32, color: u8, alpha: f32) {
-        self.set_draw_color(SdlColor::RGBA(color, color, color, (alpha * 255.0) as u8));
-        self.draw_point(SdlPoint::new(x as i32, y as i32)).unwrap();
+        if !self.clips.includes(x, y) {
+            return;
+        }
+        self.canvas.set_draw_color(SdlColor::RGBA(color, color, color, (alpha * 255.0) as u8));
+        self.canvas.draw_point(SdlPoint::new(x as i32, y as i32)).unwrap();
     }
 
     fn invert_region(&mut self, rect: &Rectangle) {
         let width = rect.width();
         let s_rect = Some(SdlRect::new(rect.min.x, rect.min.y,
                                        width, rect.height()));
-        if let Ok(data) = self.read_pixels(s_rect, PixelFormatEnum::RGB24) {
+        if let Ok(data) = self.canvas.read_pixels(s_rect, PixelFormatEnum::RGB24) {
             for y in rect.min.y..rect.max.y {
                 let v = (y - rect.min.y) as u32;
                 for x in rect.min.x..rect.max.x {
@@ -168,7 +199,7 @@ impl Framebuffer for WindowCanvas {
     }
 
     fn update(&mut self, _rect: &Rectangle, _mode: UpdateMode) -> Result<u32> {
-        self.present();
+        self.canvas.present();
         Ok(1)
     }
 
@@ -182,7 +213,7 @@ impl Framebuffer for WindowCanvas {
         let mut encoder = png::Encoder::new(file, width, height);
         encoder.set(png::ColorType::RGB).set(png::BitDepth::Eight);
         let mut writer = encoder.write_header().chain_err(|| "Can't write header.")?;
-        let data = self.read_pixels(self.viewport(), PixelFormatEnum::RGB24).unwrap_or_default();
+        let data = self.canvas.read_pixels(self.canvas.viewport(), PixelFormatEnum::RGB24).unwrap_or_default();
         writer.write_image_data(&data).chain_err(|| "Can't write data to file.")?;
         Ok(())
     }
@@ -191,8 +222,56 @@ impl Framebuffer for WindowCanvas {
 
     fn toggle_monochrome(&mut self) {}
 
+    // The window already has the rotated dimensions of the device.
+    fn set_rotation(&mut self, _: Rotation) {}
+
+    fn rotation(&self) -> Rotation {
+        Rotation::default()
+    }
+
+    fn set_gamma(&mut self, gamma: f32) {
+        self.gamma_table = gamma_table(gamma);
+    }
+
+    fn gamma_table(&self) -> &[f32; 256] {
+        &self.gamma_table
+    }
+
+    fn push_clip(&mut self, rect: &Rectangle) {
+        self.clips.push(rect);
+    }
+
+    fn pop_clip(&mut self) {
+        self.clips.pop();
+    }
+
+    fn snapshot(&self, rect: &Rectangle) -> Snapshot {
+        let rect = rect.intersection(&self.rect()).unwrap_or_default();
+        let data = if rect.area() > 0 {
+            let s_rect = Some(SdlRect::new(rect.min.x, rect.min.y,
+                                           rect.width(), rect.height()));
+            self.canvas.read_pixels(s_rect, PixelFormatEnum::RGB24).unwrap_or_default()
+        } else {
+            Vec::new()
+        };
+        Snapshot { rect, data }
+    }
+
+    fn restore(&mut self, snapshot: &Snapshot) {
+        let rect = snapshot.rect;
+        let mut colors = snapshot.data.chunks(3);
+        for y in rect.min.y..rect.max.y {
+            for x in rect.min.x..rect.max.x {
+                if let Some(rgb) = colors.next().filter(|rgb| rgb.len() == 3) {
+                    self.canvas.set_draw_color(SdlColor::RGB(rgb[0], rgb[1], rgb[2]));
+                    self.canvas.draw_point(SdlPoint::new(x, y)).unwrap();
+                }
+            }
+        }
+    }
+
     fn dims(&self) -> (u32, u32) {
-        self.window().size()
+        self.canvas.window().size()
     }
 }
 
@@ -207,8 +286,8 @@ pub fn run() -> Result<()> {
                  .build()
                  .unwrap();
 
-    let mut fb = window.into_canvas().software().build().unwrap();
-    fb.set_blend_mode(BlendMode::Blend);
+    let mut fb = EmulatorFramebuffer::new(window.into_canvas().software().build().unwrap());
+    fb.set_gamma(context.settings.text_gamma);
 
     let (tx, rx) = mpsc::channel();
     let (ty, ry) = mpsc::channel();
@@ -224,8 +303,8 @@ pub fn run() -> Result<()> {
     let tx3 = tx.clone();
     thread::spawn(move || {
         loop {
-            thread::sleep(CLOCK_REFRESH_INTERVAL);
-            tx3.send(Event::ClockTick).unwrap();
+            thread::sleep(STATUS_REFRESH_INTERVAL);
+            tx3.send(Event::RefreshStatus).unwrap();
         }
     });
 
@@ -235,6 +314,8 @@ pub fn run() -> Result<()> {
     let mut view: Box<View> = Box::new(Home::new(fb_rect, &tx, &mut context)?);
 
     let mut updating = FnvHashMap::default();
+    let mut pending_updates = Vec::new();
+    let mut pending_since = None;
 
     if context.settings.frontlight {
         let levels = context.settings.frontlight_levels;
@@ -304,24 +385,24 @@ pub fn run() -> Result<()> {
         }
 
         while let Ok(evt) = rx.recv_timeout(Duration::from_millis(20)) {
+            if pending_since.map_or(false, |t: Instant| t.elapsed() >= MAX_UPDATE_DELAY) {
+                flush_updates(&mut fb, &mut pending_updates, &mut pending_since, &mut updating);
+            }
             match evt {
                 Event::Render(mut rect, mode) => {
                     render(view.as_ref(), &mut rect, &mut fb, &mut context.fonts, &mut updating);
-                    if let Ok(tok) = fb.update(&rect, mode) {
-                        updating.insert(tok, rect);
-                    }
+                    coalesce_update(&mut pending_updates, rect, mode);
+                    pending_since.get_or_insert_with(Instant::now);
                 },
                 Event::RenderNoWait(mut rect, mode) => {
                     render_no_wait(view.as_ref(), &mut rect, &mut fb, &mut context.fonts, &mut updating);
-                    if let Ok(tok) = fb.update(&rect, mode) {
-                        updating.insert(tok, rect);
-                    }
+                    coalesce_update(&mut pending_updates, rect, mode);
+                    pending_since.get_or_insert_with(Instant::now);
                 },
                 Event::Expose(mut rect) => {
                     fill_crack(view.as_ref(), &mut rect, &mut fb, &mut context.fonts, &mut updating);
-                    if let Ok(tok) = fb.update(&rect, UpdateMode::Gui) {
-                        updating.insert(tok, rect);
-                    }
+                    coalesce_update(&mut pending_updates, rect, UpdateMode::Gui);
+                    pending_since.get_or_insert_with(Instant::now);
                 },
                 Event::Open(info) => {
                     let info2 = info.clone();
@@ -337,6 +418,20 @@ pub fn run() -> Result<()> {
                     history.push(view as Box<View>);
                     view = Box::new(r) as Box<View>;
                 },
+                Event::RefreshStatus => {
+                    // The status is also refreshed when the device resumes.
+                    // The levels of the opened book take precedence over the schedule.
+                    let overridden = view.downcast_ref::<Reader>().map_or(false, |r| r.overrides_frontlight());
+                    if context.settings.frontlight && !overridden && locate::<FrontlightWindow>(view.as_ref()).is_none() {
+                        let now = (context.clock)();
+                        let schedule = resolve_schedule(&context.settings.frontlight_schedule, now.date().naive_local(),
+                                                        context.settings.location.as_ref(), now.offset().local_minus_utc());
+                        apply_schedule(context.frontlight.as_mut(), &schedule, &now);
+                        apply_warmth_ramp(context.frontlight.as_mut(), context.settings.frontlight_warmth_ramp.as_ref(),
+                                          &context.settings.night_hours, &now);
+                    }
+                    handle_event(view.as_mut(), &evt, &tx, &mut bus, &mut context);
+                },
                 Event::Back => {
                     if let Some(v) = history.pop() {
                         view = v;
@@ -344,34 +439,85 @@ pub fn run() -> Result<()> {
                     }
                 },
                 Event::TogglePresetMenu(rect, index) => {
-                    if let Some(index) = locate_by_id(view.as_ref(), ViewId::PresetMenu) {
-                        let rect = *view.child(index).rect();
-                        view.children_mut().remove(index);
-                        tx.send(Event::Expose(rect)).unwrap();
-                    } else {
-                        let preset_menu = Menu::new(rect, ViewId::PresetMenu, MenuKind::Contextual,
-                                                    vec![EntryKind::Command("Remove".to_string(),
-                                                                            EntryId::RemovePreset(index))],
-                                                    &mut context.fonts);
-                        tx.send(Event::Render(*preset_menu.rect(), UpdateMode::Gui)).unwrap();
-                        view.children_mut().push(Box::new(preset_menu) as Box<View>);
-                    }
+                    toggle_preset_menu(view.as_mut(), rect, index, &tx, &mut context);
                 },
-                Event::Show(ViewId::Frontlight) => {
+                Event::Show(ViewId::Frontlight) | Event::ShowNear(ViewId::Frontlight, _) => {
                     if !context.settings.frontlight {
                         continue;
                     }
-                    let flw = FrontlightWindow::new(&mut context);
+                    let anchor = match evt {
+                        Event::ShowNear(_, pt) if context.settings.frontlight_anchored => Some(pt),
+                        _ => None,
+                    };
+                    // Let the warmth slider reflect the ramp.
+                    let now = (context.clock)();
+                    apply_warmth_ramp(context.frontlight.as_mut(), context.settings.frontlight_warmth_ramp.as_ref(),
+                                      &context.settings.night_hours, &now);
+                    let flw = FrontlightWindow::new(&mut context, anchor);
                     tx.send(Event::Render(*flw.rect(), UpdateMode::Gui)).unwrap();
                     view.children_mut().push(Box::new(flw) as Box<View>);
                 },
+                Event::Show(ViewId::ConfirmRemovePreset) => {
+                    let confirm = Confirmation::new(ViewId::ConfirmRemovePreset,
+                                                    Event::RemovePreset,
+                                                    "Remove this preset?".to_string(),
+                                                    &mut context.fonts);
+                    tx.send(Event::Render(*confirm.rect(), UpdateMode::Gui)).unwrap();
+                    view.children_mut().push(Box::new(confirm) as Box<View>);
+                },
+                Event::Show(ViewId::ConfirmSkipDuplicates) => {
+                    let confirm = Confirmation::new(ViewId::ConfirmSkipDuplicates,
+                                                    Event::SkipDuplicatePresets(true),
+                                                    "Skip the duplicated presets?".to_string(),
+                                                    &mut context.fonts)
+                                              .cancel_event(Event::SkipDuplicatePresets(false));
+                    tx.send(Event::Render(*confirm.rect(), UpdateMode::Gui)).unwrap();
+                    view.children_mut().push(Box::new(confirm) as Box<View>);
+                },
+                Event::PresetsMerged(count) => {
+                    let msg = if count == 1 {
+                        "Merged one duplicated preset.".to_string()
+                    } else {
+                        format!("Merged {} duplicated presets.", count)
+                    };
+                    let notif = Notification::new(ViewId::MergedPresetsNotif,
+                                                  msg,
+                                                  &mut context.notification_index,
+                                                  &mut context.fonts,
+                                                  &tx);
+                    view.children_mut().push(Box::new(notif) as Box<View>);
+                },
+                Event::Show(ViewId::RenamePreset) => {
+                    let rename_preset = NamedInput::new("Rename preset".to_string(),
+                                                        ViewId::RenamePreset,
+                                                        ViewId::RenamePresetInput,
+                                                        12,
+                                                        &mut context.fonts);
+                    tx.send(Event::Render(*rename_preset.rect(), UpdateMode::Gui)).unwrap();
+                    tx.send(Event::Focus(Some(ViewId::RenamePresetInput))).unwrap();
+                    view.children_mut().push(Box::new(rename_preset) as Box<View>);
+                },
                 Event::Close(ViewId::Frontlight) => {
                     if let Some(index) = locate::<FrontlightWindow>(view.as_ref()) {
+                        let adjusted = view.child_mut(index).downcast_mut::<FrontlightWindow>()
+                                           .map_or(false, |flw| flw.close(&tx, &mut context));
+                        if let Some(r) = view.downcast_mut::<Reader>() {
+                            r.frontlight_adjusted |= adjusted;
+                        }
                         let rect = *view.child(index).rect();
                         view.children_mut().remove(index);
                         tx.send(Event::Expose(rect)).unwrap();
                     }
                 },
+                Event::Close(ViewId::RenamePreset) => {
+                    // Let the frontlight window know that the renaming is over.
+                    handle_event(view.as_mut(), &evt, &tx, &mut bus, &mut context);
+                    if let Some(index) = locate_by_id(view.as_ref(), ViewId::RenamePreset) {
+                        let rect = overlapping_rectangle(view.child(index));
+                        tx.send(Event::Expose(rect)).unwrap();
+                        view.children_mut().remove(index);
+                    }
+                },
                 Event::Close(id) => {
                     if let Some(index) = locate_by_id(view.as_ref(), id) {
                         let rect = overlapping_rectangle(view.child(index));
@@ -379,6 +525,12 @@ pub fn run() -> Result<()> {
                         view.children_mut().remove(index);
                     }
                 },
+                Event::ToggleFrontlight | Event::Select(EntryId::ToggleFrontlight) => {
+                    context.toggle_frontlight();
+                    if let Event::Select(..) = evt {
+                        handle_event(view.as_mut(), &evt, &tx, &mut bus, &mut context);
+                    }
+                },
                 Event::Select(EntryId::ToggleInverted) => {
                     fb.toggle_inverted();
                     context.inverted = !context.inverted;
@@ -414,6 +566,8 @@ pub fn run() -> Result<()> {
                 tx.send(ce).unwrap();
             }
         }
+
+        flush_updates(&mut fb, &mut pending_updates, &mut pending_since, &mut updating);
     }
 
     if context.settings.frontlight {
@@ -0,0 +1,47 @@
+error_chain!{
+    foreign_links {
+        Io(::std::io::Error);
+        ParseInt(::std::num::ParseIntError);
+    }
+    errors {
+        SensorNotFound(path: String) {
+            description("light sensor not found")
+            display("light sensor not found: {}", path)
+        }
+
+        SensorPermissionDenied(path: String) {
+            description("light sensor access denied")
+            display("light sensor access denied: {}", path)
+        }
+
+        SensorParse(value: String) {
+            description("invalid light sensor value")
+            display("invalid light sensor value: {:?}", value)
+        }
+
+        SensorUndetected(pattern: String) {
+            description("no light sensor detected")
+            display("no light sensor detected at {}", pattern)
+        }
+
+        FrontlightNotFound(node: String) {
+            description("frontlight node not found")
+            display("frontlight node not found: {}", node)
+        }
+
+        FrontlightPermissionDenied(node: String) {
+            description("frontlight node access denied")
+            display("frontlight node access denied: {}", node)
+        }
+
+        FrontlightParse(node: String) {
+            description("invalid frontlight value")
+            display("invalid frontlight value in {}", node)
+        }
+
+        FrontlightWriteFailed(node: String) {
+            description("can't write to frontlight node")
+            display("can't write to frontlight node: {}", node)
+        }
+    }
+}
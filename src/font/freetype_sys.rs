@@ -9,6 +9,10 @@ pub const FT_LOAD_NO_SCALE: i32 = 0x1 << 0;
 pub const FT_LOAD_NO_HINTING: i32 = 0x1 << 1;
 pub const FT_LOAD_RENDER: i32 = 0x1 << 2;
 
+pub const FT_FACE_FLAG_MULTIPLE_MASTERS: libc::c_long = 0x1 << 8;
+
+pub const FT_RENDER_MODE_NORMAL: FtRenderMode = 0;
+
 pub const FT_KERNING_DEFAULT: FtKerningMode = 0;
 
 pub const FT_GLYPH_BBOX_UNSCALED: GlyphBBoxMode = 0;
@@ -22,6 +26,7 @@ pub type FtFixed = libc::c_long;
 pub type FtGlyphFormat = libc::c_uint;
 pub type GlyphBBoxMode = libc::c_uint;
 pub type FtKerningMode = libc::c_uint;
+pub type FtRenderMode = libc::c_uint;
 pub type FtGenericFinalizer = extern fn(*mut libc::c_void);
 
 pub enum FtLibrary {}
@@ -43,9 +48,16 @@ extern {
     pub fn FT_Done_Face(face: *mut FtFace) -> FtError;
     pub fn FT_Set_Char_Size(face: *mut FtFace, sx: FtF26Dot6, sy: FtF26Dot6, rx: libc::c_uint, ry: libc::c_uint) -> FtError;
     pub fn FT_Load_Glyph(face: *const FtFace, idx: libc::c_uint, flags: i32) -> FtError;
+    pub fn FT_Render_Glyph(slot: *mut FtGlyphSlot, render_mode: FtRenderMode) -> FtError;
+    pub fn FT_GlyphSlot_Embolden(slot: *mut FtGlyphSlot);
+    pub fn FT_GlyphSlot_Oblique(slot: *mut FtGlyphSlot);
+    pub fn FT_Outline_Transform(outline: *const FtOutline, matrix: *const FtMatrix);
     pub fn FT_Load_Char(face: *const FtFace, code: libc::c_ulong, flags: i32) -> FtError;
     pub fn FT_Glyph_Get_CBox(glyph: *mut FtGlyph, bbox_mode: GlyphBBoxMode, acbox: *mut FtBBox);
     pub fn FT_Get_Char_Index(face: *const FtFace, code: libc::c_ulong) -> libc::c_uint;
+    pub fn FT_Get_MM_Var(face: *mut FtFace, amaster: *mut *mut FtMMVar) -> FtError;
+    pub fn FT_Done_MM_Var(lib: *mut FtLibrary, amaster: *mut FtMMVar) -> FtError;
+    pub fn FT_Set_Var_Design_Coordinates(face: *mut FtFace, num_coords: libc::c_uint, coords: *mut FtFixed) -> FtError;
     pub fn FT_Get_Kerning(face: *const FtFace, l_glyph: libc::c_uint, r_glyph: libc::c_uint, kern_mode: FtKerningMode, kerning: *mut FtVector) -> FtError;
 }
 
@@ -76,7 +88,7 @@ pub struct FtSizeMetrics {
     pub y_ppem: libc::c_ushort,
 
     x_scale: FtFixed,
-    y_scale: FtFixed,
+    pub y_scale: FtFixed,
 
     ascender: FtPos,
     descender: FtPos,
@@ -98,6 +110,15 @@ pub struct FtVector {
     y: FtPos,
 }
 
+#[repr(C)]
+#[derive(Debug)]
+pub struct FtMatrix {
+    pub xx: FtFixed,
+    pub xy: FtFixed,
+    pub yx: FtFixed,
+    pub yy: FtFixed,
+}
+
 #[repr(C)]
 #[derive(Debug)]
 pub struct FtBBox {
@@ -168,7 +189,7 @@ pub struct FtGlyphSlot {
     pub bitmap_left: libc::c_int,
     pub bitmap_top: libc::c_int,
 
-    outline: FtOutline,
+    pub outline: FtOutline,
 
     num_subglyphs: libc::c_uint,
     subglyphs: *mut libc::c_void,
@@ -206,7 +227,7 @@ pub struct FtFace {
     num_faces: libc::c_long,
     face_index: libc::c_long,
 
-    face_flags: libc::c_long,
+    pub face_flags: libc::c_long,
     style_flags: libc::c_long,
 
     num_glyphs: libc::c_long,
@@ -224,7 +245,7 @@ pub struct FtFace {
 
     bbox: FtBBox,
 
-    units_per_em: libc::c_ushort,
+    pub units_per_em: libc::c_ushort,
     ascender: libc::c_short,
     descender: libc::c_short,
     height: libc::c_short,
@@ -250,3 +271,26 @@ pub struct FtFace {
 
     internal: *mut FtFaceInternal,
 }
+
+#[repr(C)]
+#[derive(Debug)]
+pub struct FtVarAxis {
+    name: *mut libc::c_char,
+
+    pub minimum: FtFixed,
+    pub def: FtFixed,
+    pub maximum: FtFixed,
+
+    pub tag: libc::c_ulong,
+    strid: libc::c_uint,
+}
+
+#[repr(C)]
+#[derive(Debug)]
+pub struct FtMMVar {
+    pub num_axis: libc::c_uint,
+    num_designs: libc::c_uint,
+    num_namedstyles: libc::c_uint,
+    pub axis: *mut FtVarAxis,
+    namedstyle: *mut libc::c_void,
+}
@@ -6,6 +6,7 @@ use std::mem;
 use font::freetype_sys::FtFace;
 
 pub const HB_DIRECTION_LTR: libc::c_uint = 4;
+pub const HB_DIRECTION_RTL: libc::c_uint = 5;
 
 pub type HbDirection = libc::c_uint;
 pub type HbTag = libc::uint32_t;
@@ -0,0 +1,127 @@
+use std::fs::File;
+use std::io::Read;
+use std::path::{Path, PathBuf};
+use fnv::FnvHashMap;
+use errors::*;
+
+pub const PATTERNS_DIRNAME: &str = "hyphenation";
+
+// The minimum number of letters before and after a hyphen.
+const LEFT_MIN: usize = 2;
+const RIGHT_MIN: usize = 3;
+
+// Finds the break points of words with Liang's algorithm.
+pub struct Hyphenator {
+    // The inter-letter values of each pattern, indexed by its letters.
+    patterns: FnvHashMap<String, Vec<u8>>,
+    max_len: usize,
+}
+
+// The pattern file of *language*, e.g. *hyphenation/en-us.pat*.
+pub fn patterns_path(language: &str) -> PathBuf {
+    Path::new(PATTERNS_DIRNAME).join(format!("{}.pat", language))
+}
+
+impl Hyphenator {
+    pub fn load<P: AsRef<Path>>(path: P) -> Result<Hyphenator> {
+        let mut file = File::open(path.as_ref()).chain_err(|| "Can't open patterns file.")?;
+        let mut text = String::new();
+        file.read_to_string(&mut text)?;
+        let hyphenator = Hyphenator::parse(&text);
+        if hyphenator.patterns.is_empty() {
+            bail!("No patterns found in {}.", path.as_ref().display());
+        }
+        Ok(hyphenator)
+    }
+
+    // Reads TeX patterns such as *.ach4* or *1ba*, separated by white space.
+    // Comments start with *%*, the TeX commands and braces are ignored.
+    pub fn parse(text: &str) -> Hyphenator {
+        let mut patterns = FnvHashMap::default();
+        let mut max_len = 0;
+        for line in text.lines() {
+            let line = line.split('%').next().unwrap_or("");
+            for token in line.split_whitespace() {
+                if token.contains(|c: char| c == '\\' || c == '{' || c == '}') {
+                    continue;
+                }
+                let mut letters = String::new();
+                let mut values = vec![0];
+                for c in token.chars() {
+                    if let Some(d) = c.to_digit(10) {
+                        *values.last_mut().unwrap() = d as u8;
+                    } else {
+                        letters.extend(c.to_lowercase());
+                        values.push(0);
+                    }
+                }
+                if letters.is_empty() {
+                    continue;
+                }
+                max_len = max_len.max(values.len() - 1);
+                patterns.insert(letters, values);
+            }
+        }
+        Hyphenator { patterns, max_len }
+    }
+
+    // Returns the byte offsets, within *word*, where a hyphen can be inserted.
+    // Only the leading run of letters is considered, so that the trailing
+    // punctuation stays attached to the last syllable.
+    pub fn break_points(&self, word: &str) -> Vec<usize> {
+        let letters: Vec<(usize, char)> = word.char_indices()
+                                              .take_while(|&(_, c)| c.is_alphabetic())
+                                              .collect();
+        let count = letters.len();
+        if count < LEFT_MIN + RIGHT_MIN {
+            return Vec::new();
+        }
+
+        let mut dotted = vec!['.'];
+        for &(_, c) in &letters {
+            dotted.extend(c.to_lowercase());
+        }
+        dotted.push('.');
+
+        // A letter can lower-case to several characters, give up in this case.
+        if dotted.len() != count + 2 {
+            return Vec::new();
+        }
+
+        let mut values = vec![0u8; dotted.len() + 1];
+        let mut key = String::new();
+        for i in 0..dotted.len() {
+            key.clear();
+            for j in i..dotted.len().min(i + self.max_len) {
+                key.push(dotted[j]);
+                if let Some(pattern) = self.patterns.get(&key) {
+                    for (k, &v) in pattern.iter().enumerate() {
+                        values[i + k] = values[i + k].max(v);
+                    }
+                }
+            }
+        }
+
+        // The value between the letters *k-1* and *k* sits at *k+1*,
+        // because of the leading dot.
+        (LEFT_MIN..=count - RIGHT_MIN).filter(|&k| values[k + 1] % 2 == 1)
+                                      .map(|k| letters[k].0)
+                                      .collect()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::Hyphenator;
+
+    #[test]
+    fn hyphenate_with_liang_patterns() {
+        let hyphenator = Hyphenator::parse("% From Liang's thesis.\n\
+                                            hy3ph he2n hena4 hen5at\n\
+                                            1na n2at 1tio 2io o2n");
+        assert_eq!(hyphenator.break_points("hyphenation"), vec![2, 6]);
+        assert_eq!(hyphenator.break_points("Hyphenation,"), vec![2, 6]);
+        assert_eq!(hyphenator.break_points("nation"), vec![2]);
+        assert!(hyphenator.break_points("hen").is_empty());
+    }
+}
@@ -2,17 +2,28 @@ extern crate libc;
 
 mod harfbuzz_sys;
 mod freetype_sys;
+mod hyphenation;
 
 use font::harfbuzz_sys::*;
 use font::freetype_sys::*;
 
 use std::ptr;
+use std::slice;
+use std::iter;
+use std::ops::Range;
+use std::cell::RefCell;
 use std::ffi::CString;
 use std::os::unix::ffi::OsStrExt;
-use std::path::Path;
+use std::fs;
+use std::path::{Path, PathBuf};
 use std::rc::Rc;
+use std::collections::BTreeMap;
+use fnv::FnvHashMap;
 use geom::Point;
+use unit::scale_by_dpi_raw;
+use settings::{UserFonts, DEFAULT_GLYPH_CACHE_CAPACITY};
 use framebuffer::Framebuffer;
+pub use font::hyphenation::{Hyphenator, patterns_path};
 
 // Default font size in points
 pub const DEFAULT_FONT_SIZE: f32 = 11.0;
@@ -25,40 +36,85 @@ pub const KEYBOARD_FONT_SIZES: [u32; 2] = [337, 843];
 
 pub const DISPLAY_FONT_SIZE: u32 = 2516;
 
+// The tags of the registered axes of the variable fonts.
+const WEIGHT_AXIS: u32 = 0x7767_6874; // wght
+const WIDTH_AXIS: u32 = 0x7764_7468; // wdth
+const OPTICAL_SIZE_AXIS: u32 = 0x6F70_737A; // opsz
+
+// The weight of the bold styles drawn with a variable font.
+const BOLD_WEIGHT: u16 = 700;
+
+pub const DEFAULT_AXES: Axes = Axes {
+    weight: None,
+    width: None,
+    optical_size: None,
+};
+
+// Sets of OpenType features given to the shaper.
+pub const NO_LIGATURES: &str = "-liga -clig";
+pub const TABULAR_NUMERALS: &str = "lnum tnum";
+
 pub const NORMAL_STYLE: Style = Style {
     family: Family::SansSerif,
     variant: Variant::REGULAR,
     size: FONT_SIZES[1],
+    axes: DEFAULT_AXES,
+    tracking: 0.0,
+    features: None,
+};
+
+pub const HEADER_STYLE: Style = Style {
+    family: Family::SansSerif,
+    variant: Variant::BOLD,
+    size: FONT_SIZES[1],
+    axes: DEFAULT_AXES,
+    tracking: 0.0,
+    features: None,
 };
 
 pub const KBD_CHAR: Style = Style {
     family: Family::Keyboard,
     variant: Variant::REGULAR,
     size: KEYBOARD_FONT_SIZES[1],
+    axes: DEFAULT_AXES,
+    tracking: 0.0,
+    features: None,
 };
 
 pub const KBD_LABEL: Style = Style {
     family: Family::Keyboard,
     variant: Variant::REGULAR,
     size: FONT_SIZES[0],
+    axes: DEFAULT_AXES,
+    tracking: 4.0,
+    features: Some(NO_LIGATURES),
 };
 
 pub const DISPLAY_STYLE: Style = Style {
     family: Family::Display,
     variant: Variant::REGULAR,
     size: DISPLAY_FONT_SIZE,
+    axes: DEFAULT_AXES,
+    tracking: 0.0,
+    features: None,
 };
 
 pub const MD_TITLE: Style = Style {
     family: Family::Serif,
     variant: Variant::ITALIC,
     size: FONT_SIZES[2],
+    axes: DEFAULT_AXES,
+    tracking: 0.0,
+    features: None,
 };
 
 pub const MD_AUTHOR: Style = Style {
     family: Family::Serif,
     variant: Variant::REGULAR,
     size: FONT_SIZES[1],
+    axes: DEFAULT_AXES,
+    tracking: 0.0,
+    features: None,
 };
 
 pub const MD_YEAR: Style = NORMAL_STYLE;
@@ -67,15 +123,29 @@ pub const MD_KIND: Style = Style {
     family: Family::SansSerif,
     variant: Variant::BOLD,
     size: FONT_SIZES[0],
+    axes: DEFAULT_AXES,
+    tracking: 3.0,
+    features: None,
 };
 
 pub const MD_SIZE: Style = Style {
     family: Family::SansSerif,
     variant: Variant::REGULAR,
     size: FONT_SIZES[0],
+    axes: DEFAULT_AXES,
+    tracking: 0.0,
+    features: None,
 };
 
-pub const SLIDER_VALUE: Style = MD_SIZE;
+// The digits don't move as the value changes.
+pub const SLIDER_VALUE: Style = Style {
+    family: Family::SansSerif,
+    variant: Variant::REGULAR,
+    size: FONT_SIZES[0],
+    axes: DEFAULT_AXES,
+    tracking: 0.0,
+    features: Some(TABULAR_NUMERALS),
+};
 
 const CATEGORY_DEPTH_LIMIT: usize = 5;
 
@@ -97,6 +167,8 @@ pub struct Fonts {
     serif: FontFamily,
     keyboard: Font,
     display: Font,
+    fallbacks: Rc<RefCell<Vec<Font>>>,
+    pub hyphenator: Option<Rc<Hyphenator>>,
 }
 
 impl Fonts {
@@ -117,8 +189,115 @@ impl Fonts {
             },
             keyboard: fo.open("fonts/VarelaRound-Regular.ttf")?,
             display: fo.open("fonts/Cormorant-Regular.ttf")?,
+            fallbacks: Rc::new(RefCell::new(Vec::new())),
+            hyphenator: None,
         })
     }
+
+    // The instances of the fonts at other axes are included.
+    fn each_font<F>(&mut self, mut f: F) where F: FnMut(&mut Font) {
+        let mut fonts = Vec::new();
+        for family in vec![&mut self.sans_serif, &mut self.serif] {
+            let FontFamily { regular, italic, bold, bold_italic } = family;
+            fonts.extend(vec![regular, italic, bold, bold_italic]);
+        }
+        fonts.push(&mut self.keyboard);
+        fonts.push(&mut self.display);
+        for font in fonts {
+            f(font);
+            for instance in font.instances.values_mut() {
+                f(instance);
+            }
+        }
+    }
+
+    pub fn set_glyph_cache_capacity(&mut self, capacity: usize) {
+        self.each_font(|font| font.glyph_cache.set_capacity(capacity));
+        for font in self.fallbacks.borrow_mut().iter_mut() {
+            font.glyph_cache.set_capacity(capacity);
+        }
+    }
+
+    // Replaces the sans serif fonts with the ones found in the directory of *user_fonts*.
+    // The built-in fonts are kept for the styles whose files can't be loaded.
+    pub fn load_user_fonts(&mut self, user_fonts: &UserFonts) {
+        let mut names: Vec<String> = match fs::read_dir(&user_fonts.path) {
+            Ok(entries) => entries.filter_map(|entry| entry.ok())
+                                  .filter_map(|entry| entry.file_name().into_string().ok())
+                                  .filter(|name| is_font_file(name))
+                                  .collect(),
+            Err(e) => {
+                eprintln!("Warning: can't read {}: {}", user_fonts.path.display(), e);
+                Vec::new()
+            },
+        };
+        names.sort();
+        let fo = match FontOpener::new() {
+            Ok(fo) => fo,
+            Err(e) => {
+                eprintln!("Warning: can't load user fonts: {}", e);
+                return;
+            },
+        };
+        let slots = [(Variant::REGULAR, &user_fonts.regular),
+                     (Variant::ITALIC, &user_fonts.italic),
+                     (Variant::BOLD, &user_fonts.bold),
+                     (Variant::BOLD | Variant::ITALIC, &user_fonts.bold_italic)];
+        let mut loaded = Vec::new();
+        let mut missing = Vec::new();
+        for &(variant, name) in &slots {
+            let name = name.as_ref().or_else(|| {
+                names.iter().find(|name| variant_from_name(name) == Some(variant))
+            });
+            if let Some(name) = name {
+                let path = user_fonts.path.join(name);
+                match fo.open(&path) {
+                    Ok(font) => {
+                        *font_from_variant(&mut self.sans_serif, variant) = font;
+                        loaded.push((variant, path));
+                    },
+                    Err(e) => eprintln!("Warning: can't load {}: {}", name, e),
+                }
+            } else {
+                missing.push(variant);
+            }
+        }
+        // The styles without files are synthesized from the closest loaded face.
+        let variants: Vec<Variant> = loaded.iter().map(|&(variant, _)| variant).collect();
+        for variant in missing {
+            if let Some(base) = closest_variant(variant, &variants) {
+                let path = &loaded.iter().find(|&&(v, _)| v == base).unwrap().1;
+                match fo.open(path) {
+                    Ok(mut font) => {
+                        font.synthetic = variant - base;
+                        // A variable face reaches the bold weight on its weight axis.
+                        if font.synthetic.contains(Variant::BOLD) && font.has_axis(WEIGHT_AXIS) {
+                            font.synthetic.remove(Variant::BOLD);
+                            font.default_weight = Some(BOLD_WEIGHT);
+                        }
+                        *font_from_variant(&mut self.sans_serif, variant) = font;
+                    },
+                    Err(e) => eprintln!("Warning: can't load {}: {}", path.display(), e),
+                }
+            } else {
+                eprintln!("Warning: no user font for the {:?} style, the built-in one is kept.", variant);
+            }
+        }
+    }
+
+    // The fonts at *paths* are consulted, in order, for the characters missing from every style.
+    pub fn set_fallbacks<P: AsRef<Path>>(&mut self, paths: &[P]) -> Result<()> {
+        let fo = FontOpener::new()?;
+        let mut fallbacks = paths.iter().map(|path| fo.open(path)).collect::<Result<Vec<Font>>>()?;
+        let capacity = self.keyboard.glyph_cache.capacity;
+        for font in &mut fallbacks {
+            font.glyph_cache.set_capacity(capacity);
+        }
+        self.fallbacks = Rc::new(RefCell::new(fallbacks));
+        let fallbacks = self.fallbacks.clone();
+        self.each_font(|font| font.fallbacks = fallbacks.clone());
+        Ok(())
+    }
 }
 
 bitflags! {
@@ -141,6 +320,133 @@ pub struct Style {
     family: Family,
     variant: Variant,
     pub size: u32,
+    pub axes: Axes,
+    // The space added after each glyph but the last, in pixels at 300 DPI.
+    pub tracking: f32,
+    // The OpenType features, e.g. *smcp* or *-liga*, separated by spaces.
+    pub features: Option<&'static str>,
+}
+
+// The coordinates of a style on the axes of a variable font:
+// the weight ranges from 100 to 900, the width is a percentage of the normal width
+// and the optical size is in points. The axes left unset keep their default values.
+#[derive(Debug, Copy, Clone, Default, Eq, PartialEq, Hash)]
+pub struct Axes {
+    pub weight: Option<u16>,
+    pub width: Option<u16>,
+    pub optical_size: Option<u16>,
+}
+
+// The design coordinates, in 16.16 fixed point, of *axes* for a variable font
+// whose axes are given by their tags, minimums, defaults and maximums.
+pub fn design_coordinates(axes: &Axes, ranges: &[(u32, FtFixed, FtFixed, FtFixed)]) -> Vec<FtFixed> {
+    ranges.iter().map(|&(tag, minimum, default, maximum)| {
+        let value = match tag {
+            WEIGHT_AXIS => axes.weight,
+            WIDTH_AXIS => axes.width,
+            OPTICAL_SIZE_AXIS => axes.optical_size,
+            _ => None,
+        };
+        value.map_or(default, |v| ((v as FtFixed) << 16).max(minimum).min(maximum))
+    }).collect()
+}
+
+// The static variant closest to the weight of *axes*, for the fonts that aren't variable.
+// Their width is imitated by scaling the glyphs, see *Font::set_axes*, and their optical
+// size, which can't be imitated, is the one they were designed for.
+pub fn nearest_variant(variant: Variant, axes: &Axes) -> Variant {
+    match axes.weight {
+        Some(weight) if weight >= 600 => variant | Variant::BOLD,
+        Some(_) => variant - Variant::BOLD,
+        None => variant,
+    }
+}
+
+// The number of leading glyphs kept and the index of the first trailing glyph kept
+// when the middle of a line, made of glyphs with the given *advances*, is cut to fit *max_width*.
+pub fn middle_cut(advances: &[u32], max_width: u32) -> (usize, usize) {
+    let len = advances.len();
+    let (mut head, mut tail) = (0, len);
+    let mut width = 0;
+    // Glyphs are taken alternately from both ends.
+    while head < tail {
+        let index = if head <= len - tail { head } else { tail - 1 };
+        if width + advances[index] > max_width {
+            break;
+        }
+        width += advances[index];
+        if index == head {
+            head += 1;
+        } else {
+            tail -= 1;
+        }
+    }
+    (head, tail)
+}
+
+// Whether the first strongly directional character of *text* is written from right to left.
+pub fn is_rtl(text: &str) -> bool {
+    for c in text.chars() {
+        match c {
+            '\u{0590}'..='\u{08FF}' | '\u{FB1D}'..='\u{FDFF}' | '\u{FE70}'..='\u{FEFF}' => return true,
+            _ if c.is_alphabetic() => return false,
+            _ => (),
+        }
+    }
+    false
+}
+
+// Splits *text* into runs of characters drawn with the same face, given *faces_count* faces
+// tried in order. *covers(face, c)* tells whether *face* has a glyph for *c*, and the first
+// face draws the characters that no face covers. A character covered by the face of the
+// current run stays in it, hence spaces and punctuation don't break the runs.
+pub fn fallback_runs<F>(text: &str, faces_count: usize, mut covers: F) -> Vec<(usize, Range<usize>)> where F: FnMut(usize, char) -> bool {
+    let mut runs: Vec<(usize, Range<usize>)> = Vec::new();
+    for (i, c) in text.char_indices() {
+        let end = i + c.len_utf8();
+        if let Some(run) = runs.last_mut() {
+            if covers(run.0, c) {
+                run.1.end = end;
+                continue;
+            }
+        }
+        let face = (0..faces_count).find(|&face| covers(face, c)).unwrap_or(0);
+        match runs.last_mut() {
+            Some(run) if run.0 == face => run.1.end = end,
+            _ => runs.push((face, i..end)),
+        }
+    }
+    runs
+}
+
+fn is_font_file(name: &str) -> bool {
+    Path::new(name).extension().and_then(|ext| ext.to_str())
+                   .map_or(false, |ext| ext.eq_ignore_ascii_case("ttf") || ext.eq_ignore_ascii_case("otf"))
+}
+
+// The variant of a font file named after its style, e.g. *Inter-BoldItalic.otf*.
+// The files of the other weights and widths have no variant.
+pub fn variant_from_name(name: &str) -> Option<Variant> {
+    const OTHER_STYLES: [&str; 11] = ["thin", "light", "medium", "semibold", "demibold", "extrabold",
+                                      "ultrabold", "black", "heavy", "condensed", "expanded"];
+    let stem = Path::new(name).file_stem()?.to_str()?.to_lowercase();
+    if OTHER_STYLES.iter().any(|style| stem.contains(style)) {
+        return None;
+    }
+    let mut variant = Variant::REGULAR;
+    if stem.contains("bold") {
+        variant |= Variant::BOLD;
+    }
+    if stem.contains("italic") || stem.contains("oblique") {
+        variant |= Variant::ITALIC;
+    }
+    Some(variant)
+}
+
+// The available variant, included in *variant*, that has the most styles in common with it.
+pub fn closest_variant(variant: Variant, available: &[Variant]) -> Option<Variant> {
+    available.iter().filter(|v| variant.contains(**v))
+             .max_by_key(|v| v.bits().count_ones()).cloned()
 }
 
 pub fn font_from_variant(family: &mut FontFamily, variant: Variant) -> &mut Font {
@@ -159,19 +465,35 @@ pub fn font_from_style<'a>(fonts: &'a mut Fonts, style: &Style, dpi: u16) -> &'a
     let font = match style.family {
         Family::SansSerif => {
             let family = &mut fonts.sans_serif;
-            font_from_variant(family, style.variant)
+            let variant = family.variant_for(style);
+            font_from_variant(family, variant)
         },
         Family::Serif => {
             let family = &mut fonts.serif;
-            font_from_variant(family, style.variant)
+            let variant = family.variant_for(style);
+            font_from_variant(family, variant)
         },
         Family::Keyboard => &mut fonts.keyboard,
         Family::Display => &mut fonts.display,
     };
+    let font = font.instance(&style.axes);
     font.set_size(style.size, dpi);
+    font.tracking = scale_by_dpi_raw(style.tracking, dpi).round() as u32;
+    font.features = style.features;
     font
 }
 
+impl FontFamily {
+    // A variable font renders every weight, the others need the nearest static variant.
+    fn variant_for(&mut self, style: &Style) -> Variant {
+        if font_from_variant(self, style.variant).variable {
+            style.variant
+        } else {
+            nearest_variant(style.variant, &style.axes)
+        }
+    }
+}
+
 pub struct FontLibrary(*mut FtLibrary);
 
 pub struct FontOpener(Rc<FontLibrary>);
@@ -187,6 +509,90 @@ pub struct Font {
     // lowercase and uppercase x heights
     pub x_heights: (u32, u32),
     space_codepoint: u32,
+    glyph_cache: GlyphCache,
+    // Whether the face has axes of variation.
+    variable: bool,
+    // The styles imitated by transforming the glyphs of the face.
+    synthetic: Variant,
+    // The weight of a variable font when the style doesn't give one.
+    default_weight: Option<u16>,
+    axes: Axes,
+    // The file of the face, from which the instances are opened.
+    path: Option<PathBuf>,
+    // The instances of the face at the other axes.
+    instances: FnvHashMap<Axes, Font>,
+    // The space, in pixels, added after each glyph of the plans but the last.
+    tracking: u32,
+    // The features applied before the ones given to *plan*.
+    features: Option<&'static str>,
+    // Consulted, in order, for the characters missing from this font.
+    fallbacks: Rc<RefCell<Vec<Font>>>,
+}
+
+// The coverage of the pixels of a rendered glyph, and its position relative to the pen.
+#[derive(Debug, Clone)]
+pub struct GlyphBitmap {
+    pub left: i32,
+    pub top: i32,
+    pub width: i32,
+    pub height: i32,
+    pub buf: Vec<u8>,
+}
+
+// The codepoint, size, resolution and axes of a glyph. Each font has its own cache,
+// hence the style of the glyph is implied.
+type GlyphKey = (u32, u32, u16, Axes);
+
+// The most recently used glyphs of a font.
+pub struct GlyphCache {
+    capacity: usize,
+    tick: u64,
+    entries: FnvHashMap<GlyphKey, (GlyphBitmap, u64)>,
+    // The keys of the entries, by last use, so that the eviction doesn't scan the entries.
+    uses: BTreeMap<u64, GlyphKey>,
+}
+
+impl GlyphCache {
+    pub fn new(capacity: usize) -> GlyphCache {
+        GlyphCache {
+            capacity,
+            tick: 0,
+            entries: FnvHashMap::default(),
+            uses: BTreeMap::new(),
+        }
+    }
+
+    // At least one glyph is kept, whatever the capacity.
+    pub fn set_capacity(&mut self, capacity: usize) {
+        self.capacity = capacity;
+        while self.entries.len() > capacity.max(1) {
+            self.evict();
+        }
+    }
+
+    fn evict(&mut self) {
+        let oldest = self.uses.keys().next().cloned();
+        if let Some(tick) = oldest {
+            let key = self.uses.remove(&tick).unwrap();
+            self.entries.remove(&key);
+        }
+    }
+
+    // Calls *rasterize* when the glyph isn't cached.
+    pub fn get_or_insert_with<F>(&mut self, key: GlyphKey, rasterize: F) -> &GlyphBitmap where F: FnOnce() -> GlyphBitmap {
+        self.tick += 1;
+        if !self.entries.contains_key(&key) {
+            if self.entries.len() >= self.capacity.max(1) {
+                self.evict();
+            }
+            self.entries.insert(key, (rasterize(), 0));
+        }
+        let entry = self.entries.get_mut(&key).unwrap();
+        self.uses.remove(&entry.1);
+        self.uses.insert(self.tick, key);
+        entry.1 = self.tick;
+        &entry.0
+    }
 }
 
 impl RenderPlan {
@@ -226,8 +632,14 @@ impl FontOpener {
             let ellipsis = RenderPlan::default();
             let x_heights = (0, 0);
             let space_codepoint = FT_Get_Char_Index(face, ' ' as libc::c_ulong);
+            let variable = (*face).face_flags & FT_FACE_FLAG_MULTIPLE_MASTERS != 0;
             Ok(Font { _lib: self.0.clone(), face, font,
-                      size: 0, dpi: 0, ellipsis, x_heights, space_codepoint })
+                      size: 0, dpi: 0, ellipsis, x_heights, space_codepoint,
+                      glyph_cache: GlyphCache::new(DEFAULT_GLYPH_CACHE_CAPACITY),
+                      variable, synthetic: Variant::REGULAR, default_weight: None,
+                      axes: DEFAULT_AXES, path: Some(path.as_ref().to_path_buf()),
+                      instances: FnvHashMap::default(), tracking: 0, features: None,
+                      fallbacks: Rc::new(RefCell::new(Vec::new())) })
         }
     }
 
@@ -242,8 +654,13 @@ impl FontOpener {
             let font = ptr::null_mut();
             let x_heights = (0, 0);
             let space_codepoint = FT_Get_Char_Index(face, ' ' as libc::c_ulong);
+            let variable = (*face).face_flags & FT_FACE_FLAG_MULTIPLE_MASTERS != 0;
             Ok(Font { _lib: self.0.clone(), face, font,
-                      size: 0, dpi: 0, ellipsis, x_heights, space_codepoint })
+                      size: 0, dpi: 0, ellipsis, x_heights, space_codepoint,
+                      glyph_cache: GlyphCache::new(DEFAULT_GLYPH_CACHE_CAPACITY),
+                      variable, synthetic: Variant::REGULAR, default_weight: None,
+                      axes: DEFAULT_AXES, path: None, instances: FnvHashMap::default(),
+                      tracking: 0, features: None, fallbacks: Rc::new(RefCell::new(Vec::new())) })
         }
     }
 }
@@ -262,12 +679,162 @@ impl Font {
             self.dpi = dpi;
             FT_Set_Char_Size(self.face, size as FtF26Dot6, 0, dpi as libc::c_uint, 0);
             self.font = hb_ft_font_create(self.face, ptr::null());
-            self.ellipsis = self.plan("…", None, None);
+            self.ellipsis = self.shape("…", None, HB_DIRECTION_LTR);
             self.x_heights = (self.height('x'), self.height('X'));
         }
     }
 
+    // The axes that matter to this font: a variable font follows all of them,
+    // the other fonts only keep the width, which is imitated by scaling their glyphs horizontally.
+    fn effective_axes(&self, axes: &Axes) -> Axes {
+        if self.variable {
+            Axes { weight: axes.weight.or(self.default_weight), .. *axes }
+        } else {
+            Axes { width: axes.width.filter(|&w| w != 100), .. DEFAULT_AXES }
+        }
+    }
+
+    // The font drawn at *axes*. The instances of the face at other axes than its own
+    // are opened once, and kept for the next styles with the same axes.
+    pub fn instance(&mut self, axes: &Axes) -> &mut Font {
+        let axes = self.effective_axes(axes);
+        if axes == self.axes {
+            return self;
+        }
+        if !self.instances.contains_key(&axes) {
+            let font = self.path.as_ref().and_then(|path| {
+                FontOpener(self._lib.clone()).open(path)
+                    .map_err(|e| eprintln!("Warning: can't open {}: {}", path.display(), e)).ok()
+            });
+            match font {
+                Some(mut font) => {
+                    font.synthetic = self.synthetic;
+                    font.default_weight = self.default_weight;
+                    font.glyph_cache.set_capacity(self.glyph_cache.capacity);
+                    font.fallbacks = self.fallbacks.clone();
+                    font.set_axes(&axes);
+                    self.instances.insert(axes, font);
+                },
+                // A font without a file is moved to the axes instead.
+                None => {
+                    self.set_axes(&axes);
+                    return self;
+                },
+            }
+        }
+        self.instances.get_mut(&axes).unwrap()
+    }
+
+    // Moves a variable font to the coordinates *axes*. The other fonts only keep the width.
+    pub fn set_axes(&mut self, axes: &Axes) {
+        let axes = self.effective_axes(axes);
+        if !self.variable {
+            if self.axes != axes {
+                self.axes = axes;
+                if !self.font.is_null() {
+                    self.ellipsis = self.shape("…", None, HB_DIRECTION_LTR);
+                }
+            }
+            return;
+        }
+        if self.axes == axes {
+            return;
+        }
+        let ranges = match self.axis_ranges() {
+            Some(ranges) => ranges,
+            None => return,
+        };
+        unsafe {
+            let mut coords = design_coordinates(&axes, &ranges);
+            FT_Set_Var_Design_Coordinates(self.face, coords.len() as libc::c_uint, coords.as_mut_ptr());
+            self.axes = axes;
+            // The shaping font and the metrics depend on the coordinates.
+            if !self.font.is_null() {
+                hb_font_destroy(self.font);
+                self.font = ptr::null_mut();
+                let (size, dpi) = (self.size, self.dpi);
+                self.set_size(size, dpi);
+            }
+        }
+    }
+
+    // The tags, minimums, defaults and maximums of the axes of a variable font.
+    fn axis_ranges(&self) -> Option<Vec<(u32, FtFixed, FtFixed, FtFixed)>> {
+        unsafe {
+            let mut mm_var = ptr::null_mut();
+            if FT_Get_MM_Var(self.face, &mut mm_var) != FT_ERR_OK {
+                return None;
+            }
+            let ranges = slice::from_raw_parts((*mm_var).axis, (*mm_var).num_axis as usize)
+                               .iter().map(|axis| (axis.tag as u32, axis.minimum, axis.def, axis.maximum))
+                               .collect();
+            FT_Done_MM_Var((self._lib).0, mm_var);
+            Some(ranges)
+        }
+    }
+
+    fn has_axis(&self, tag: u32) -> bool {
+        self.variable && self.axis_ranges().map_or(false, |ranges| ranges.iter().any(|r| r.0 == tag))
+    }
+
     pub fn plan(&mut self, txt: &str, max_width: Option<u32>, features: Option<&str>) -> RenderPlan {
+        let mut render_plan = self.shape(txt, features, HB_DIRECTION_LTR);
+        render_plan.space_out(self.tracking);
+        if let Some(mw) = max_width {
+            self.crop_right(&mut render_plan, mw);
+        }
+        render_plan
+    }
+
+    // The glyphs are in visual order: the end of the text is on the left.
+    pub fn plan_rtl(&mut self, txt: &str, max_width: Option<u32>, features: Option<&str>) -> RenderPlan {
+        let mut render_plan = self.shape(txt, features, HB_DIRECTION_RTL);
+        render_plan.space_out(self.tracking);
+        if let Some(mw) = max_width {
+            self.crop_left(&mut render_plan, mw);
+        }
+        render_plan
+    }
+
+    // The runs of characters missing from this font are shaped with its fallbacks.
+    fn shape(&mut self, txt: &str, features: Option<&str>, direction: HbDirection) -> RenderPlan {
+        let features: Vec<&str> = self.features.iter().chain(features.iter())
+                                      .flat_map(|f| f.split_whitespace()).collect();
+        let features = features.join(" ");
+        let fallbacks = self.fallbacks.clone();
+        let mut fallbacks = fallbacks.borrow_mut();
+        if fallbacks.is_empty() {
+            return self.shape_run(txt, &features, direction);
+        }
+        let faces: Vec<*mut FtFace> = iter::once(self.face).chain(fallbacks.iter().map(|font| font.face)).collect();
+        let runs = fallback_runs(txt, faces.len(), |face, c| unsafe {
+            FT_Get_Char_Index(faces[face], c as libc::c_ulong) != 0
+        });
+        let mut render_plan = RenderPlan::default();
+        for (face, range) in runs {
+            let mut run_plan = if face == 0 {
+                self.shape_run(&txt[range], &features, direction)
+            } else {
+                let font = &mut fallbacks[face - 1];
+                font.set_size(self.size, self.dpi);
+                font.shape_run(&txt[range], &features, direction)
+            };
+            for glyph in &mut run_plan.glyphs {
+                glyph.face = face;
+            }
+            render_plan.width += run_plan.width;
+            // The glyphs are in visual order.
+            if direction == HB_DIRECTION_RTL {
+                run_plan.glyphs.extend(render_plan.glyphs.drain(..));
+                render_plan.glyphs = run_plan.glyphs;
+            } else {
+                render_plan.glyphs.extend(run_plan.glyphs);
+            }
+        }
+        render_plan
+    }
+
+    fn shape_run(&mut self, txt: &str, features: &str, direction: HbDirection) -> RenderPlan {
         unsafe {
             let buf = hb_buffer_create();
             hb_buffer_add_utf8(buf,
@@ -275,23 +842,19 @@ impl Font {
                                txt.len() as libc::c_int,
                                0,
                                -1);
-            hb_buffer_set_direction(buf, HB_DIRECTION_LTR);
+            hb_buffer_set_direction(buf, direction);
             hb_buffer_guess_segment_properties(buf);
 
-            let features_vec = if let Some(features_txt) = features {
-                features_txt.split(' ')
-                    .filter_map(|f| {
-                        let mut feature = HbFeature::default();
-                        let ret = hb_feature_from_string(f.as_ptr() as *const libc::c_char, f.len() as libc::c_int, &mut feature);
-                        if ret == 1 {
-                            Some(feature)
-                        } else {
-                            None
-                        }
-                    }).collect()
-            } else {
-                vec![]
-            };
+            let features_vec: Vec<HbFeature> = features.split_whitespace()
+                .filter_map(|f| {
+                    let mut feature = HbFeature::default();
+                    let ret = hb_feature_from_string(f.as_ptr() as *const libc::c_char, f.len() as libc::c_int, &mut feature);
+                    if ret == 1 {
+                        Some(feature)
+                    } else {
+                        None
+                    }
+                }).collect();
 
             hb_shape(self.font, buf, features_vec.as_ptr(), features_vec.len() as libc::c_uint);
  
@@ -299,23 +862,32 @@ impl Font {
             let info = hb_buffer_get_glyph_infos(buf, ptr::null_mut());
             let pos = hb_buffer_get_glyph_positions(buf, ptr::null_mut());
             let mut render_plan = RenderPlan::default();
+            let bold_advance = if self.synthetic.contains(Variant::BOLD) {
+                self.embolden_strength()
+            } else {
+                0
+            };
+            let stretch = self.stretch();
 
             for i in 0..len {
                 let pos_i = &*pos.offset(i as isize);
                 let info_i = &*info.offset(i as isize);
-                render_plan.width += (pos_i.x_advance >> 6) as u32;
+                // The emboldened glyphs are wider, the marks don't move.
+                let x_advance = if pos_i.x_advance != 0 {
+                    stretched(pos_i.x_advance, stretch) + bold_advance
+                } else {
+                    0
+                };
+                render_plan.width += (x_advance >> 6) as u32;
                 let glyph = GlyphPlan {
                     codepoint: info_i.codepoint,
-                    advance: pt!(pos_i.x_advance >> 6, pos_i.y_advance >> 6),
-                    offset: pt!(pos_i.x_offset >> 6, -pos_i.y_offset >> 6),
+                    face: 0,
+                    advance: pt!(x_advance >> 6, pos_i.y_advance >> 6),
+                    offset: pt!(stretched(pos_i.x_offset, stretch) >> 6, -pos_i.y_offset >> 6),
                 };
                 render_plan.glyphs.push(glyph);
             }
 
-            if let Some(mw) = max_width {
-                self.crop_right(&mut render_plan, mw);
-            }
-
             hb_buffer_destroy(buf);
             render_plan
         }
@@ -336,6 +908,56 @@ impl Font {
         render_plan.glyphs.extend_from_slice(&self.ellipsis.glyphs[..]);
     }
 
+    #[inline]
+    pub fn crop_left(&self, render_plan: &mut RenderPlan, max_width: u32) {
+        if render_plan.width <= max_width {
+            return;
+        }
+        render_plan.width += self.ellipsis.width;
+        let mut start = 0;
+        while start < render_plan.glyphs.len() && render_plan.width > max_width {
+            render_plan.width -= render_plan.glyphs[start].advance.x as u32;
+            start += 1;
+        }
+        render_plan.glyphs = self.ellipsis.glyphs.iter()
+                                 .chain(render_plan.glyphs[start..].iter()).cloned().collect();
+    }
+
+    #[inline]
+    pub fn clip_left(&self, render_plan: &mut RenderPlan, max_width: u32) {
+        let mut start = 0;
+        while start < render_plan.glyphs.len() && render_plan.width > max_width {
+            render_plan.width -= render_plan.glyphs[start].advance.x as u32;
+            start += 1;
+        }
+        render_plan.glyphs.drain(..start);
+    }
+
+    #[inline]
+    pub fn clip_right(&self, render_plan: &mut RenderPlan, max_width: u32) {
+        while render_plan.width > max_width {
+            if let Some(gp) = render_plan.glyphs.pop() {
+                render_plan.width -= gp.advance.x as u32;
+            } else {
+                break;
+            }
+        }
+    }
+
+    #[inline]
+    pub fn crop_middle(&self, render_plan: &mut RenderPlan, max_width: u32) {
+        if render_plan.width <= max_width {
+            return;
+        }
+        let advances: Vec<u32> = render_plan.glyphs.iter().map(|gp| gp.advance.x as u32).collect();
+        let (head, tail) = middle_cut(&advances, max_width.saturating_sub(self.ellipsis.width));
+        render_plan.width = advances[..head].iter().sum::<u32>() + self.ellipsis.width +
+                            advances[tail..].iter().sum::<u32>();
+        render_plan.glyphs = render_plan.glyphs[..head].iter()
+                                        .chain(self.ellipsis.glyphs.iter())
+                                        .chain(render_plan.glyphs[tail..].iter()).cloned().collect();
+    }
+
     #[inline]
     pub fn crop_around(&self, render_plan: &mut RenderPlan, index: usize, max_width: u32) -> usize {
         if render_plan.width <= max_width {
@@ -401,6 +1023,11 @@ impl Font {
         }
     }
 
+    // Widens the spaces between the words of *render_plan* for it to span *max_width*.
+    pub fn justify(&self, render_plan: &mut RenderPlan, max_width: u32) {
+        render_plan.stretch(self.space_codepoint, max_width);
+    }
+
     pub fn cut_point(&self, render_plan: &RenderPlan, max_width: u32) -> (usize, u32) {
         let mut width = render_plan.width;
         let glyphs = &render_plan.glyphs;
@@ -416,7 +1043,7 @@ impl Font {
         let j = i;
         let last_width = width;
 
-        while i > 0 && glyphs[i].codepoint != self.space_codepoint {
+        while i > 0 && (glyphs[i].face != 0 || glyphs[i].codepoint != self.space_codepoint) {
             i -= 1;
             width -= glyphs[i].advance.x as u32;
         }
@@ -430,24 +1057,30 @@ impl Font {
     }
 
     pub fn render(&mut self, fb: &mut Framebuffer, color: u8, render_plan: &RenderPlan, origin: &Point) {
-        unsafe {
-            let mut pos = *origin;
-            for glyph in &render_plan.glyphs {
-                FT_Load_Glyph(self.face, glyph.codepoint, FT_LOAD_RENDER | FT_LOAD_NO_HINTING);
-                // FT_Load_Glyph(self.face, glyph.codepoint, FT_LOAD_RENDER);
-                let glyph_slot = (*self.face).glyph;
-                let top_left = pos + glyph.offset + pt!((*glyph_slot).bitmap_left, -(*glyph_slot).bitmap_top);
-                let bitmap = &(*glyph_slot).bitmap;
-                for y in 0..bitmap.rows {
-                    for x in 0..bitmap.width {
-                        let blackness = *bitmap.buffer.offset((bitmap.pitch * y + x) as isize);
-                        let alpha = blackness as f32 / 255.0;
-                        let pt = top_left + pt!(x, y);
-                        fb.set_blended_pixel(pt.x as u32, pt.y as u32, color, alpha);
-                    }
+        let mut pos = *origin;
+        let (size, dpi) = (self.size, self.dpi);
+        let fallbacks = self.fallbacks.clone();
+        let mut fallbacks = fallbacks.borrow_mut();
+        for glyph in &render_plan.glyphs {
+            let font = if glyph.face == 0 {
+                &mut *self
+            } else {
+                let font = &mut fallbacks[glyph.face - 1];
+                font.set_size(size, dpi);
+                font
+            };
+            let (face, synthetic, stretch) = (font.face, font.synthetic, font.stretch());
+            let key = (glyph.codepoint, size, dpi, font.axes);
+            let bitmap = font.glyph_cache.get_or_insert_with(key, || unsafe { rasterize(face, glyph.codepoint, synthetic, stretch) });
+            let top_left = pos + glyph.offset + pt!(bitmap.left, -bitmap.top);
+            for y in 0..bitmap.height {
+                for x in 0..bitmap.width {
+                    let blackness = bitmap.buf[(bitmap.width * y + x) as usize];
+                    let pt = top_left + pt!(x, y);
+                    fb.set_covered_pixel(pt.x as u32, pt.y as u32, color, blackness);
                 }
-                pos += glyph.advance;
             }
+            pos += glyph.advance;
         }
     }
 
@@ -462,6 +1095,24 @@ impl Font {
         }
     }
 
+    // The width, in percent of the normal width, imitated by a font that isn't variable.
+    fn stretch(&self) -> Option<u16> {
+        if self.variable {
+            None
+        } else {
+            self.axes.width
+        }
+    }
+
+    // The thickening, in 26.6 pixels, applied by *FT_GlyphSlot_Embolden*.
+    fn embolden_strength(&self) -> i32 {
+        unsafe {
+            let units_per_em = (*self.face).units_per_em as i64;
+            let y_scale = ((*(*self.face).size).metrics).y_scale as i64;
+            (((units_per_em * y_scale + 0x8000) >> 16) / 24) as i32
+        }
+    }
+
     pub fn em(&self) -> u16 {
         unsafe {
             ((*(*self.face).size).metrics).x_ppem
@@ -469,9 +1120,49 @@ impl Font {
     }
 }
 
+// Scales a horizontal distance by *stretch*, in percent.
+fn stretched(value: i32, stretch: Option<u16>) -> i32 {
+    stretch.map_or(value, |s| value * s as i32 / 100)
+}
+
+unsafe fn rasterize(face: *mut FtFace, codepoint: u32, synthetic: Variant, stretch: Option<u16>) -> GlyphBitmap {
+    let glyph_slot = (*face).glyph;
+    if synthetic.is_empty() && stretch.is_none() {
+        FT_Load_Glyph(face, codepoint, FT_LOAD_RENDER | FT_LOAD_NO_HINTING);
+    } else {
+        FT_Load_Glyph(face, codepoint, FT_LOAD_NO_HINTING);
+        if let Some(s) = stretch {
+            let matrix = FtMatrix { xx: ((s as FtFixed) << 16) / 100, xy: 0, yx: 0, yy: 1 << 16 };
+            FT_Outline_Transform(&(*glyph_slot).outline, &matrix);
+        }
+        if synthetic.contains(Variant::BOLD) {
+            FT_GlyphSlot_Embolden(glyph_slot);
+        }
+        if synthetic.contains(Variant::ITALIC) {
+            FT_GlyphSlot_Oblique(glyph_slot);
+        }
+        FT_Render_Glyph(glyph_slot, FT_RENDER_MODE_NORMAL);
+    }
+    let bitmap = &(*glyph_slot).bitmap;
+    let mut buf = Vec::with_capacity((bitmap.rows * bitmap.width) as usize);
+    for y in 0..bitmap.rows {
+        let row = bitmap.buffer.offset((bitmap.pitch * y) as isize);
+        buf.extend_from_slice(slice::from_raw_parts(row, bitmap.width as usize));
+    }
+    GlyphBitmap {
+        left: (*glyph_slot).bitmap_left,
+        top: (*glyph_slot).bitmap_top,
+        width: bitmap.width as i32,
+        height: bitmap.rows as i32,
+        buf,
+    }
+}
+
 #[derive(Debug, Copy, Clone)]
 pub struct GlyphPlan {
     codepoint: u32,
+    // The index of the face in the font followed by its fallbacks.
+    face: usize,
     offset: Point,
     advance: Point,
 }
@@ -496,6 +1187,53 @@ impl RenderPlan {
     pub fn advance_at(&self, index: usize) -> i32 {
         self.glyphs.iter().take(index).map(|g| g.advance.x).sum()
     }
+
+    // The number of glyphs to skip, at either end, for the others to fit *max_width*.
+    pub fn overflow(&self, max_width: u32) -> usize {
+        let mut width = self.width;
+        let mut count = 0;
+        while count < self.glyphs.len() && width > max_width {
+            width -= self.glyphs[count].advance.x as u32;
+            count += 1;
+        }
+        count
+    }
+
+    // Spreads the space missing to reach *max_width* over the glyphs *gap*, from the
+    // primary face, found between other glyphs. The leading and trailing gaps are kept,
+    // and a plan without inner gaps or wider than *max_width* is left unchanged.
+    fn stretch(&mut self, gap: u32, max_width: u32) {
+        let is_gap = |g: &GlyphPlan| g.face == 0 && g.codepoint == gap;
+        let first = self.glyphs.iter().position(|g| !is_gap(g));
+        let last = self.glyphs.iter().rposition(|g| !is_gap(g));
+        let gaps: Vec<usize> = match (first, last) {
+            (Some(first), Some(last)) => (first..last).filter(|&i| is_gap(&self.glyphs[i])).collect(),
+            _ => Vec::new(),
+        };
+        if gaps.is_empty() || self.width >= max_width {
+            return;
+        }
+        let extra = max_width - self.width;
+        let count = gaps.len() as u32;
+        for (k, &i) in gaps.iter().enumerate() {
+            // The remainder goes to the first gaps.
+            let share = extra / count + if (k as u32) < extra % count { 1 } else { 0 };
+            self.glyphs[i].advance.x += share as i32;
+        }
+        self.width = max_width;
+    }
+
+    pub fn skip_left(&mut self, count: usize) {
+        let count = count.min(self.glyphs.len());
+        self.width -= self.advance_at(count) as u32;
+        self.glyphs.drain(..count);
+    }
+
+    pub fn skip_right(&mut self, count: usize) {
+        let start = self.glyphs.len().saturating_sub(count);
+        self.width -= self.glyphs[start..].iter().map(|g| g.advance.x as u32).sum::<u32>();
+        self.glyphs.truncate(start);
+    }
 }
 
 impl Default for RenderPlan {
@@ -1006,3 +1744,197 @@ impl AsErrorKind for FtError {
         }
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::{middle_cut, is_rtl, fallback_runs, design_coordinates, nearest_variant, stretched};
+    use std::time::Instant;
+    use super::{Axes, Variant, DEFAULT_AXES, WEIGHT_AXIS, WIDTH_AXIS, RenderPlan, GlyphPlan, GlyphCache, GlyphBitmap};
+    use settings::DEFAULT_GLYPH_CACHE_CAPACITY;
+    use super::{FontOpener, FONT_SIZES, NO_LIGATURES, variant_from_name, closest_variant};
+
+    #[test]
+    fn cut_in_the_middle() {
+        let advances = [10; 8];
+        assert_eq!(middle_cut(&advances, 100), (4, 4));
+        assert_eq!(middle_cut(&advances, 50), (3, 6));
+        assert_eq!(middle_cut(&advances, 45), (2, 6));
+        assert_eq!(middle_cut(&advances, 5), (0, 8));
+    }
+
+    #[test]
+    fn skip_overflowing_glyphs() {
+        let glyph = GlyphPlan { codepoint: 0, face: 0, offset: pt!(0, 0), advance: pt!(10, 0) };
+        let mut plan = RenderPlan { width: 60, glyphs: vec![glyph; 6] };
+        assert_eq!(plan.overflow(60), 0);
+        assert_eq!(plan.overflow(35), 3);
+        plan.skip_left(2);
+        assert_eq!(plan.width, 40);
+        plan.skip_right(3);
+        assert_eq!(plan.width, 10);
+        plan.skip_right(3);
+        assert_eq!(plan.width, 0);
+    }
+
+    #[test]
+    fn toggle_ligatures() {
+        let mut font = FontOpener::new().unwrap().open("fonts/NotoSerif-Regular.ttf").unwrap();
+        font.set_size(FONT_SIZES[1], 300);
+        let ligated = font.plan("office", None, None);
+        let spelled = font.plan("office", None, Some(NO_LIGATURES));
+        assert_eq!(spelled.glyphs.len(), 6);
+        assert!(ligated.glyphs.len() < spelled.glyphs.len());
+        font.features = Some(NO_LIGATURES);
+        assert_eq!(font.plan("office", None, None).glyphs.len(), 6);
+    }
+
+    #[test]
+    fn guess_variants_from_names() {
+        assert_eq!(variant_from_name("Inter-Regular.ttf"), Some(Variant::REGULAR));
+        assert_eq!(variant_from_name("Inter.otf"), Some(Variant::REGULAR));
+        assert_eq!(variant_from_name("Inter-Italic.ttf"), Some(Variant::ITALIC));
+        assert_eq!(variant_from_name("Inter-Bold.ttf"), Some(Variant::BOLD));
+        assert_eq!(variant_from_name("Inter Bold Oblique.otf"), Some(Variant::BOLD | Variant::ITALIC));
+        assert_eq!(variant_from_name("Inter-SemiBold.ttf"), None);
+        assert_eq!(variant_from_name("Inter-ExtraLightItalic.ttf"), None);
+    }
+
+    #[test]
+    fn synthesize_from_closest_variants() {
+        let regular_only = [Variant::REGULAR];
+        assert_eq!(closest_variant(Variant::BOLD, &regular_only), Some(Variant::REGULAR));
+        assert_eq!(closest_variant(Variant::BOLD | Variant::ITALIC, &regular_only), Some(Variant::REGULAR));
+        let with_italic = [Variant::REGULAR, Variant::ITALIC];
+        assert_eq!(closest_variant(Variant::BOLD | Variant::ITALIC, &with_italic), Some(Variant::ITALIC));
+        assert_eq!(closest_variant(Variant::BOLD, &with_italic), Some(Variant::REGULAR));
+        assert_eq!(closest_variant(Variant::ITALIC, &[Variant::BOLD]), None);
+    }
+
+    #[test]
+    fn justify_lines() {
+        let letter = GlyphPlan { codepoint: 1, face: 0, offset: pt!(0, 0), advance: pt!(10, 0) };
+        let space = GlyphPlan { codepoint: 3, advance: pt!(4, 0), ..letter };
+        // « a b c »
+        let mut plan = RenderPlan { width: 46, glyphs: vec![space, letter, space, letter, space, letter, space] };
+        plan.stretch(3, 53);
+        assert_eq!(plan.width, 53);
+        let advances: Vec<i32> = plan.glyphs.iter().map(|g| g.advance.x).collect();
+        assert_eq!(advances, vec![4, 10, 8, 10, 7, 10, 4]);
+        // A single word.
+        let mut plan = RenderPlan { width: 30, glyphs: vec![letter; 3] };
+        plan.stretch(3, 53);
+        assert_eq!(plan.width, 30);
+        // Nothing but spaces.
+        let mut plan = RenderPlan { width: 8, glyphs: vec![space; 2] };
+        plan.stretch(3, 53);
+        assert_eq!(plan.width, 8);
+    }
+
+    #[test]
+    fn track_glyphs() {
+        let glyph = GlyphPlan { codepoint: 0, face: 0, offset: pt!(0, 0), advance: pt!(10, 0) };
+        let mut plan = RenderPlan { width: 40, glyphs: vec![glyph; 4] };
+        plan.space_out(0);
+        assert_eq!(plan.width, 40);
+        plan.space_out(3);
+        assert_eq!(plan.width, 49);
+        assert_eq!(plan.advance_at(4), 49);
+        assert_eq!(plan.glyphs[3].advance.x, 10);
+    }
+
+    #[test]
+    fn rasterize_repeated_text_once() {
+        let mut cache = GlyphCache::new(8);
+        let mut rasterizations = 0;
+        let text: Vec<u32> = "4.2 4.25".chars().map(|c| c as u32).collect();
+        // The value of a slider redrawn on each frame.
+        for _ in 0..10 {
+            for &codepoint in &text {
+                cache.get_or_insert_with((codepoint, 349, 300, DEFAULT_AXES), || {
+                    rasterizations += 1;
+                    GlyphBitmap { left: 0, top: 0, width: 0, height: 0, buf: Vec::new() }
+                });
+            }
+        }
+        assert_eq!(rasterizations, 5);
+        // The least recently used glyphs are evicted.
+        cache.set_capacity(2);
+        let mut rasterizations = 0;
+        for &codepoint in &[b'2', b'5', b'2', b'4'] {
+            cache.get_or_insert_with((codepoint as u32, 349, 300, DEFAULT_AXES), || {
+                rasterizations += 1;
+                GlyphBitmap { left: 0, top: 0, width: 0, height: 0, buf: Vec::new() }
+            });
+        }
+        assert_eq!(rasterizations, 1);
+    }
+
+    // Run with *cargo test --release bench_glyph_cache -- --ignored --nocapture*.
+    #[test]
+    #[ignore]
+    fn bench_glyph_cache() {
+        let capacity = DEFAULT_GLYPH_CACHE_CAPACITY;
+        let mut cache = GlyphCache::new(capacity);
+        let rounds = 1_000_000;
+        let start = Instant::now();
+        // Cycling through more glyphs than the cache holds evicts on every lookup.
+        for i in 0..rounds {
+            let codepoint = (i % (capacity + capacity / 2)) as u32;
+            cache.get_or_insert_with((codepoint, 349, 300, DEFAULT_AXES), || {
+                GlyphBitmap { left: 0, top: 0, width: 0, height: 0, buf: Vec::new() }
+            });
+        }
+        let elapsed = start.elapsed();
+        assert_eq!(cache.entries.len(), capacity);
+        assert_eq!(cache.uses.len(), capacity);
+        println!("{} ns per lookup.", elapsed.as_nanos() / rounds as u128);
+    }
+
+    #[test]
+    fn detect_rtl_text() {
+        assert!(is_rtl("שלום"));
+        assert!(is_rtl("1984 — كتاب"));
+        assert!(!is_rtl("Dune"));
+        assert!(!is_rtl("Dune: كتاب"));
+        assert!(!is_rtl("42"));
+    }
+
+    #[test]
+    fn split_runs_by_coverage() {
+        // The first face covers ASCII, the second covers the ideographs and the spaces.
+        let covers = |face: usize, c: char| if face == 0 { c.is_ascii() } else { c == ' ' || c > '\u{2E80}' };
+        assert_eq!(fallback_runs("Tokyo 東京 2020", 2, covers),
+                   vec![(0, 0..6), (1, 6..13), (0, 13..17)]);
+        assert_eq!(fallback_runs("Dune", 2, covers), vec![(0, 0..4)]);
+        // Nothing covers the snowman.
+        assert_eq!(fallback_runs("☃ 東", 2, covers), vec![(0, 0..4), (1, 4..7)]);
+        assert!(fallback_runs("", 2, covers).is_empty());
+    }
+
+    #[test]
+    fn place_styles_on_axes() {
+        let ranges = [(WEIGHT_AXIS, 100 << 16, 400 << 16, 900 << 16),
+                      (WIDTH_AXIS, 75 << 16, 100 << 16, 100 << 16)];
+        assert_eq!(design_coordinates(&DEFAULT_AXES, &ranges), vec![400 << 16, 100 << 16]);
+        let axes = Axes { weight: Some(700), width: Some(125), optical_size: Some(12) };
+        assert_eq!(design_coordinates(&axes, &ranges), vec![700 << 16, 100 << 16]);
+        assert_eq!(nearest_variant(Variant::ITALIC, &axes), Variant::ITALIC | Variant::BOLD);
+        let axes = Axes { weight: Some(300), ..DEFAULT_AXES };
+        assert_eq!(nearest_variant(Variant::BOLD, &axes), Variant::REGULAR);
+        assert_eq!(nearest_variant(Variant::BOLD, &DEFAULT_AXES), Variant::BOLD);
+        assert_eq!(stretched(640, Some(75)), 480);
+        assert_eq!(stretched(640, None), 640);
+    }
+
+    #[test]
+    fn keep_an_instance_per_axes() {
+        let fo = FontOpener::new().unwrap();
+        let mut font = fo.open("fonts/NotoSans-Regular.ttf").unwrap();
+        let narrow = Axes { width: Some(75), ..DEFAULT_AXES };
+        assert_eq!(font.instance(&narrow).axes, narrow);
+        assert_eq!(font.instance(&DEFAULT_AXES).axes, DEFAULT_AXES);
+        assert_eq!(font.instance(&Axes { weight: Some(700), ..narrow }).axes, narrow);
+        assert_eq!(font.axes, DEFAULT_AXES);
+        assert_eq!(font.instances.len(), 1);
+    }
+}
@@ -2,7 +2,8 @@ extern crate png;
 
 use std::fs::File;
 use png::HasParameters;
-use framebuffer::{Framebuffer, UpdateMode};
+use framebuffer::{Framebuffer, UpdateMode, Rotation, ClipStack, Snapshot};
+use framebuffer::{transform_color, rotate_buffer, gamma_table};
 use color::WHITE;
 use geom::{Rectangle, lerp};
 use errors::*;
@@ -13,6 +14,9 @@ pub struct ImageFramebuffer {
     data: Vec<u8>,
     inverted: bool,
     monochrome: bool,
+    rotation: Rotation,
+    gamma_table: [f32; 256],
+    clips: ClipStack,
 }
 
 impl ImageFramebuffer {
@@ -24,27 +28,25 @@ impl ImageFramebuffer {
             data: vec![WHITE; len],
             inverted: false,
             monochrome: false,
+            rotation: Rotation::default(),
+            gamma_table: gamma_table(1.0),
+            clips: ClipStack::default(),
         }
     }
-}
 
-#[inline]
-fn transform_color(color: u8, inverted: bool, monochrome: bool) -> u8 {
-    let color = if inverted {
-        255 - color
-    } else {
-        color
-    };
-    if monochrome {
-        (color > 127) as u8 * 255
-    } else {
-        color
+    // The address, within the data, of the pixel at the given drawing coordinates.
+    fn address(&self, x: u32, y: u32) -> usize {
+        let pt = self.rotation.transform(pt!(x as i32, y as i32), (self.width, self.height));
+        (pt.y as u32 * self.width + pt.x as u32) as usize
     }
 }
 
 impl Framebuffer for ImageFramebuffer {
     fn set_pixel(&mut self, x: u32, y: u32, color: u8) {
-        let addr = (y * self.width + x) as usize;
+        if !self.clips.includes(x, y) {
+            return;
+        }
+        let addr = self.address(x, y);
         self.data[addr] = color;
     }
 
@@ -53,15 +55,22 @@ impl Framebuffer for ImageFramebuffer {
             self.set_pixel(x, y, color);
             return;
         }
-        let addr = (y * self.width + x) as usize;
+        if !self.clips.includes(x, y) {
+            return;
+        }
+        let addr = self.address(x, y);
         let blended_color = lerp(self.data[addr] as f32, color as f32, alpha) as u8;
         self.data[addr] = blended_color;
     }
 
     fn invert_region(&mut self, rect: &Rectangle) {
+        let rect = match self.clips.clip(rect) {
+            Some(rect) => rect,
+            None => return,
+        };
         for y in rect.min.y..rect.max.y {
             for x in rect.min.x..rect.max.x {
-                let addr = (y * self.width as i32 + x) as usize;
+                let addr = self.address(x as u32, y as u32);
                 let color = 255 - self.data[addr];
                 self.data[addr] = color;
             }
@@ -82,7 +91,8 @@ impl Framebuffer for ImageFramebuffer {
         let mut encoder = png::Encoder::new(file, width, height);
         encoder.set(png::ColorType::Grayscale).set(png::BitDepth::Eight);
         let mut writer = encoder.write_header().chain_err(|| "Can't write header.")?;
-        let data: Vec<u8> = self.data.iter().map(|c| transform_color(*c, self.inverted, self.monochrome)).collect();
+        let data: Vec<u8> = rotate_buffer(&self.data, (self.width, self.height), self.rotation)
+                                .into_iter().map(|c| transform_color(c, self.inverted, self.monochrome)).collect();
         writer.write_image_data(&data).chain_err(|| "Can't write data to file.")?;
         Ok(())
     }
@@ -95,7 +105,267 @@ impl Framebuffer for ImageFramebuffer {
         self.monochrome = !self.monochrome;
     }
 
+    fn set_rotation(&mut self, rotation: Rotation) {
+        self.rotation = rotation;
+    }
+
+    fn rotation(&self) -> Rotation {
+        self.rotation
+    }
+
+    fn set_gamma(&mut self, gamma: f32) {
+        self.gamma_table = gamma_table(gamma);
+    }
+
+    fn gamma_table(&self) -> &[f32; 256] {
+        &self.gamma_table
+    }
+
+    fn push_clip(&mut self, rect: &Rectangle) {
+        self.clips.push(rect);
+    }
+
+    fn pop_clip(&mut self) {
+        self.clips.pop();
+    }
+
+    fn snapshot(&self, rect: &Rectangle) -> Snapshot {
+        let rect = rect.intersection(&self.rect()).unwrap_or_default();
+        let mut data = Vec::with_capacity(rect.area() as usize);
+        for y in rect.min.y..rect.max.y {
+            for x in rect.min.x..rect.max.x {
+                data.push(self.data[self.address(x as u32, y as u32)]);
+            }
+        }
+        Snapshot { rect, data }
+    }
+
+    fn restore(&mut self, snapshot: &Snapshot) {
+        let rect = snapshot.rect;
+        let mut colors = snapshot.data.iter();
+        for y in rect.min.y..rect.max.y {
+            for x in rect.min.x..rect.max.x {
+                let addr = self.address(x as u32, y as u32);
+                if let Some(&color) = colors.next() {
+                    self.data[addr] = color;
+                }
+            }
+        }
+    }
+
     fn dims(&self) -> (u32, u32) {
-        (self.width, self.height)
+        self.rotation.dims((self.width, self.height))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use framebuffer::{Framebuffer, Rotation, Pixmap};
+    use geom::{CornerSpec, BorderSpec, BorderStyle, Axis};
+    use color::{BLACK, WHITE};
+    use super::ImageFramebuffer;
+
+    #[test]
+    fn blit_rotated_pattern() {
+        let mut fb = ImageFramebuffer::new(3, 2);
+        fb.set_rotation(Rotation::Clockwise);
+        assert_eq!(fb.dims(), (2, 3));
+        // A vertical line along the left edge of the drawing space.
+        fb.draw_rectangle(&rect![0, 0, 1, 3], BLACK);
+        assert_eq!(fb.data, vec![BLACK, BLACK, BLACK,
+                                 WHITE, WHITE, WHITE]);
+        fb.set_rotation(Rotation::UpsideDown);
+        fb.set_pixel(0, 0, BLACK);
+        assert_eq!(fb.data[5], BLACK);
+        assert_eq!(fb.data[3], WHITE);
+    }
+
+    #[test]
+    fn draw_lines() {
+        let mut fb = ImageFramebuffer::new(8, 8);
+        let color = |fb: &ImageFramebuffer, x: usize, y: usize| fb.data[y * 8 + x];
+        fb.draw_line(&pt!(0, 0), &pt!(7, 7), 1, BLACK);
+        for i in 0..8 {
+            assert_eq!(color(&fb, i, i), BLACK);
+        }
+        assert_eq!(color(&fb, 1, 0), WHITE);
+        assert_eq!(color(&fb, 0, 1), WHITE);
+
+        let mut fb = ImageFramebuffer::new(8, 8);
+        fb.draw_line(&pt!(1, 0), &pt!(3, 7), 3, BLACK);
+        assert_eq!(color(&fb, 0, 0), BLACK);
+        assert_eq!(color(&fb, 3, 0), WHITE);
+        assert_eq!(color(&fb, 0, 3), WHITE);
+        assert_eq!(color(&fb, 1, 3), BLACK);
+        assert_eq!(color(&fb, 3, 3), BLACK);
+        assert_eq!(color(&fb, 4, 3), WHITE);
+        assert_eq!(color(&fb, 4, 7), BLACK);
+
+        let mut fb = ImageFramebuffer::new(8, 8);
+        fb.draw_line(&pt!(-5, 2), &pt!(20, 2), 2, BLACK);
+        for x in 0..8 {
+            assert_eq!(color(&fb, x, 1), BLACK);
+            assert_eq!(color(&fb, x, 2), BLACK);
+            assert_eq!(color(&fb, x, 3), WHITE);
+        }
+    }
+
+    #[test]
+    fn draw_circles_and_ellipses() {
+        let color = |fb: &ImageFramebuffer, x: usize, y: usize| fb.data[y * 8 + x];
+        let mut fb = ImageFramebuffer::new(8, 8);
+        fb.draw_circle(&pt!(4, 4), 3, None, BLACK);
+        assert_eq!(color(&fb, 4, 4), BLACK);
+        assert_eq!(color(&fb, 2, 2), BLACK);
+        assert_eq!(color(&fb, 0, 4), WHITE);
+        let edge = color(&fb, 1, 4);
+        assert!(edge > BLACK && edge < 0x20);
+        assert_eq!(color(&fb, 4, 1), edge);
+
+        let mut fb = ImageFramebuffer::new(8, 8);
+        fb.draw_circle(&pt!(4, 4), 3, Some(1), BLACK);
+        assert_eq!(color(&fb, 4, 4), WHITE);
+        assert_eq!(color(&fb, 1, 4), edge);
+        let inner_edge = color(&fb, 2, 2);
+        assert!(inner_edge > 0x40 && inner_edge < 0x80);
+
+        let mut fb = ImageFramebuffer::new(8, 8);
+        fb.draw_ellipse(&pt!(4, 4), (3, 1), None, BLACK);
+        assert_eq!(color(&fb, 4, 3), BLACK);
+        assert_eq!(color(&fb, 4, 2), WHITE);
+        assert_eq!(color(&fb, 7, 4), WHITE);
+
+        let mut fb = ImageFramebuffer::new(8, 8);
+        fb.draw_circle(&pt!(0, 0), 3, None, BLACK);
+        assert_eq!(color(&fb, 0, 0), BLACK);
+    }
+
+    #[test]
+    fn dithered_gradients() {
+        let mut fb = ImageFramebuffer::new(9, 4);
+        fb.draw_gradient(&rect![0, 0, 9, 4], BLACK, WHITE, Axis::Horizontal);
+        for y in 0..4 {
+            assert_eq!(fb.data[y * 9], BLACK);
+            assert_eq!(fb.data[y * 9 + 8], WHITE);
+            let middle = fb.data[y * 9 + 4] as i32;
+            assert!((middle - 0x80).abs() <= 0x11);
+        }
+        // Only the gray levels of the panel are used.
+        assert!(fb.data.iter().all(|&c| c % 0x11 == 0));
+
+        let mut fb = ImageFramebuffer::new(2, 5);
+        fb.draw_gradient(&rect![0, 0, 2, 5], WHITE, BLACK, Axis::Vertical);
+        assert_eq!(&fb.data[..2], &[WHITE, WHITE]);
+        assert_eq!(&fb.data[8..], &[BLACK, BLACK]);
+    }
+
+    #[test]
+    fn blend_over_known_backgrounds() {
+        let mut fb = ImageFramebuffer::new(3, 1);
+        fb.draw_rectangle(&rect![0, 0, 3, 1], 0x80);
+        fb.blend_rectangle(&rect![0, 0, 1, 1], BLACK, 128);
+        fb.blend_rectangle(&rect![1, 0, 2, 1], BLACK, 255);
+        fb.blend_rectangle(&rect![2, 0, 3, 1], BLACK, 0);
+        assert_eq!(fb.data, vec![63, BLACK, 0x80]);
+
+        let mut fb = ImageFramebuffer::new(3, 1);
+        let pixmap = Pixmap { width: 3, height: 1, buf: vec![BLACK, 0x40, BLACK] };
+        fb.blend_pixmap(&pixmap, &[255, 128, 0], &pt!(0, 0));
+        assert_eq!(fb.data, vec![BLACK, 159, WHITE]);
+    }
+
+    #[test]
+    fn dashed_outlines() {
+        let mut fb = ImageFramebuffer::new(10, 10);
+        fb.draw_rectangle_outline(&rect![0, 0, 10, 10],
+                                  &BorderSpec { thickness: 1,
+                                                color: BLACK,
+                                                style: BorderStyle::Dashed { on: 2, off: 2 } });
+        assert_eq!(&fb.data[..5], &[BLACK, BLACK, WHITE, WHITE, BLACK]);
+        // The left side, going up from the bottom.
+        assert_eq!(fb.data[80], WHITE);
+        assert_eq!(fb.data[70], BLACK);
+        assert_eq!(fb.data[60], BLACK);
+        assert_eq!(fb.data[50], WHITE);
+        assert_eq!(fb.data[55], WHITE);
+    }
+
+    #[test]
+    fn draw_within_nested_clips() {
+        let mut fb = ImageFramebuffer::new(8, 8);
+        fb.push_clip(&rect![2, 2, 6, 6]);
+        fb.push_clip(&rect![4, 0, 8, 8]);
+        fb.clear(BLACK);
+        fb.draw_line(&pt!(0, 5), &pt!(7, 5), 1, BLACK);
+        for y in 0..8 {
+            for x in 0..8 {
+                let inside = x >= 4 && x < 6 && y >= 2 && y < 6;
+                assert_eq!(fb.data[y * 8 + x] == BLACK, inside);
+            }
+        }
+        fb.pop_clip();
+        fb.blend_rectangle(&rect![0, 0, 8, 8], BLACK, 255);
+        fb.invert_region(&rect![0, 0, 8, 1]);
+        assert_eq!(fb.data[2 * 8 + 2], BLACK);
+        assert_eq!(fb.data[9], WHITE);
+        assert_eq!(fb.data[0], WHITE);
+        fb.push_clip(&rect![7, 7, 8, 8]);
+        fb.push_clip(&rect![0, 0, 1, 1]);
+        fb.set_pixel(0, 0, BLACK);
+        fb.set_pixel(7, 7, BLACK);
+        assert_eq!(fb.data[0], WHITE);
+        assert_eq!(fb.data[63], WHITE);
+        fb.pop_clip();
+        fb.pop_clip();
+        fb.pop_clip();
+        fb.set_pixel(0, 0, BLACK);
+        assert_eq!(fb.data[0], BLACK);
+    }
+
+    #[test]
+    fn restore_snapshots() {
+        let mut fb = ImageFramebuffer::new(6, 4);
+        fb.set_rotation(Rotation::Clockwise);
+        fb.draw_gradient(&rect![0, 0, 4, 6], BLACK, WHITE, Axis::Vertical);
+        let original = fb.data.clone();
+        let snapshot = fb.snapshot(&rect![1, 2, 9, 5]);
+        assert_eq!(snapshot.rect, rect![1, 2, 4, 5]);
+        assert_eq!(snapshot.data.len(), 9);
+        fb.clear(0x80);
+        fb.push_clip(&rect![0, 0, 1, 1]);
+        fb.restore(&snapshot);
+        fb.pop_clip();
+        fb.draw_rectangle(&rect![0, 0, 4, 2], BLACK);
+        for y in 0..6 {
+            for x in 0..4 {
+                let addr = fb.address(x, y);
+                if y >= 2 && y < 5 && x >= 1 {
+                    assert_eq!(fb.data[addr], original[addr]);
+                } else if y < 2 {
+                    assert_eq!(fb.data[addr], BLACK);
+                } else {
+                    assert_eq!(fb.data[addr], 0x80);
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn smooth_rounded_corners() {
+        // The exact coverage of the north west corner of radius 4.
+        let reference = [[0xFF, 0xD6, 0x4E, 0x0B],
+                         [0xD6, 0x0F, 0x00, 0x00],
+                         [0x4E, 0x00, 0x00, 0x00],
+                         [0x0B, 0x00, 0x00, 0x00]];
+        let mut fb = ImageFramebuffer::new(10, 10);
+        fb.draw_rounded_rectangle(&rect![0, 0, 10, 10], &CornerSpec::Uniform(4), BLACK);
+        for y in 0..4 {
+            for x in 0..4 {
+                let color = fb.data[y * 10 + x] as i32;
+                assert!((color - reference[y][x]).abs() <= 8);
+            }
+        }
+        assert_eq!(fb.data[5], BLACK);
+        assert_eq!(fb.data[50], BLACK);
     }
 }
@@ -12,7 +12,9 @@ use std::ops::Drop;
 use libc::ioctl;
 use png::HasParameters;
 use geom::Rectangle;
-use framebuffer::{UpdateMode, Framebuffer};
+use framebuffer::{UpdateMode, Framebuffer, Rotation, transform_color, quantize_gray, rotate_buffer};
+use framebuffer::{ClipStack, Snapshot, gamma_table};
+use device::CURRENT_DEVICE;
 use framebuffer::mxcfb_sys::*;
 use errors::*;
 
@@ -29,7 +31,7 @@ impl Into<MxcfbRect> for Rectangle {
 
 type SetPixelRgb = fn(&mut KoboFramebuffer, u32, u32, [u8; 3]);
 type GetPixelRgb = fn(&KoboFramebuffer, u32, u32) -> [u8; 3];
-type AsRgb = fn(&KoboFramebuffer) -> Vec<u8>;
+type AsGray = fn(&KoboFramebuffer) -> Vec<u8>;
 
 pub struct KoboFramebuffer {
     device: File,
@@ -39,7 +41,10 @@ pub struct KoboFramebuffer {
     flags: u32,
     set_pixel_rgb: SetPixelRgb,
     get_pixel_rgb: GetPixelRgb,
-    as_rgb: AsRgb,
+    as_gray: AsGray,
+    rotation: Rotation,
+    gamma_table: [f32; 256],
+    clips: ClipStack,
     pub bytes_per_pixel: u8,
     pub var_info: VarScreenInfo,
     pub fix_info: FixScreenInfo,
@@ -47,6 +52,10 @@ pub struct KoboFramebuffer {
 
 impl Framebuffer for KoboFramebuffer {
     fn set_pixel(&mut self, x: u32, y: u32, color: u8) {
+        if !self.clips.includes(x, y) {
+            return;
+        }
+        let (x, y) = self.physical_position(x, y);
         (self.set_pixel_rgb)(self, x, y, [color, color, color]);
     }
 
@@ -55,6 +64,10 @@ impl Framebuffer for KoboFramebuffer {
             self.set_pixel(x, y, color);
             return;
         }
+        if !self.clips.includes(x, y) {
+            return;
+        }
+        let (x, y) = self.physical_position(x, y);
         let rgb = (self.get_pixel_rgb)(self, x, y);
         let color_alpha = color as f32 * alpha;
         let r = color_alpha + (1.0 - alpha) * rgb[0] as f32;
@@ -64,6 +77,10 @@ impl Framebuffer for KoboFramebuffer {
     }
 
     fn invert_region(&mut self, rect: &Rectangle) {
+        let rect = match self.clips.clip(rect) {
+            Some(rect) => self.rotation.transform_rect(&rect, self.physical_dims()),
+            None => return,
+        };
         for y in rect.min.y..rect.max.y {
             for x in rect.min.x..rect.max.x {
                 let rgb = (self.get_pixel_rgb)(self, x as u32, y as u32);
@@ -104,7 +121,7 @@ impl Framebuffer for KoboFramebuffer {
             flags |= EPDC_FLAG_FORCE_MONOCHROME;
         }
         let update_data = MxcfbUpdateData {
-            update_region: (*rect).into(),
+            update_region: self.rotation.transform_rect(rect, self.physical_dims()).into(),
             waveform_mode,
             update_mode,
             update_marker,
@@ -141,9 +158,16 @@ impl Framebuffer for KoboFramebuffer {
         let (width, height) = self.dims();
         let file = File::create(path).chain_err(|| "Can't create output file.")?;
         let mut encoder = png::Encoder::new(file, width, height);
-        encoder.set(png::ColorType::RGB).set(png::BitDepth::Eight);
+        encoder.set(png::ColorType::Grayscale).set(png::BitDepth::Eight);
         let mut writer = encoder.write_header().chain_err(|| "Can't write header.")?;
-        writer.write_image_data(&(self.as_rgb)(self)).chain_err(|| "Can't write data to file.")?;
+        let inverted = self.flags & EPDC_FLAG_ENABLE_INVERSION != 0;
+        let monochrome = self.flags & EPDC_FLAG_FORCE_MONOCHROME != 0;
+        let levels = CURRENT_DEVICE.gray_levels();
+        let gray = rotate_buffer(&(self.as_gray)(self), self.physical_dims(), self.rotation);
+        let data: Vec<u8> = gray.into_iter()
+                                .map(|c| transform_color(quantize_gray(c, levels), inverted, monochrome))
+                                .collect();
+        writer.write_image_data(&data).chain_err(|| "Can't write data to file.")?;
         Ok(())
     }
 
@@ -155,12 +179,62 @@ impl Framebuffer for KoboFramebuffer {
         self.flags ^= EPDC_FLAG_FORCE_MONOCHROME;
     }
 
-    fn width(&self) -> u32 {
-        self.var_info.xres
+    fn set_rotation(&mut self, rotation: Rotation) {
+        self.rotation = rotation;
+    }
+
+    fn rotation(&self) -> Rotation {
+        self.rotation
+    }
+
+    fn set_gamma(&mut self, gamma: f32) {
+        self.gamma_table = gamma_table(gamma);
+    }
+
+    fn gamma_table(&self) -> &[f32; 256] {
+        &self.gamma_table
+    }
+
+    fn push_clip(&mut self, rect: &Rectangle) {
+        self.clips.push(rect);
+    }
+
+    fn pop_clip(&mut self) {
+        self.clips.pop();
     }
 
-    fn height(&self) -> u32 {
-        self.var_info.yres
+    // The bytes of the physical rows covered by the rectangle are copied as is.
+    fn snapshot(&self, rect: &Rectangle) -> Snapshot {
+        let rect = rect.intersection(&self.rect()).unwrap_or_default();
+        let physical_rect = self.rotation.transform_rect(&rect, self.physical_dims());
+        let bytes = self.as_bytes();
+        let mut data = Vec::with_capacity(rect.area() as usize * self.bytes_per_pixel as usize);
+        for y in physical_rect.min.y..physical_rect.max.y {
+            let start = self.address(physical_rect.min.x as u32, y as u32);
+            let end = start + physical_rect.width() as usize * self.bytes_per_pixel as usize;
+            data.extend_from_slice(&bytes[start..end]);
+        }
+        Snapshot { rect, data }
+    }
+
+    fn restore(&mut self, snapshot: &Snapshot) {
+        let physical_rect = self.rotation.transform_rect(&snapshot.rect, self.physical_dims());
+        let row_length = physical_rect.width() as usize * self.bytes_per_pixel as usize;
+        if snapshot.data.len() < row_length * physical_rect.height() as usize {
+            return;
+        }
+        for (i, y) in (physical_rect.min.y..physical_rect.max.y).enumerate() {
+            let start = self.address(physical_rect.min.x as u32, y as u32);
+            let row = &snapshot.data[i * row_length..(i + 1) * row_length];
+            unsafe {
+                let spot = self.frame.offset(start as isize) as *mut u8;
+                ptr::copy_nonoverlapping(row.as_ptr(), spot, row_length);
+            }
+        }
+    }
+
+    fn dims(&self) -> (u32, u32) {
+        self.rotation.dims(self.physical_dims())
     }
 }
 
@@ -196,10 +270,10 @@ impl KoboFramebuffer {
         if frame == libc::MAP_FAILED {
             bail!(Error::with_chain(io::Error::last_os_error(), "Can't map memory."));
         } else {
-            let (set_pixel_rgb, get_pixel_rgb, as_rgb): (SetPixelRgb, GetPixelRgb, AsRgb) = if var_info.bits_per_pixel > 16 {
-                (set_pixel_rgb_32, get_pixel_rgb_32, as_rgb_32)
+            let (set_pixel_rgb, get_pixel_rgb, as_gray): (SetPixelRgb, GetPixelRgb, AsGray) = if var_info.bits_per_pixel > 16 {
+                (set_pixel_rgb_32, get_pixel_rgb_32, as_gray_32)
             } else {
-                (set_pixel_rgb_16, get_pixel_rgb_16, as_rgb_16)
+                (set_pixel_rgb_16, get_pixel_rgb_16, as_gray_16)
             };
             Ok(KoboFramebuffer {
                    device,
@@ -209,7 +283,10 @@ impl KoboFramebuffer {
                    flags: 0,
                    set_pixel_rgb,
                    get_pixel_rgb,
-                   as_rgb,
+                   as_gray,
+                   rotation: Rotation::default(),
+                   gamma_table: gamma_table(1.0),
+                   clips: ClipStack::default(),
                    bytes_per_pixel: bytes_per_pixel as u8,
                    var_info,
                    fix_info,
@@ -217,6 +294,22 @@ impl KoboFramebuffer {
         }
     }
     
+    // The dimensions of the panel, regardless of the rotation.
+    fn physical_dims(&self) -> (u32, u32) {
+        (self.var_info.xres, self.var_info.yres)
+    }
+
+    fn physical_position(&self, x: u32, y: u32) -> (u32, u32) {
+        let pt = self.rotation.transform(pt!(x as i32, y as i32), self.physical_dims());
+        (pt.x as u32, pt.y as u32)
+    }
+
+    // The offset, within the frame, of the pixel at the given physical coordinates.
+    fn address(&self, x: u32, y: u32) -> usize {
+        (self.var_info.xoffset + x) as usize * self.bytes_per_pixel as usize +
+        (self.var_info.yoffset + y) as usize * self.fix_info.line_length as usize
+    }
+
     fn as_bytes(&self) -> &[u8] {
         unsafe { slice::from_raw_parts(self.frame as *const u8, self.frame_size) }
     }
@@ -282,34 +375,30 @@ fn get_pixel_rgb_32(fb: &KoboFramebuffer, x: u32, y: u32) -> [u8; 3] {
     }
 }
 
-fn as_rgb_16(fb: &KoboFramebuffer) -> Vec<u8> {
-    let (width, height) = fb.dims();
-    let mut rgb888 = Vec::with_capacity((width * height * 3) as usize);
+// The gray pixels are written with equal components, the green one has the most bits.
+fn as_gray_16(fb: &KoboFramebuffer) -> Vec<u8> {
+    let (width, height) = fb.physical_dims();
+    let mut gray8 = Vec::with_capacity((width * height) as usize);
     let rgb565 = fb.as_bytes();
     let virtual_width = fb.var_info.xres_virtual as usize;
     for (_, pair) in rgb565.chunks(2).take(height as usize * virtual_width).enumerate()
                            .filter(|&(i, _)| i % virtual_width < width as usize) {
-        let red = pair[1] & 0b1111_1000;
         let green = ((pair[1] & 0b0000_0111) << 5) | ((pair[0] & 0b1110_0000) >> 3);
-        let blue = (pair[0] & 0b0001_1111) << 3;
-        rgb888.extend_from_slice(&[red, green, blue]);
+        gray8.push(green | green >> 6);
     }
-    rgb888
+    gray8
 }
 
-fn as_rgb_32(fb: &KoboFramebuffer) -> Vec<u8> {
-    let (width, height) = fb.dims();
-    let mut rgb888 = Vec::with_capacity((width * height * 3) as usize);
+fn as_gray_32(fb: &KoboFramebuffer) -> Vec<u8> {
+    let (width, height) = fb.physical_dims();
+    let mut gray8 = Vec::with_capacity((width * height) as usize);
     let bgra8888 = fb.as_bytes();
     let virtual_width = fb.var_info.xres_virtual as usize;
     for (_, bgra) in bgra8888.chunks(4).take(height as usize * virtual_width).enumerate()
                            .filter(|&(i, _)| i % virtual_width < width as usize) {
-        let red = bgra[2];
-        let green = bgra[1];
-        let blue = bgra[0];
-        rgb888.extend_from_slice(&[red, green, blue]);
+        gray8.push(bgra[1]);
     }
-    rgb888
+    gray8
 }
 
 pub fn fix_screen_info(device: &File) -> Result<FixScreenInfo> {
@@ -2,13 +2,230 @@ mod mxcfb_sys;
 mod kobo;
 mod image;
 
-use geom::{Point, Rectangle, surface_area, nearest_segment_point, lerp};
-use geom::{CornerSpec, BorderSpec, ColorSource, Vec2};
+use geom::{Point, Rectangle, surface_area, disk_coverage, nearest_segment_point, ellipse_distance, lerp};
+use geom::{CornerSpec, BorderSpec, BorderStyle, ColorSource, Vec2, Axis, border_position};
+use device::CURRENT_DEVICE;
 use errors::*;
 
 pub use self::kobo::KoboFramebuffer;
 pub use self::image::ImageFramebuffer;
 
+// The color seen on screen, given the inversion and monochrome flags of the display.
+#[inline]
+pub fn transform_color(color: u8, inverted: bool, monochrome: bool) -> u8 {
+    let color = if inverted {
+        255 - color
+    } else {
+        color
+    };
+    if monochrome {
+        (color > 127) as u8 * 255
+    } else {
+        color
+    }
+}
+
+// The closest of *levels* evenly spaced gray levels.
+#[inline]
+pub fn quantize_gray(color: u8, levels: u16) -> u8 {
+    let steps = levels.max(2) as u32 - 1;
+    ((color as u32 * steps + 127) / 255 * 255 / steps) as u8
+}
+
+// The opacities of the 256 coverage values of an anti-aliased glyph.
+// Gammas above 1 darken the edges, a gamma of 1 keeps the coverage as is.
+pub fn gamma_table(gamma: f32) -> [f32; 256] {
+    let mut table = [0.0; 256];
+    for (i, alpha) in table.iter_mut().enumerate() {
+        *alpha = (i as f32 / 255.0).powf(1.0 / gamma);
+    }
+    table
+}
+
+#[derive(Debug, Copy, Clone, Eq, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub enum Dithering {
+    // Error diffusion, slower but smoother.
+    FloydSteinberg,
+    // A 4×4 Bayer matrix.
+    Ordered,
+}
+
+const BAYER_MATRIX: [[i32; 4]; 4] = [[ 0,  8,  2, 10],
+                                     [12,  4, 14,  6],
+                                     [ 3, 11,  1,  9],
+                                     [15,  7, 13,  5]];
+
+// The gray level, among *levels* levels, of the pixel at *x*, *y* in an ordered dithering of *color*.
+#[inline]
+pub fn ordered_gray(color: u8, levels: u16, x: usize, y: usize) -> u8 {
+    let step = 255 / (levels.max(2) as i32 - 1);
+    let offset = (2 * BAYER_MATRIX[y % 4][x % 4] + 1 - 16) * step / 32;
+    let value = (color as i32 + offset).max(0).min(255) as u8;
+    quantize_gray(value, levels)
+}
+
+// Reduces the colors of a grayscale buffer of the given *width* to *levels* gray levels.
+pub fn dither(buf: &mut [u8], width: usize, levels: u16, dithering: Dithering) {
+    if width == 0 {
+        return;
+    }
+    match dithering {
+        Dithering::FloydSteinberg => {
+            let height = buf.len() / width;
+            let mut values: Vec<i32> = buf.iter().map(|&c| c as i32).collect();
+            for y in 0..height {
+                for x in 0..width {
+                    let addr = y * width + x;
+                    let old = values[addr].max(0).min(255) as u8;
+                    let new = quantize_gray(old, levels);
+                    buf[addr] = new;
+                    let error = old as i32 - new as i32;
+                    if x + 1 < width {
+                        values[addr + 1] += error * 7 / 16;
+                    }
+                    if y + 1 < height {
+                        if x > 0 {
+                            values[addr + width - 1] += error * 3 / 16;
+                        }
+                        values[addr + width] += error * 5 / 16;
+                        if x + 1 < width {
+                            values[addr + width + 1] += error / 16;
+                        }
+                    }
+                }
+            }
+        },
+        Dithering::Ordered => {
+            for (addr, color) in buf.iter_mut().enumerate() {
+                *color = ordered_gray(*color, levels, addr % width, addr / width);
+            }
+        },
+    }
+}
+
+// The rotation of what's drawn, clockwise, relatively to the natural orientation of the panel.
+#[derive(Debug, Copy, Clone, Eq, PartialEq)]
+pub enum Rotation {
+    Upright,
+    Clockwise,
+    UpsideDown,
+    Counterclockwise,
+}
+
+impl Default for Rotation {
+    fn default() -> Self {
+        Rotation::Upright
+    }
+}
+
+impl Rotation {
+    pub fn from_degrees(degrees: u16) -> Option<Rotation> {
+        match degrees {
+            0 => Some(Rotation::Upright),
+            90 => Some(Rotation::Clockwise),
+            180 => Some(Rotation::UpsideDown),
+            270 => Some(Rotation::Counterclockwise),
+            _ => None,
+        }
+    }
+
+    pub fn inverse(self) -> Rotation {
+        match self {
+            Rotation::Clockwise => Rotation::Counterclockwise,
+            Rotation::Counterclockwise => Rotation::Clockwise,
+            _ => self,
+        }
+    }
+
+    // The dimensions of the rotated space.
+    pub fn dims(self, dims: (u32, u32)) -> (u32, u32) {
+        match self {
+            Rotation::Clockwise | Rotation::Counterclockwise => (dims.1, dims.0),
+            _ => dims,
+        }
+    }
+
+    // Maps a point to the target space of dimensions *dims*.
+    pub fn transform(self, pt: Point, dims: (u32, u32)) -> Point {
+        let (width, height) = (dims.0 as i32, dims.1 as i32);
+        match self {
+            Rotation::Upright => pt,
+            Rotation::Clockwise => pt!(width - 1 - pt.y, pt.x),
+            Rotation::UpsideDown => pt!(width - 1 - pt.x, height - 1 - pt.y),
+            Rotation::Counterclockwise => pt!(pt.y, height - 1 - pt.x),
+        }
+    }
+
+    // Maps a rectangle to the target space of dimensions *dims*.
+    pub fn transform_rect(self, rect: &Rectangle, dims: (u32, u32)) -> Rectangle {
+        let (width, height) = (dims.0 as i32, dims.1 as i32);
+        match self {
+            Rotation::Upright => *rect,
+            Rotation::Clockwise => rect![width - rect.max.y, rect.min.x,
+                                         width - rect.min.y, rect.max.x],
+            Rotation::UpsideDown => rect![width - rect.max.x, height - rect.max.y,
+                                          width - rect.min.x, height - rect.min.y],
+            Rotation::Counterclockwise => rect![rect.min.y, height - rect.max.x,
+                                                rect.max.y, height - rect.min.x],
+        }
+    }
+}
+
+// Reorders the pixels of a buffer of dimensions *dims* as seen once rotated.
+pub fn rotate_buffer(buf: &[u8], dims: (u32, u32), rotation: Rotation) -> Vec<u8> {
+    let (width, height) = rotation.dims(dims);
+    let mut rotated = Vec::with_capacity(buf.len());
+    for y in 0..height as i32 {
+        for x in 0..width as i32 {
+            let pt = rotation.transform(pt!(x, y), dims);
+            rotated.push(buf[(pt.y * dims.0 as i32 + pt.x) as usize]);
+        }
+    }
+    rotated
+}
+
+// The pixels of a region of a framebuffer, in the format of that framebuffer.
+#[derive(Debug, Clone)]
+pub struct Snapshot {
+    pub rect: Rectangle,
+    pub data: Vec<u8>,
+}
+
+// The nested regions outside of which nothing is drawn.
+#[derive(Debug, Clone, Default)]
+pub struct ClipStack {
+    rects: Vec<Rectangle>,
+}
+
+impl ClipStack {
+    // The new region is the intersection of *rect* with the current one.
+    pub fn push(&mut self, rect: &Rectangle) {
+        let clip = match self.rects.last() {
+            Some(last) => last.intersection(rect).unwrap_or_else(|| Rectangle::new(rect.min, rect.min)),
+            None => *rect,
+        };
+        self.rects.push(clip);
+    }
+
+    pub fn pop(&mut self) {
+        self.rects.pop();
+    }
+
+    #[inline]
+    pub fn includes(&self, x: u32, y: u32) -> bool {
+        self.rects.last().map_or(true, |r| r.includes(&pt!(x as i32, y as i32)))
+    }
+
+    // The visible part of *rect*.
+    pub fn clip(&self, rect: &Rectangle) -> Option<Rectangle> {
+        match self.rects.last() {
+            Some(last) => last.intersection(rect),
+            None => Some(*rect),
+        }
+    }
+}
+
 #[derive(Debug, Copy, Clone, Eq, PartialEq)]
 pub enum UpdateMode {
     Gui,
@@ -18,6 +235,43 @@ pub enum UpdateMode {
     FastMono,
 }
 
+impl UpdateMode {
+    // The higher the rank, the better the result.
+    fn rank(self) -> u8 {
+        match self {
+            UpdateMode::FastMono => 0,
+            UpdateMode::Fast => 1,
+            UpdateMode::Gui => 2,
+            UpdateMode::Partial => 3,
+            UpdateMode::Full => 4,
+        }
+    }
+
+    fn is_fast(self) -> bool {
+        self == UpdateMode::Fast || self == UpdateMode::FastMono
+    }
+}
+
+// Adds an update to the pending ones. The updates whose regions touch are merged
+// into one update of their bounding rectangle, with the best of their modes.
+// The fast updates are only merged together: they'd otherwise be upgraded to slow
+// updates of a larger region.
+pub fn coalesce_update(pending: &mut Vec<(Rectangle, UpdateMode)>, rect: Rectangle, mode: UpdateMode) {
+    let mut rect = rect;
+    let mut mode = mode;
+    // The merged region might reach other pending updates.
+    while let Some(index) = pending.iter().position(|&(ref other, other_mode)| {
+        other_mode.is_fast() == mode.is_fast() && other.touches(&rect)
+    }) {
+        let (other_rect, other_mode) = pending.remove(index);
+        rect.absorb(&other_rect);
+        if other_mode.rank() > mode.rank() {
+            mode = other_mode;
+        }
+    }
+    pending.push((rect, mode));
+}
+
 #[derive(Debug, Clone)]
 pub struct Pixmap {
     pub width: i32,
@@ -34,6 +288,24 @@ pub trait Framebuffer {
     fn save(&self, path: &str) -> Result<()>;
     fn toggle_inverted(&mut self);
     fn toggle_monochrome(&mut self);
+    fn set_rotation(&mut self, rotation: Rotation);
+    fn rotation(&self) -> Rotation;
+    fn set_gamma(&mut self, gamma: f32);
+    // The alpha of each glyph coverage, for the current gamma.
+    fn gamma_table(&self) -> &[f32; 256];
+    // Restricts the drawing to the intersection of *rect* and the current clipping region.
+    fn push_clip(&mut self, rect: &Rectangle);
+    fn pop_clip(&mut self);
+    // Copies the pixels of the part of *rect* within the framebuffer.
+    fn snapshot(&self, rect: &Rectangle) -> Snapshot;
+    // Puts back the pixels of *snapshot*, regardless of the clipping region.
+    fn restore(&mut self, snapshot: &Snapshot);
+
+    // Blends a pixel partially covered by a glyph.
+    fn set_covered_pixel(&mut self, x: u32, y: u32, color: u8, coverage: u8) {
+        let alpha = self.gamma_table()[coverage as usize];
+        self.set_blended_pixel(x, y, color, alpha);
+    }
 
     fn width(&self) -> u32 {
         let (width, _) = self.dims();
@@ -67,7 +339,34 @@ pub trait Framebuffer {
         }
     }
 
-    fn draw_blended_rectangle(&mut self, rect: &Rectangle, color: u8, alpha: f32) {
+    // Fills *rect* with the colors going from *from_color* to *to_color*, along *axis*,
+    // dithered to the gray levels of the panel to avoid banding.
+    fn draw_gradient(&mut self, rect: &Rectangle, from_color: u8, to_color: u8, axis: Axis) {
+        let levels = CURRENT_DEVICE.gray_levels();
+        let length = match axis {
+            Axis::Horizontal => rect.width(),
+            Axis::Vertical => rect.height(),
+        };
+        let last = (length.max(2) - 1) as f32;
+        for y in rect.min.y..rect.max.y {
+            for x in rect.min.x..rect.max.x {
+                let position = match axis {
+                    Axis::Horizontal => x - rect.min.x,
+                    Axis::Vertical => y - rect.min.y,
+                };
+                let color = lerp(from_color as f32, to_color as f32, position as f32 / last).round() as u8;
+                let color = ordered_gray(color, levels, x as usize, y as usize);
+                self.set_pixel(x as u32, y as u32, color);
+            }
+        }
+    }
+
+    // Mixes *color* with the pixels of *rect*, *alpha* ranges from 0 (transparent) to 255 (opaque).
+    fn blend_rectangle(&mut self, rect: &Rectangle, color: u8, alpha: u8) {
+        if alpha == 0 {
+            return;
+        }
+        let alpha = alpha as f32 / 255.0;
         for y in rect.min.y..rect.max.y {
             for x in rect.min.x..rect.max.x {
                 self.set_blended_pixel(x as u32, y as u32, color, alpha);
@@ -75,24 +374,93 @@ pub trait Framebuffer {
         }
     }
 
+    // Draws the segment from *start* to *end*, both included.
+    // The parts outside of the framebuffer are skipped.
+    fn draw_line(&mut self, start: &Point, end: &Point, thickness: u16, color: u8) {
+        let fb_rect = self.rect();
+        let thickness = thickness.max(1) as i32;
+        let offset = thickness / 2;
+
+        let straight = if start.y == end.y {
+            Some(rect![start.x.min(end.x), start.y - offset,
+                       start.x.max(end.x) + 1, start.y - offset + thickness])
+        } else if start.x == end.x {
+            Some(rect![start.x - offset, start.y.min(end.y),
+                       start.x - offset + thickness, start.y.max(end.y) + 1])
+        } else {
+            None
+        };
+
+        if let Some(rect) = straight {
+            if let Some(rect) = rect.intersection(&fb_rect) {
+                self.draw_rectangle(&rect, color);
+            }
+            return;
+        }
+
+        let dx = (end.x - start.x).abs();
+        let dy = -(end.y - start.y).abs();
+        let sx = if start.x < end.x { 1 } else { -1 };
+        let sy = if start.y < end.y { 1 } else { -1 };
+        // Steep lines are thickened horizontally, the others vertically.
+        let steep = -dy > dx;
+        let mut err = dx + dy;
+        let mut pt = *start;
+
+        loop {
+            let span = if steep {
+                rect![pt.x - offset, pt.y, pt.x - offset + thickness, pt.y + 1]
+            } else {
+                rect![pt.x, pt.y - offset, pt.x + 1, pt.y - offset + thickness]
+            };
+            if let Some(span) = span.intersection(&fb_rect) {
+                self.draw_rectangle(&span, color);
+            }
+            if pt == *end {
+                break;
+            }
+            let e2 = 2 * err;
+            if e2 >= dy {
+                err += dy;
+                pt.x += sx;
+            }
+            if e2 <= dx {
+                err += dx;
+                pt.y += sy;
+            }
+        }
+    }
+
     fn draw_rectangle_outline(&mut self, rect: &Rectangle, border: &BorderSpec) {
         let BorderSpec { thickness: border_thickness,
-                         color: border_color } = *border;
-        self.draw_rectangle(&rect![rect.min.x, rect.min.y,
-                                   rect.max.x - border_thickness as i32,
-                                   rect.min.y + border_thickness as i32],
-                            border_color);
-        self.draw_rectangle(&rect![rect.max.x - border_thickness as i32, rect.min.y,
-                                   rect.max.x, rect.max.y - border_thickness as i32],
-                            border_color);
-        self.draw_rectangle(&rect![rect.min.x + border_thickness as i32,
-                                   rect.max.y - border_thickness as i32,
-                                   rect.max.x, rect.max.y],
-                            border_color);
-        self.draw_rectangle(&rect![rect.min.x, rect.min.y + border_thickness as i32,
-                                   rect.min.x + border_thickness as i32,
-                                   rect.max.y],
-                            border_color);
+                         color: border_color,
+                         style: border_style } = *border;
+        let sides = [rect![rect.min.x, rect.min.y,
+                           rect.max.x - border_thickness as i32,
+                           rect.min.y + border_thickness as i32],
+                     rect![rect.max.x - border_thickness as i32, rect.min.y,
+                           rect.max.x, rect.max.y - border_thickness as i32],
+                     rect![rect.min.x + border_thickness as i32,
+                           rect.max.y - border_thickness as i32,
+                           rect.max.x, rect.max.y],
+                     rect![rect.min.x, rect.min.y + border_thickness as i32,
+                           rect.min.x + border_thickness as i32,
+                           rect.max.y]];
+        for side in &sides {
+            if border_style == BorderStyle::Solid {
+                self.draw_rectangle(side, border_color);
+                continue;
+            }
+            for y in side.min.y..side.max.y {
+                for x in side.min.x..side.max.x {
+                    let position = border_position(rect, (0, 0, 0, 0), border_thickness,
+                                                   vec2!(x as f32, y as f32) + 0.5);
+                    if border_style.inks(position, border_thickness) {
+                        self.set_pixel(x as u32, y as u32, border_color);
+                    }
+                }
+            }
+        }
     }
 
     fn draw_pixmap(&mut self, pixmap: &Pixmap, pt: &Point) {
@@ -119,6 +487,42 @@ pub trait Framebuffer {
         }
     }
 
+    // Draws the part of *pixmap* within *rect*, dithered to the gray levels of the panel.
+    fn draw_dithered_pixmap(&mut self, pixmap: &Pixmap, rect: &Rectangle, pt: &Point, dithering: Dithering) {
+        let clipped = match rect.intersection(&rect![0, 0, pixmap.width, pixmap.height]) {
+            Some(clipped) => clipped,
+            None => return,
+        };
+        let pt = *pt + (clipped.min - rect.min);
+        let rect = clipped;
+        let width = rect.width() as usize;
+        let mut buf = Vec::with_capacity(width * rect.height() as usize);
+        for y in rect.min.y..rect.max.y {
+            let start = (y * pixmap.width + rect.min.x) as usize;
+            buf.extend_from_slice(&pixmap.buf[start..start + width]);
+        }
+        dither(&mut buf, width, CURRENT_DEVICE.gray_levels(), dithering);
+        for (addr, color) in buf.into_iter().enumerate() {
+            let px = pt.x + (addr % width) as i32;
+            let py = pt.y + (addr / width) as i32;
+            self.set_pixel(px as u32, py as u32, color);
+        }
+    }
+
+    // Mixes each pixel of *pixmap* with the pixel below it, according to the matching opacity in *alphas*.
+    fn blend_pixmap(&mut self, pixmap: &Pixmap, alphas: &[u8], pt: &Point) {
+        for y in 0..pixmap.height {
+            for x in 0..pixmap.width {
+                let addr = (y * pixmap.width + x) as usize;
+                if alphas[addr] == 0 {
+                    continue;
+                }
+                let alpha = alphas[addr] as f32 / 255.0;
+                self.set_blended_pixel((x + pt.x) as u32, (y + pt.y) as u32, pixmap.buf[addr], alpha);
+            }
+        }
+    }
+
     fn draw_blended_pixmap(&mut self, pixmap: &Pixmap, pt: &Point, color: u8) {
         for y in 0..pixmap.height {
             for x in 0..pixmap.width {
@@ -164,9 +568,7 @@ pub trait Framebuffer {
                 }
                 if let Some((center, radius)) = pole {
                     let v = vec2!((x - center.x) as f32, (y - center.y) as f32) + 0.5;
-                    let angle = v.angle();
-                    let dist = v.length() - radius as f32;
-                    alpha = surface_area(dist, angle);
+                    alpha = disk_coverage(v, radius as f32);
                 }
                 self.set_blended_pixel(x as u32, y as u32, color, alpha);
             }
@@ -189,7 +591,8 @@ pub trait Framebuffer {
         };
 
         let BorderSpec { thickness: border_thickness,
-                         color: border_color } = *border;
+                         color: border_color,
+                         style: border_style } = *border;
         let nw_c = rect.min + nw;
         let ne_c = pt!(rect.max.x - ne, rect.min.y + ne);
         let se_c = rect.max - se;
@@ -200,6 +603,11 @@ pub trait Framebuffer {
                 let mut alpha = 1.0;
                 let mut pole = None;
                 let mut color = color.color(x, y);
+                // The fill replaces the border within the gaps of the dashes and dots.
+                let inked = border_style == BorderStyle::Solid ||
+                            border_style.inks(border_position(rect, (nw, ne, se, sw), border_thickness,
+                                                              vec2!(x as f32, y as f32) + 0.5),
+                                              border_thickness);
                 if x < nw_c.x && y < nw_c.y {
                     pole = Some((nw_c, nw));
                 } else if x >= ne_c.x && y < ne_c.y {
@@ -211,25 +619,18 @@ pub trait Framebuffer {
                 }
                 if let Some((center, radius)) = pole {
                     let small_radius = radius - border_thickness as i32;
-                    let mid_radius = 0.5 * (radius as f32 + small_radius as f32);
                     let v = vec2!((x - center.x) as f32, (y - center.y) as f32) + 0.5;
-                    let angle = v.angle();
-                    let dist = v.length();
-                    if dist < mid_radius {
-                        let delta_dist = small_radius as f32 - dist;
-                        alpha = surface_area(delta_dist, angle);
-                        color = lerp(color as f32, border_color as f32, alpha) as u8;
-                        alpha = 1.0;
-                    } else {
-                        let delta_dist = dist - radius as f32;
-                        color = border_color;
-                        alpha = surface_area(delta_dist, angle);
+                    // The fill covers the inner disk, the border the rest of the outer disk.
+                    let inner_alpha = disk_coverage(v, small_radius as f32);
+                    alpha = disk_coverage(v, radius as f32);
+                    if alpha > 0.0 && inked {
+                        color = lerp(border_color as f32, color as f32, inner_alpha / alpha) as u8;
                     }
                 } else {
-                    if x < rect.min.x + border_thickness as i32 ||
-                       x >= rect.max.x - border_thickness as i32 ||
-                       y < rect.min.y + border_thickness as i32 ||
-                       y >= rect.max.y - border_thickness as i32 {
+                    if inked && (x < rect.min.x + border_thickness as i32 ||
+                                 x >= rect.max.x - border_thickness as i32 ||
+                                 y < rect.min.y + border_thickness as i32 ||
+                                 y >= rect.max.y - border_thickness as i32) {
                         color = border_color;
                     }
                 }
@@ -319,4 +720,149 @@ pub trait Framebuffer {
             }
         }
     }
+
+    // Draws the outline of the circle when *thickness* is given, fills it otherwise.
+    fn draw_circle(&mut self, center: &Point, radius: i32, thickness: Option<u16>, color: u8) {
+        self.draw_ellipse(center, (radius, radius), thickness, color);
+    }
+
+    // *radii* holds the horizontal and vertical radii.
+    fn draw_ellipse(&mut self, center: &Point, radii: (i32, i32), thickness: Option<u16>, color: u8) {
+        let (a, b) = radii;
+        if a <= 0 || b <= 0 {
+            return;
+        }
+
+        let rect = rect![center.x - a, center.y - b, center.x + a, center.y + b];
+        let rect = match rect.intersection(&self.rect()) {
+            Some(rect) => rect,
+            None => return,
+        };
+        let outer_radii = vec2!(a as f32, b as f32);
+        let inner_radii = thickness.map(|t| outer_radii - t as f32)
+                                   .filter(|r| r.x > 0.0 && r.y > 0.0);
+
+        for y in rect.min.y..rect.max.y {
+            for x in rect.min.x..rect.max.x {
+                let v = vec2!((x - center.x) as f32, (y - center.y) as f32) + 0.5;
+                let (delta_dist, angle) = ellipse_distance(v, outer_radii);
+                let mut alpha = surface_area(delta_dist, angle);
+                if let Some(inner_radii) = inner_radii {
+                    let (delta_dist, angle) = ellipse_distance(v, inner_radii);
+                    alpha = (alpha - surface_area(delta_dist, angle)).max(0.0);
+                }
+                if alpha > 0.0 {
+                    self.set_blended_pixel(x as u32, y as u32, color, alpha);
+                }
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{transform_color, quantize_gray, dither, Dithering, Rotation, rotate_buffer};
+    use super::{gamma_table, coalesce_update, UpdateMode};
+
+    #[test]
+    fn screen_colors() {
+        assert_eq!(transform_color(0x30, false, false), 0x30);
+        assert_eq!(transform_color(0x30, true, false), 0xCF);
+        assert_eq!(transform_color(0x30, false, true), 0x00);
+        assert_eq!(transform_color(0x30, true, true), 0xFF);
+        assert_eq!(quantize_gray(0x00, 16), 0x00);
+        assert_eq!(quantize_gray(0x10, 16), 0x11);
+        assert_eq!(quantize_gray(0x7F, 16), 0x77);
+        assert_eq!(quantize_gray(0xFC, 16), 0xFF);
+        assert_eq!(quantize_gray(0x7F, 2), 0x00);
+        assert_eq!(quantize_gray(0x80, 2), 0xFF);
+    }
+
+    #[test]
+    fn merge_touching_updates() {
+        let mut pending = Vec::new();
+        // The rows of a list, a distant button and a row rendered again.
+        let renders = [(rect![0, 0, 100, 20], UpdateMode::Gui),
+                       (rect![0, 20, 100, 40], UpdateMode::Gui),
+                       (rect![200, 0, 250, 20], UpdateMode::Partial),
+                       (rect![0, 40, 100, 60], UpdateMode::Gui),
+                       (rect![0, 20, 100, 40], UpdateMode::Fast),
+                       (rect![100, 60, 120, 80], UpdateMode::Gui)];
+        for &(rect, mode) in &renders {
+            coalesce_update(&mut pending, rect, mode);
+        }
+        assert_eq!(pending, vec![(rect![200, 0, 250, 20], UpdateMode::Partial),
+                                 (rect![0, 0, 100, 60], UpdateMode::Gui),
+                                 (rect![0, 20, 100, 40], UpdateMode::Fast),
+                                 (rect![100, 60, 120, 80], UpdateMode::Gui)]);
+        coalesce_update(&mut pending, rect![90, 50, 210, 70], UpdateMode::Full);
+        assert_eq!(pending, vec![(rect![0, 20, 100, 40], UpdateMode::Fast),
+                                 (rect![0, 0, 250, 80], UpdateMode::Full)]);
+        coalesce_update(&mut pending, rect![0, 40, 100, 50], UpdateMode::FastMono);
+        assert_eq!(pending, vec![(rect![0, 0, 250, 80], UpdateMode::Full),
+                                 (rect![0, 20, 100, 50], UpdateMode::Fast)]);
+    }
+
+    #[test]
+    fn darken_glyph_edges() {
+        let neutral = gamma_table(1.0);
+        let dark = gamma_table(1.8);
+        for i in 0..256 {
+            assert_eq!(neutral[i], i as f32 / 255.0);
+            assert!(dark[i] >= neutral[i]);
+        }
+        assert_eq!(dark[0], 0.0);
+        assert_eq!(dark[255], 1.0);
+        assert!(dark[128] > 0.6);
+    }
+
+    #[test]
+    fn rotate_points_and_rectangles() {
+        let dims = (4, 3);
+        let rotations = [Rotation::Upright, Rotation::Clockwise,
+                         Rotation::UpsideDown, Rotation::Counterclockwise];
+        assert_eq!(Rotation::Clockwise.transform(pt!(0, 0), dims), pt!(3, 0));
+        assert_eq!(Rotation::Counterclockwise.transform(pt!(0, 0), dims), pt!(0, 2));
+        for &rotation in &rotations {
+            let logical = rotation.dims(dims);
+            for y in 0..logical.1 as i32 {
+                for x in 0..logical.0 as i32 {
+                    let pt = rotation.transform(pt!(x, y), dims);
+                    assert!(pt.x >= 0 && pt.x < 4 && pt.y >= 0 && pt.y < 3);
+                    assert_eq!(rotation.inverse().transform(pt, logical), pt!(x, y));
+                    // The rectangle of a single pixel lands on the pixel.
+                    let rect = rotation.transform_rect(&rect![x, y, x + 1, y + 1], dims);
+                    assert_eq!(rect, rect![pt.x, pt.y, pt.x + 1, pt.y + 1]);
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn read_back_rotated_buffer() {
+        // 0 1
+        // 2 3
+        // 4 5
+        let buf = [0, 1, 2, 3, 4, 5];
+        assert_eq!(rotate_buffer(&buf, (2, 3), Rotation::Upright), buf.to_vec());
+        assert_eq!(rotate_buffer(&buf, (2, 3), Rotation::Clockwise), vec![1, 3, 5, 0, 2, 4]);
+        assert_eq!(rotate_buffer(&buf, (2, 3), Rotation::UpsideDown), vec![5, 4, 3, 2, 1, 0]);
+        assert_eq!(rotate_buffer(&buf, (2, 3), Rotation::Counterclockwise), vec![4, 2, 0, 5, 3, 1]);
+    }
+
+    #[test]
+    fn dither_to_panel_levels() {
+        for &dithering in &[Dithering::FloydSteinberg, Dithering::Ordered] {
+            // A flat gray between two levels is rendered with both.
+            let mut buf = vec![0x80; 64];
+            dither(&mut buf, 8, 2, dithering);
+            assert!(buf.iter().all(|&c| c == 0x00 || c == 0xFF));
+            let whites = buf.iter().filter(|&&c| c == 0xFF).count();
+            assert!(whites >= 24 && whites <= 40);
+            // The levels themselves are left untouched.
+            let mut buf = vec![0x00, 0x11, 0xEE, 0xFF];
+            dither(&mut buf, 2, 16, dithering);
+            assert_eq!(buf, vec![0x00, 0x11, 0xEE, 0xFF]);
+        }
+    }
 }
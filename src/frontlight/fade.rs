@@ -0,0 +1,136 @@
+use std::thread;
+use std::sync::Arc;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::time::Duration;
+use frontlight::LightLevels;
+use settings::FrontlightFade;
+
+// A transition between two light levels, performed in a fixed number of steps.
+// A fade can be cancelled from another thread: the ticking thread is expected
+// to stop as soon as the shared flag is raised.
+pub struct Fade {
+    from: LightLevels,
+    to: LightLevels,
+    steps: u32,
+    step: u32,
+    cancelled: Arc<AtomicBool>,
+}
+
+impl Fade {
+    pub fn new(from: LightLevels, to: LightLevels, steps: u32) -> Fade {
+        Fade {
+            from,
+            to,
+            steps: steps.max(1),
+            step: 0,
+            cancelled: Arc::new(AtomicBool::new(false)),
+        }
+    }
+
+    pub fn target(&self) -> LightLevels {
+        self.to
+    }
+
+    pub fn cancelled(&self) -> Arc<AtomicBool> {
+        Arc::clone(&self.cancelled)
+    }
+
+    pub fn cancel(&self) {
+        self.cancelled.store(true, Ordering::Relaxed);
+    }
+
+    // The fraction of the steps already performed.
+    pub fn progress(&self) -> f32 {
+        self.step as f32 / self.steps as f32
+    }
+
+    pub fn is_cancelled(&self) -> bool {
+        self.cancelled.load(Ordering::Relaxed)
+    }
+
+    // Calls *tick* once per step, spread over the given duration, from another thread.
+    // The ticks stop when the fade is cancelled or when *tick* returns false.
+    pub fn run<F>(&self, duration: Duration, tick: F) where F: Fn() -> bool + Send + 'static {
+        let cancelled = self.cancelled();
+        let steps = self.steps;
+        let interval = duration / steps;
+        thread::spawn(move || {
+            for _ in 0..steps {
+                thread::sleep(interval);
+                if cancelled.load(Ordering::Relaxed) || !tick() {
+                    break;
+                }
+            }
+        });
+    }
+
+    // Returns the levels of the next step, the last step yields the target.
+    pub fn next(&mut self) -> Option<LightLevels> {
+        if self.is_cancelled() || self.step >= self.steps {
+            return None;
+        }
+        self.step += 1;
+        if self.step == self.steps {
+            Some(self.to)
+        } else {
+            Some(self.from.interpolate(&self.to, self.step as f32 / self.steps as f32))
+        }
+    }
+}
+
+// The fade performed when the device resumes: the intensity rises from zero
+// to the given levels. Short sleeps aren't followed by a fade.
+pub fn wake_fade(levels: &LightLevels, asleep: Duration, settings: &FrontlightFade) -> Option<Fade> {
+    if settings.wake_duration == 0 || settings.frames == 0 || levels.intensity <= 0.0 ||
+       asleep < Duration::from_secs(settings.wake_threshold) {
+        return None;
+    }
+    let start = LightLevels { intensity: 0.0, warmth: levels.warmth };
+    Some(Fade::new(start, *levels, settings.frames))
+}
+
+#[cfg(test)]
+mod tests {
+    use std::time::Duration;
+    use frontlight::LightLevels;
+    use settings::FrontlightFade;
+    use super::{Fade, wake_fade};
+
+    #[test]
+    fn complete_fade() {
+        let from = LightLevels { intensity: 0.0, warmth: 0.0 };
+        let to = LightLevels { intensity: 40.0, warmth: 80.0 };
+        let mut fade = Fade::new(from, to, 4);
+        let levels: Vec<LightLevels> = (0..5).filter_map(|_| fade.next()).collect();
+        assert_eq!(levels.len(), 4);
+        assert_eq!(levels[1], LightLevels { intensity: 20.0, warmth: 40.0 });
+        assert_eq!(levels[3], to);
+    }
+
+    #[test]
+    fn interrupted_fade() {
+        let from = LightLevels { intensity: 0.0, warmth: 0.0 };
+        let to = LightLevels { intensity: 40.0, warmth: 80.0 };
+        let mut fade = Fade::new(from, to, 4);
+        let flag = fade.cancelled();
+        assert!(fade.next().is_some());
+        fade.cancel();
+        assert!(flag.load(::std::sync::atomic::Ordering::Relaxed));
+        assert_eq!(fade.next(), None);
+        assert_eq!(fade.target(), to);
+    }
+
+    #[test]
+    fn fade_on_wake() {
+        let settings = FrontlightFade::default();
+        let levels = LightLevels { intensity: 30.0, warmth: 60.0 };
+        let long = Duration::from_secs(settings.wake_threshold + 1);
+        let short = Duration::from_secs(settings.wake_threshold / 2);
+        let mut fade = wake_fade(&levels, long, &settings).unwrap();
+        assert_eq!(fade.next().map(|l| l.warmth), Some(60.0));
+        assert_eq!(fade.target(), levels);
+        assert!(wake_fade(&levels, short, &settings).is_none());
+        let off = LightLevels { intensity: 0.0, warmth: 60.0 };
+        assert!(wake_fade(&off, long, &settings).is_none());
+    }
+}
@@ -0,0 +1,48 @@
+use frontlight::{Frontlight, LightLevels, floored_intensity};
+
+// Keeps the intensity of the wrapped frontlight above a minimum,
+// except when the light is turned off.
+pub struct FlooredFrontlight {
+    inner: Box<Frontlight>,
+    min_intensity: f32,
+}
+
+impl FlooredFrontlight {
+    pub fn new(inner: Box<Frontlight>, min_intensity: f32) -> FlooredFrontlight {
+        FlooredFrontlight {
+            inner,
+            min_intensity,
+        }
+    }
+}
+
+impl Frontlight for FlooredFrontlight {
+    fn set_intensity(&mut self, value: f32) {
+        self.inner.set_intensity(floored_intensity(value, self.min_intensity));
+    }
+
+    fn set_warmth(&mut self, value: f32) {
+        self.inner.set_warmth(value);
+    }
+
+    fn levels(&self) -> LightLevels {
+        self.inner.levels()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use frontlight::{Frontlight, LightLevels};
+    use super::FlooredFrontlight;
+
+    #[test]
+    fn floored_frontlight() {
+        let mut frontlight = FlooredFrontlight::new(Box::new(LightLevels::default()), 3.0);
+        frontlight.set_intensity(1.0);
+        assert_eq!(frontlight.levels().intensity, 3.0);
+        frontlight.set_intensity(40.0);
+        assert_eq!(frontlight.levels().intensity, 40.0);
+        frontlight.set_intensity(0.0);
+        assert_eq!(frontlight.levels().intensity, 0.0);
+    }
+}
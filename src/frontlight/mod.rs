@@ -1,11 +1,22 @@
 mod standard;
 mod natural;
+mod fade;
+mod floored;
+mod schedule;
+mod solar;
 
 pub use self::standard::StandardFrontlight;
 pub use self::natural::NaturalFrontlight;
+pub use self::fade::{Fade, wake_fade};
+pub use self::floored::FlooredFrontlight;
+pub use self::schedule::{WarmthRamp, ScheduleTime};
+pub use self::schedule::{apply_schedule, apply_warmth_ramp, resolve_schedule};
 use geom::lerp;
 
-#[derive(Debug, Copy, Clone, Serialize, Deserialize)]
+// The levels used when there's nothing better to go by.
+pub const DEFAULT_LEVELS: LightLevels = LightLevels { intensity: 50.0, warmth: 0.0 };
+
+#[derive(Debug, Copy, Clone, PartialEq, Serialize, Deserialize)]
 pub struct LightLevels {
     pub intensity: f32,
     pub warmth: f32,
@@ -21,6 +32,14 @@ impl Default for LightLevels {
 }
 
 impl LightLevels {
+    // Raises a non-null intensity up to *min_intensity*.
+    pub fn floored(&self, min_intensity: f32) -> Self {
+        LightLevels {
+            intensity: floored_intensity(self.intensity, min_intensity),
+            warmth: self.warmth,
+        }
+    }
+
     pub fn interpolate(&self, other: &Self, t: f32) -> Self {
         LightLevels {
             intensity: lerp(self.intensity, other.intensity, t),
@@ -29,6 +48,31 @@ impl LightLevels {
     }
 }
 
+// A null intensity turns the light off, hence it's never raised.
+pub fn floored_intensity(intensity: f32, min_intensity: f32) -> f32 {
+    if intensity <= 0.0 {
+        0.0
+    } else {
+        intensity.max(min_intensity)
+    }
+}
+
+// The intensity sliders are perceptually uniform: a position in [0, 100] maps to
+// the hardware intensity through the given gamma, and a gamma of 1.0 is linear.
+pub fn intensity_from_position(position: f32, gamma: f32) -> f32 {
+    if gamma == 1.0 || gamma <= 0.0 {
+        return position;
+    }
+    100.0 * (position.max(0.0) / 100.0).powf(gamma)
+}
+
+pub fn position_from_intensity(intensity: f32, gamma: f32) -> f32 {
+    if gamma == 1.0 || gamma <= 0.0 {
+        return intensity;
+    }
+    100.0 * (intensity.max(0.0) / 100.0).powf(1.0 / gamma)
+}
+
 pub trait Frontlight {
     fn set_intensity(&mut self, value: f32);
     fn set_warmth(&mut self, value: f32);
@@ -48,3 +92,29 @@ impl Frontlight for LightLevels {
         *self
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::{LightLevels, intensity_from_position, position_from_intensity};
+
+    #[test]
+    fn floored_levels() {
+        let levels = LightLevels { intensity: 2.0, warmth: 30.0 };
+        assert_eq!(levels.floored(5.0), LightLevels { intensity: 5.0, warmth: 30.0 });
+        assert_eq!(levels.floored(1.0), levels);
+        let mid = LightLevels::default().interpolate(&levels, 0.5);
+        assert!(mid.floored(5.0).intensity >= 5.0);
+        assert_eq!(LightLevels::default().floored(5.0), LightLevels::default());
+    }
+
+    #[test]
+    fn gamma_mapping() {
+        assert_eq!(intensity_from_position(37.0, 1.0), 37.0);
+        assert_eq!(intensity_from_position(50.0, 2.0), 25.0);
+        assert_eq!(intensity_from_position(100.0, 2.2), 100.0);
+        assert_eq!(position_from_intensity(25.0, 2.0), 50.0);
+        assert_eq!(position_from_intensity(0.0, 2.2), 0.0);
+        let position = position_from_intensity(intensity_from_position(42.0, 2.2), 2.2);
+        assert!((position - 42.0).abs() < 1e-3);
+    }
+}
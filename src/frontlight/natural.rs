@@ -1,5 +1,4 @@
-use std::io::Read;
-use std::io::Write;
+use std::io::{self, Read, Write};
 use std::fs::File;
 use std::fs::OpenOptions;
 use std::path::PathBuf;
@@ -63,12 +62,17 @@ impl NaturalFrontlight {
         for (light, name) in FRONTLIGHT_DIRS.iter() {
             let dir = base.join(name);
             let mut buf = String::new();
-            let mut file = File::open(dir.join(FRONTLIGHT_MAX_VALUE))?;
-            file.read_to_string(&mut buf)?;
-            maxima.insert(*light, buf.trim_right().parse()?);
-            let file = OpenOptions::new().write(true).open(dir.join(FRONTLIGHT_VALUE))?;
+            let mut file = File::open(dir.join(FRONTLIGHT_MAX_VALUE)).map_err(|e| frontlight_error(e, name))?;
+            file.read_to_string(&mut buf).map_err(|e| frontlight_error(e, name))?;
+            let max_value = buf.trim_right().parse().map_err(|e| {
+                Error::with_chain(e, ErrorKind::FrontlightParse(name.to_string()))
+            })?;
+            maxima.insert(*light, max_value);
+            let file = OpenOptions::new().write(true).open(dir.join(FRONTLIGHT_VALUE))
+                                   .map_err(|e| frontlight_error(e, name))?;
             values.insert(*light, file);
-            let file = OpenOptions::new().write(true).open(dir.join(FRONTLIGHT_POWER))?;
+            let file = OpenOptions::new().write(true).open(dir.join(FRONTLIGHT_POWER))
+                                   .map_err(|e| frontlight_error(e, name))?;
             powers.insert(*light, file);
         }
         Ok(NaturalFrontlight {
@@ -80,25 +84,27 @@ impl NaturalFrontlight {
         })
     }
 
-    fn set(&mut self, c: LightColor, percent: f32) {
+    fn set(&mut self, c: LightColor, percent: f32) -> Result<()> {
         let max_value = self.maxima[&c] as f32;
         let value = (percent.max(0.0).min(100.0) / 100.0 * max_value) as i16;
+        let node = FRONTLIGHT_DIRS[&c];
         let mut file = &self.values[&c];
-        write!(file, "{}", value).unwrap();
+        write!(file, "{}", value).chain_err(|| ErrorKind::FrontlightWriteFailed(node.to_string()))?;
         let mut file = &self.powers[&c];
         let power = if value > 0 {
             FRONTLIGHT_POWER_ON
         } else {
             FRONTLIGHT_POWER_OFF
         };
-        write!(file, "{}", power).unwrap();
+        write!(file, "{}", power).chain_err(|| ErrorKind::FrontlightWriteFailed(node.to_string()))
     }
 
     fn update(&mut self, intensity: f32, warmth: f32) {
         let i = intensity / 100.0;
         let w = warmth / 100.0;
         let white = 80.0 * i * (1.0 - w).sqrt();
-        self.set(LightColor::White, white);
+        self.set(LightColor::White, white)
+            .map_err(|e| eprintln!("Can't set frontlight: {}", e)).ok();
 
         if self.values.len() == 3 {
             let green = 64.0 * (w * i).sqrt();
@@ -107,11 +113,14 @@ impl NaturalFrontlight {
             } else {
                 green + 20.0 + 7.0 * (1.0 - green / 64.0) + w * 4.0
             };
-            self.set(LightColor::Red, red);
-            self.set(LightColor::Green, green);
+            self.set(LightColor::Red, red)
+                .map_err(|e| eprintln!("Can't set frontlight: {}", e)).ok();
+            self.set(LightColor::Green, green)
+                .map_err(|e| eprintln!("Can't set frontlight: {}", e)).ok();
         } else {
             let orange = 95.0 * (w * i).sqrt();
-            self.set(LightColor::Orange, orange);
+            self.set(LightColor::Orange, orange)
+                .map_err(|e| eprintln!("Can't set frontlight: {}", e)).ok();
         }
 
         self.intensity = intensity;
@@ -119,6 +128,14 @@ impl NaturalFrontlight {
     }
 }
 
+fn frontlight_error(err: io::Error, node: &str) -> Error {
+    match err.kind() {
+        io::ErrorKind::NotFound => Error::with_chain(err, ErrorKind::FrontlightNotFound(node.to_string())),
+        io::ErrorKind::PermissionDenied => Error::with_chain(err, ErrorKind::FrontlightPermissionDenied(node.to_string())),
+        _ => err.into(),
+    }
+}
+
 impl Frontlight for NaturalFrontlight {
     fn set_intensity(&mut self, value: f32) {
         let warmth = self.warmth;
@@ -137,3 +154,20 @@ impl Frontlight for NaturalFrontlight {
         }
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use std::io;
+    use errors::ErrorKind;
+    use super::frontlight_error;
+
+    #[test]
+    fn frontlight_error_kinds() {
+        let err = frontlight_error(io::Error::from(io::ErrorKind::NotFound), "lm3630a_ledb");
+        assert!(matches!(*err.kind(), ErrorKind::FrontlightNotFound(ref n) if n == "lm3630a_ledb"));
+        let err = frontlight_error(io::Error::from(io::ErrorKind::PermissionDenied), "lm3630a_ledb");
+        assert!(matches!(*err.kind(), ErrorKind::FrontlightPermissionDenied(..)));
+        let err = frontlight_error(io::Error::from(io::ErrorKind::Other), "lm3630a_ledb");
+        assert!(matches!(*err.kind(), ErrorKind::Io(..)));
+    }
+}
@@ -0,0 +1,235 @@
+use chrono::{NaiveDate, NaiveTime, Duration, Timelike};
+use device::CURRENT_DEVICE;
+use frontlight::{Frontlight, LightLevels};
+use frontlight::solar::sun_times;
+use settings::{NightHours, Location};
+
+const SECONDS_PER_DAY: u32 = 24 * 60 * 60;
+
+// The time of a schedule point: either a fixed time of day, or an offset,
+// in minutes, from the sunrise or the sunset.
+#[derive(Debug, Copy, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(untagged)]
+pub enum ScheduleTime {
+    Fixed(NaiveTime),
+    Solar {
+        event: SolarEvent,
+        #[serde(default)]
+        offset: i64,
+    },
+}
+
+#[derive(Debug, Copy, Clone, Eq, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub enum SolarEvent {
+    Dawn,
+    Dusk,
+}
+
+impl SolarEvent {
+    // Used when the location is unknown, or when the sun doesn't rise or set.
+    fn fallback(&self) -> NaiveTime {
+        match *self {
+            SolarEvent::Dawn => NaiveTime::from_hms(7, 0, 0),
+            SolarEvent::Dusk => NaiveTime::from_hms(19, 0, 0),
+        }
+    }
+}
+
+// Turns the schedule points into times of day for the given date.
+// *utc_offset* is the offset of the local time zone, in seconds.
+pub fn resolve_schedule(schedule: &[(ScheduleTime, LightLevels)], date: NaiveDate,
+                        location: Option<&Location>, utc_offset: i32) -> Vec<(NaiveTime, LightLevels)> {
+    let sun = location.and_then(|l| sun_times(date, l.latitude, l.longitude, utc_offset));
+    schedule.iter().map(|&(time, levels)| {
+        let time = match time {
+            ScheduleTime::Fixed(time) => time,
+            ScheduleTime::Solar { event, offset } => {
+                let base = match (event, sun) {
+                    (SolarEvent::Dawn, Some((sunrise, _))) => sunrise,
+                    (SolarEvent::Dusk, Some((_, sunset))) => sunset,
+                    _ => event.fallback(),
+                };
+                base.overflowing_add_signed(Duration::minutes(offset)).0
+            },
+        };
+        (time, levels)
+    }).collect()
+}
+
+// The warmth rises smoothly from zero at *start* to *max_warmth* at *end*,
+// and stays there until the end of the night hours.
+#[derive(Debug, Copy, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct WarmthRamp {
+    pub start: NaiveTime,
+    pub end: NaiveTime,
+    pub max_warmth: f32,
+}
+
+impl WarmthRamp {
+    // The warmth is only driven between the start of the ramp and the morning.
+    pub fn warmth_at<T: Timelike>(&self, time: &T, night_hours: &NightHours) -> Option<f32> {
+        let start = self.start.num_seconds_from_midnight();
+        let since_start = |s: u32| (s + SECONDS_PER_DAY - start) % SECONDS_PER_DAY;
+        let elapsed = since_start(time.num_seconds_from_midnight());
+        let span = since_start(self.end.num_seconds_from_midnight());
+        let morning = since_start(60 * night_hours.end as u32);
+
+        if elapsed < span {
+            let t = elapsed as f32 / span as f32;
+            Some(self.max_warmth * t * t * (3.0 - 2.0 * t))
+        } else if elapsed < morning.max(span) {
+            Some(self.max_warmth)
+        } else {
+            None
+        }
+    }
+}
+
+// The levels scheduled at the given time: the levels of the two surrounding
+// schedule points are interpolated, wrapping around midnight.
+pub fn scheduled_levels<T: Timelike>(schedule: &[(NaiveTime, LightLevels)], time: &T) -> Option<LightLevels> {
+    if schedule.is_empty() {
+        return None;
+    }
+
+    let mut points: Vec<(u32, LightLevels)> = schedule.iter()
+                                                       .map(|&(t, levels)| (t.num_seconds_from_midnight(), levels))
+                                                       .collect();
+    points.sort_by_key(|&(s, _)| s);
+
+    let now = time.num_seconds_from_midnight();
+    let next_index = points.iter().position(|&(s, _)| s > now).unwrap_or(0);
+    let prev_index = (next_index + points.len() - 1) % points.len();
+    let (prev, prev_levels) = points[prev_index];
+    let (next, next_levels) = points[next_index];
+
+    let span = (next + SECONDS_PER_DAY - prev) % SECONDS_PER_DAY;
+    if span == 0 {
+        return Some(prev_levels);
+    }
+
+    let elapsed = (now + SECONDS_PER_DAY - prev) % SECONDS_PER_DAY;
+    Some(prev_levels.interpolate(&next_levels, elapsed as f32 / span as f32))
+}
+
+// Applies the levels scheduled at the given time. Returns whether the schedule
+// isn't empty.
+pub fn apply_schedule<T: Timelike>(frontlight: &mut Frontlight, schedule: &[(NaiveTime, LightLevels)], time: &T) -> bool {
+    if let Some(levels) = scheduled_levels(schedule, time) {
+        frontlight.set_intensity(levels.intensity);
+        frontlight.set_warmth(levels.warmth);
+        true
+    } else {
+        false
+    }
+}
+
+// Applies the warmth of the ramp at the given time. Returns whether the warmth was set.
+pub fn apply_warmth_ramp<T: Timelike>(frontlight: &mut Frontlight, ramp: Option<&WarmthRamp>, night_hours: &NightHours, time: &T) -> bool {
+    if !CURRENT_DEVICE.has_natural_light() {
+        return false;
+    }
+    if let Some(warmth) = ramp.and_then(|r| r.warmth_at(time, night_hours)) {
+        frontlight.set_warmth(warmth);
+        true
+    } else {
+        false
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use serde_json;
+    use chrono::{NaiveDate, NaiveTime};
+    use frontlight::LightLevels;
+    use settings::{NightHours, Location};
+    use super::{scheduled_levels, apply_schedule, resolve_schedule, WarmthRamp, ScheduleTime, SolarEvent};
+
+    fn schedule() -> Vec<(NaiveTime, LightLevels)> {
+        vec![(NaiveTime::from_hms(22, 0, 0), LightLevels { intensity: 10.0, warmth: 100.0 }),
+             (NaiveTime::from_hms(8, 0, 0), LightLevels { intensity: 40.0, warmth: 0.0 }),
+             (NaiveTime::from_hms(18, 0, 0), LightLevels { intensity: 20.0, warmth: 50.0 })]
+    }
+
+    #[test]
+    fn interpolate_schedule() {
+        let schedule = schedule();
+        assert_eq!(scheduled_levels(&schedule, &NaiveTime::from_hms(8, 0, 0)),
+                   Some(LightLevels { intensity: 40.0, warmth: 0.0 }));
+        assert_eq!(scheduled_levels(&schedule, &NaiveTime::from_hms(13, 0, 0)),
+                   Some(LightLevels { intensity: 30.0, warmth: 25.0 }));
+        assert_eq!(scheduled_levels(&schedule, &NaiveTime::from_hms(20, 0, 0)),
+                   Some(LightLevels { intensity: 15.0, warmth: 75.0 }));
+    }
+
+    #[test]
+    fn interpolate_schedule_across_midnight() {
+        let schedule = schedule();
+        assert_eq!(scheduled_levels(&schedule, &NaiveTime::from_hms(3, 0, 0)),
+                   Some(LightLevels { intensity: 25.0, warmth: 50.0 }));
+        assert_eq!(scheduled_levels(&schedule, &NaiveTime::from_hms(0, 30, 0)),
+                   Some(LightLevels { intensity: 17.5, warmth: 75.0 }));
+        let single = vec![schedule[0]];
+        assert_eq!(scheduled_levels(&single, &NaiveTime::from_hms(12, 0, 0)), Some(schedule[0].1));
+    }
+
+    #[test]
+    fn empty_schedule() {
+        let mut levels = LightLevels { intensity: 5.0, warmth: 5.0 };
+        assert_eq!(scheduled_levels(&[], &NaiveTime::from_hms(12, 0, 0)), None);
+        assert!(!apply_schedule(&mut levels, &[], &NaiveTime::from_hms(12, 0, 0)));
+        assert_eq!(levels, LightLevels { intensity: 5.0, warmth: 5.0 });
+        assert!(apply_schedule(&mut levels, &schedule(), &NaiveTime::from_hms(8, 0, 0)));
+        assert_eq!(levels, LightLevels { intensity: 40.0, warmth: 0.0 });
+    }
+
+    #[test]
+    fn evening_warmth_ramp() {
+        let night_hours = NightHours::default();
+        let ramp = WarmthRamp {
+            start: NaiveTime::from_hms(19, 0, 0),
+            end: NaiveTime::from_hms(23, 0, 0),
+            max_warmth: 80.0,
+        };
+        assert_eq!(ramp.warmth_at(&NaiveTime::from_hms(12, 0, 0), &night_hours), None);
+        assert_eq!(ramp.warmth_at(&NaiveTime::from_hms(19, 0, 0), &night_hours), Some(0.0));
+        assert_eq!(ramp.warmth_at(&NaiveTime::from_hms(21, 0, 0), &night_hours), Some(40.0));
+        assert!(ramp.warmth_at(&NaiveTime::from_hms(20, 0, 0), &night_hours).unwrap() < 20.0);
+        assert!(ramp.warmth_at(&NaiveTime::from_hms(22, 0, 0), &night_hours).unwrap() > 60.0);
+        assert_eq!(ramp.warmth_at(&NaiveTime::from_hms(23, 30, 0), &night_hours), Some(80.0));
+        assert_eq!(ramp.warmth_at(&NaiveTime::from_hms(3, 0, 0), &night_hours), Some(80.0));
+        assert_eq!(ramp.warmth_at(&NaiveTime::from_hms(7, 0, 0), &night_hours), None);
+    }
+
+    #[test]
+    fn resolve_solar_schedule() {
+        let levels = LightLevels::default();
+        let schedule = vec![(ScheduleTime::Fixed(NaiveTime::from_hms(12, 0, 0)), levels),
+                            (ScheduleTime::Solar { event: SolarEvent::Dawn, offset: 30 }, levels),
+                            (ScheduleTime::Solar { event: SolarEvent::Dusk, offset: -90 }, levels)];
+        let times = |location: Option<&Location>, date: NaiveDate| {
+            resolve_schedule(&schedule, date, location, 2 * 3600).into_iter()
+                                                                  .map(|(t, _)| t.format("%H:%M").to_string())
+                                                                  .collect::<Vec<String>>()
+        };
+        let paris = Location { latitude: 48.8566, longitude: 2.3522 };
+        let tromso = Location { latitude: 69.6492, longitude: 18.9553 };
+        let solstice = NaiveDate::from_ymd(2018, 6, 21);
+        assert_eq!(times(None, solstice), vec!["12:00", "07:30", "17:30"]);
+        assert_eq!(times(Some(&tromso), solstice), vec!["12:00", "07:30", "17:30"]);
+        assert_eq!(times(Some(&paris), solstice), vec!["12:00", "06:18", "20:28"]);
+    }
+
+    #[test]
+    fn solar_schedule_format() {
+        let text = r#"[["21:30:00", {"intensity": 10.0, "warmth": 90.0}],
+                       [{"event": "dusk", "offset": -60}, {"intensity": 30.0, "warmth": 40.0}],
+                       [{"event": "dawn"}, {"intensity": 60.0, "warmth": 0.0}]]"#;
+        let schedule: Vec<(ScheduleTime, LightLevels)> = serde_json::from_str(text).unwrap();
+        assert_eq!(schedule[0].0, ScheduleTime::Fixed(NaiveTime::from_hms(21, 30, 0)));
+        assert_eq!(schedule[1].0, ScheduleTime::Solar { event: SolarEvent::Dusk, offset: -60 });
+        assert_eq!(schedule[2].0, ScheduleTime::Solar { event: SolarEvent::Dawn, offset: 0 });
+    }
+}
@@ -0,0 +1,88 @@
+use std::f64::consts::PI;
+use chrono::{NaiveDate, NaiveTime};
+
+const SECONDS_PER_DAY: i64 = 24 * 60 * 60;
+const J2000: f64 = 2_451_545.0;
+const EARTH_OBLIQUITY: f64 = 23.4397;
+// Accounts for the refraction and the apparent radius of the sun.
+const SUN_ALTITUDE: f64 = -0.833;
+
+#[inline]
+fn sin_deg(x: f64) -> f64 {
+    (x * PI / 180.0).sin()
+}
+
+#[inline]
+fn cos_deg(x: f64) -> f64 {
+    (x * PI / 180.0).cos()
+}
+
+// Returns the local times of the sunrise and the sunset on the given date, at the given
+// latitude and longitude (in degrees, positive towards the north and the east).
+// *utc_offset* is the offset of the local time zone, in seconds.
+// There's no sunrise nor sunset during the polar days and nights.
+pub fn sun_times(date: NaiveDate, latitude: f64, longitude: f64, utc_offset: i32) -> Option<(NaiveTime, NaiveTime)> {
+    let days = date.signed_duration_since(NaiveDate::from_ymd(2000, 1, 1)).num_days() as f64;
+    let mean_solar_noon = days + 0.0008 - longitude / 360.0;
+    let mean_anomaly = (357.5291 + 0.985_600_28 * mean_solar_noon) % 360.0;
+    let center = 1.9148 * sin_deg(mean_anomaly) +
+                 0.02 * sin_deg(2.0 * mean_anomaly) +
+                 0.0003 * sin_deg(3.0 * mean_anomaly);
+    let ecliptic_longitude = (mean_anomaly + center + 180.0 + 102.9372) % 360.0;
+    let transit = J2000 + mean_solar_noon +
+                  0.0053 * sin_deg(mean_anomaly) -
+                  0.0069 * sin_deg(2.0 * ecliptic_longitude);
+    let sin_declination = sin_deg(ecliptic_longitude) * sin_deg(EARTH_OBLIQUITY);
+    let cos_declination = (1.0 - sin_declination * sin_declination).sqrt();
+    let cos_hour_angle = (sin_deg(SUN_ALTITUDE) - sin_deg(latitude) * sin_declination) /
+                         (cos_deg(latitude) * cos_declination);
+
+    if cos_hour_angle.abs() > 1.0 {
+        return None;
+    }
+
+    let hour_angle = cos_hour_angle.acos() * 180.0 / PI;
+    // The julian date of the midnight that starts the given day, in UTC.
+    let midnight = J2000 - 0.5 + days;
+    let local_time = |julian_date: f64| {
+        let seconds = ((julian_date - midnight) * SECONDS_PER_DAY as f64).round() as i64 + utc_offset as i64;
+        let seconds = ((seconds % SECONDS_PER_DAY) + SECONDS_PER_DAY) % SECONDS_PER_DAY;
+        NaiveTime::from_num_seconds_from_midnight(seconds as u32, 0)
+    };
+
+    Some((local_time(transit - hour_angle / 360.0),
+          local_time(transit + hour_angle / 360.0)))
+}
+
+#[cfg(test)]
+mod tests {
+    use chrono::{NaiveDate, NaiveTime, Timelike};
+    use super::sun_times;
+
+    fn minutes(time: NaiveTime) -> i32 {
+        (time.hour() * 60 + time.minute()) as i32
+    }
+
+    #[test]
+    fn paris_summer_solstice() {
+        let date = NaiveDate::from_ymd(2018, 6, 21);
+        let (sunrise, sunset) = sun_times(date, 48.8566, 2.3522, 2 * 3600).unwrap();
+        assert!((minutes(sunrise) - (5 * 60 + 47)).abs() <= 3);
+        assert!((minutes(sunset) - (21 * 60 + 58)).abs() <= 3);
+    }
+
+    #[test]
+    fn sydney_winter_solstice() {
+        let date = NaiveDate::from_ymd(2018, 6, 21);
+        let (sunrise, sunset) = sun_times(date, -33.8688, 151.2093, 10 * 3600).unwrap();
+        assert!((minutes(sunrise) - (7 * 60)).abs() <= 3);
+        assert!((minutes(sunset) - (16 * 60 + 54)).abs() <= 3);
+    }
+
+    #[test]
+    fn polar_day_and_night() {
+        let (latitude, longitude) = (69.6492, 18.9553);
+        assert_eq!(sun_times(NaiveDate::from_ymd(2018, 6, 21), latitude, longitude, 2 * 3600), None);
+        assert_eq!(sun_times(NaiveDate::from_ymd(2018, 12, 21), latitude, longitude, 3600), None);
+    }
+}
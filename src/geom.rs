@@ -79,6 +79,76 @@ impl ColorSource for u8 {
 pub struct BorderSpec {
     pub thickness: u16,
     pub color: u8,
+    pub style: BorderStyle,
+}
+
+// The lengths of the dashes and of the gaps between them are in pixels.
+// The dots are as long as the border is thick.
+#[derive(Debug, Copy, Clone, Eq, PartialEq)]
+pub enum BorderStyle {
+    Solid,
+    Dashed { on: u16, off: u16 },
+    Dotted,
+}
+
+impl BorderStyle {
+    // Whether the border is drawn at the given distance along it.
+    pub fn inks(&self, position: f32, thickness: u16) -> bool {
+        match *self {
+            BorderStyle::Solid => true,
+            BorderStyle::Dashed { on, off } => {
+                position.rem_euclid((on + off).max(1) as f32) < on as f32
+            },
+            BorderStyle::Dotted => {
+                let length = thickness.max(1) as f32;
+                position.rem_euclid(2.0 * length) < length
+            },
+        }
+    }
+}
+
+// The distance from the start of the top side of *rect*, going clockwise along
+// the middle of its border, to the point of the border closest to *pt*.
+// The corners have the given radii: north west, north east, south east and south west.
+pub fn border_position(rect: &Rectangle, radii: (i32, i32, i32, i32), thickness: u16, pt: Vec2) -> f32 {
+    let (nw, ne, se, sw) = radii;
+    let half_thickness = thickness as f32 / 2.0;
+    let arc = |radius: i32| (radius as f32 - half_thickness).max(0.0) * consts::FRAC_PI_2;
+    let nw_c = Vec2::new((rect.min.x + nw) as f32, (rect.min.y + nw) as f32);
+    let ne_c = Vec2::new((rect.max.x - ne) as f32, (rect.min.y + ne) as f32);
+    let se_c = Vec2::new((rect.max.x - se) as f32, (rect.max.y - se) as f32);
+    let sw_c = Vec2::new((rect.min.x + sw) as f32, (rect.max.y - sw) as f32);
+    let top = ne_c.x - nw_c.x;
+    let right = top + arc(ne) + se_c.y - ne_c.y;
+    let bottom = right + arc(se) + se_c.x - sw_c.x;
+    let left = bottom + arc(sw) + sw_c.y - nw_c.y;
+    // The fraction of a quarter turn.
+    let turn = |y: f32, x: f32| y.atan2(x).max(0.0).min(consts::FRAC_PI_2) / consts::FRAC_PI_2;
+
+    if pt.x >= ne_c.x && pt.y < ne_c.y {
+        let v = pt - ne_c;
+        top + turn(v.x, -v.y) * arc(ne)
+    } else if pt.x >= se_c.x && pt.y >= se_c.y {
+        let v = pt - se_c;
+        right + turn(v.y, v.x) * arc(se)
+    } else if pt.x < sw_c.x && pt.y >= sw_c.y {
+        let v = pt - sw_c;
+        bottom + turn(-v.x, v.y) * arc(sw)
+    } else if pt.x < nw_c.x && pt.y < nw_c.y {
+        let v = pt - nw_c;
+        left + turn(-v.y, -v.x) * arc(nw)
+    } else {
+        let distances = [pt.y - rect.min.y as f32, rect.max.x as f32 - pt.x,
+                         rect.max.y as f32 - pt.y, pt.x - rect.min.x as f32];
+        let side = (0..4).min_by(|&a, &b| distances[a].partial_cmp(&distances[b])
+                                                      .unwrap_or(Ordering::Equal)).unwrap_or(0);
+        match side {
+            0 => pt.x - nw_c.x,
+            1 => top + arc(ne) + pt.y - ne_c.y,
+            2 => right + arc(se) + se_c.x - pt.x,
+            _ => bottom + arc(sw) + sw_c.y - pt.y,
+        }
+    }
 }
 
 const HALF_PIXEL_DIAGONAL: f32 = consts::SQRT_2 / 2.0;
@@ -108,6 +178,33 @@ pub fn surface_area(dist: f32, angle: f32) -> f32 {
     }
 }
 
+// The number of samples, per side of a pixel, used to compute the coverage of an arc.
+const ARC_SAMPLES: i32 = 8;
+
+// Returns the fraction of the pixel centered at v, relative to the center of a disk
+// of the given radius, that lies within that disk.
+pub fn disk_coverage(v: Vec2, radius: f32) -> f32 {
+    if radius <= 0.0 {
+        return 0.0;
+    }
+    let dist = v.length() - radius;
+    if dist.abs() > HALF_PIXEL_DIAGONAL {
+        return if dist.is_sign_positive() { 0.0 } else { 1.0 };
+    }
+    let step = 1.0 / ARC_SAMPLES as f32;
+    let origin = v - 0.5 + 0.5 * step;
+    let mut count = 0;
+    for j in 0..ARC_SAMPLES {
+        for i in 0..ARC_SAMPLES {
+            let s = origin + Vec2::new(i as f32 * step, j as f32 * step);
+            if s.dot(s) <= radius * radius {
+                count += 1;
+            }
+        }
+    }
+    count as f32 / (ARC_SAMPLES * ARC_SAMPLES) as f32
+}
+
 // Returns the nearest point to p on segment ab
 pub fn nearest_segment_point(p: Vec2, a: Vec2, b: Vec2) -> Vec2 {
     let ab = b - a;
@@ -124,6 +221,14 @@ pub fn nearest_segment_point(p: Vec2, a: Vec2, b: Vec2) -> Vec2 {
     a + t * ab
 }
 
+// Returns the approximate (signed) distance and angle from p, relative to the center
+// of an ellipse of the given radii, to its boundary. It's exact for circles.
+pub fn ellipse_distance(p: Vec2, radii: Vec2) -> (f32, f32) {
+    let k = (p / radii).length();
+    let normal = p / (radii * radii);
+    (p.length() * (1.0 - 1.0 / k), normal.angle())
+}
+
 #[inline]
 pub fn halves(n: i32) -> (i32, i32) {
     let small_half = n / 2;
@@ -342,6 +447,14 @@ impl Rectangle {
         self.min.y < rect.max.y && rect.min.y < self.max.y
     }
 
+    // Overlaps or shares part of an edge.
+    pub fn touches(&self, rect: &Rectangle) -> bool {
+        (self.min.x <= rect.max.x && rect.min.x <= self.max.x &&
+         self.min.y < rect.max.y && rect.min.y < self.max.y) ||
+        (self.min.x < rect.max.x && rect.min.x < self.max.x &&
+         self.min.y <= rect.max.y && rect.min.y <= self.max.y)
+    }
+
     pub fn merge(&mut self, pt: &Point) {
         if pt.x < self.min.x {
             self.min.x = pt.x;
@@ -829,7 +942,7 @@ impl DivAssign<f32> for Vec2 {
 
 #[cfg(test)]
 mod tests {
-    use super::divide;
+    use super::{divide, border_position, BorderStyle, Vec2};
 
     #[test]
     fn overlaping_rectangles() {
@@ -867,4 +980,24 @@ mod tests {
         assert_eq!(v.iter().max(), Some(&4));
         assert_eq!(v.iter().min(), Some(&3));
     }
+
+    #[test]
+    fn walk_along_borders() {
+        let rect = rect![0, 0, 10, 10];
+        let square = (0, 0, 0, 0);
+        assert_eq!(border_position(&rect, square, 2, Vec2::new(5.5, 0.5)), 5.5);
+        assert_eq!(border_position(&rect, square, 2, Vec2::new(9.5, 5.5)), 15.5);
+        assert_eq!(border_position(&rect, square, 2, Vec2::new(4.5, 9.5)), 25.5);
+        assert_eq!(border_position(&rect, square, 2, Vec2::new(0.5, 4.5)), 35.5);
+        // Halfway through the north east corner.
+        let position = border_position(&rect, (4, 4, 4, 4), 2, Vec2::new(8.0, 2.0));
+        assert!((position - 4.356).abs() < 0.01);
+        let dashed = BorderStyle::Dashed { on: 3, off: 2 };
+        assert!(dashed.inks(2.0, 2));
+        assert!(!dashed.inks(position, 2));
+        assert!(dashed.inks(-4.5, 2));
+        assert!(BorderStyle::Dotted.inks(position, 2));
+        assert!(!BorderStyle::Dotted.inks(2.5, 2));
+        assert!(BorderStyle::Solid.inks(position, 2));
+    }
 }
@@ -12,6 +12,7 @@ use geom::{Point, Dir, Axis};
 pub const JITTER_TOLERANCE_MM: f32 = 6.0;
 pub const FINGER_HOLD_DELAY: Duration = Duration::from_millis(666);
 pub const BUTTON_HOLD_DELAY: Duration = Duration::from_millis(1500);
+pub const DOUBLE_TAP_DELAY: Duration = Duration::from_millis(300);
 
 #[derive(Debug, Copy, Clone)]
 pub enum GestureEvent {
@@ -44,6 +45,8 @@ pub enum GestureEvent {
         quarter_turns: i8,
         center: Point,
     },
+    // Follows the second tap.
+    DoubleTap(Point),
     HoldFinger(Point),
     HoldButton(ButtonCode),
 }
@@ -65,6 +68,7 @@ pub fn parse_gesture_events(rx: &Receiver<DeviceEvent>, ty: &Sender<Event>) {
     let contacts: Arc<Mutex<FnvHashMap<i32, TouchState>>> = Arc::new(Mutex::new(FnvHashMap::default()));
     let buttons: Arc<Mutex<FnvHashMap<ButtonCode, f64>>> = Arc::new(Mutex::new(FnvHashMap::default()));
     let mut segments: Vec<(Point, Point)> = Vec::new();
+    let mut last_tap: Option<(Point, f64)> = None;
     let jitter = CURRENT_DEVICE.dpi as f32 * mm_to_in(JITTER_TOLERANCE_MM);
     while let Ok(evt) = rx.recv() {
         ty.send(Event::Device(evt)).unwrap();
@@ -95,7 +99,7 @@ pub fn parse_gesture_events(rx: &Receiver<DeviceEvent>, ty: &Sender<Event>) {
                     ts.current = position;
                 }
             },
-            DeviceEvent::Finger { status: FingerStatus::Up, position, id, .. } => {
+            DeviceEvent::Finger { status: FingerStatus::Up, position, id, time } => {
                 let mut ct = contacts.lock().unwrap();
                 if let Some(TouchState { initial, .. }) = ct.remove(&id) {
                     segments.push((initial, position));
@@ -105,6 +109,16 @@ pub fn parse_gesture_events(rx: &Receiver<DeviceEvent>, ty: &Sender<Event>) {
                     if len == 1 {
                         let ge = interpret_segment(segments.pop().unwrap(), jitter);
                         ty.send(Event::Gesture(ge)).unwrap();
+                        if let GestureEvent::Tap(pt) = ge {
+                            if is_double_tap(last_tap, pt, time, jitter) {
+                                ty.send(Event::Gesture(GestureEvent::DoubleTap(pt))).unwrap();
+                                last_tap = None;
+                            } else {
+                                last_tap = Some((pt, time));
+                            }
+                        } else {
+                            last_tap = None;
+                        }
                     } else if len == 2 {
                         let ge1 = interpret_segment(segments.pop().unwrap(), jitter);
                         let ge2 = interpret_segment(segments.pop().unwrap(), jitter);
@@ -181,6 +195,14 @@ pub fn parse_gesture_events(rx: &Receiver<DeviceEvent>, ty: &Sender<Event>) {
     }
 }
 
+// The second tap must come quickly, and close to the first one.
+fn is_double_tap(last_tap: Option<(Point, f64)>, pt: Point, time: f64, jitter: f32) -> bool {
+    let delay = DOUBLE_TAP_DELAY.as_secs() as f64 + DOUBLE_TAP_DELAY.subsec_nanos() as f64 * 1e-9;
+    last_tap.map_or(false, |(last_pt, last_time)| {
+        time - last_time <= delay && (pt - last_pt).length() < jitter
+    })
+}
+
 fn interpret_segment((a, b): (Point, Point), jitter: f32) -> GestureEvent {
     let ab = b - a;
     if ab.length() < jitter {
@@ -193,3 +215,17 @@ fn interpret_segment((a, b): (Point, Point), jitter: f32) -> GestureEvent {
         }
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::is_double_tap;
+
+    #[test]
+    fn double_tap_timing() {
+        let pt = pt!(100, 100);
+        assert!(is_double_tap(Some((pt, 10.0)), pt!(102, 99), 10.2, 20.0));
+        assert!(!is_double_tap(Some((pt, 10.0)), pt, 10.5, 20.0));
+        assert!(!is_double_tap(Some((pt, 10.0)), pt!(150, 100), 10.1, 20.0));
+        assert!(!is_double_tap(None, pt, 10.1, 20.0));
+    }
+}
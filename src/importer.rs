@@ -35,14 +35,7 @@ mod frontlight;
 mod lightsensor;
 mod symbolic_path;
 
-mod errors {
-    error_chain!{
-        foreign_links {
-            Io(::std::io::Error);
-            ParseInt(::std::num::ParseIntError);
-        }
-    }
-}
+mod errors;
 
 use std::env;
 use std::fs;
@@ -11,6 +11,7 @@ use std::ffi::CString;
 use fnv::{FnvHashMap, FnvHashSet};
 use device::CURRENT_DEVICE;
 use geom::Point;
+use framebuffer::Rotation;
 use errors::*;
 
 // Event types
@@ -32,6 +33,9 @@ pub const SYN_REPORT: u16 = 0;
 
 pub const KEY_POWER: u16 = 116;
 pub const KEY_HOME: u16 = 102;
+// The page turn buttons.
+pub const KEY_BACKWARD: u16 = 193;
+pub const KEY_FORWARD: u16 = 194;
 pub const SLEEP_COVER: u16 = 59;
 
 pub const SINGLE_TOUCH_CODES: TouchCodes = TouchCodes {
@@ -224,13 +228,14 @@ fn parse_usb_events(tx: &Sender<DeviceEvent>) {
     }
 }
 
-pub fn device_events(rx: Receiver<InputEvent>, dims: (u32, u32)) -> Receiver<DeviceEvent> {
+// The positions are mapped from the panel, of dimensions *dims*, to the drawing space of the given rotation.
+pub fn device_events(rx: Receiver<InputEvent>, dims: (u32, u32), rotation: Rotation) -> Receiver<DeviceEvent> {
     let (ty, ry) = mpsc::channel();
-    thread::spawn(move || parse_device_events(&rx, &ty, dims));
+    thread::spawn(move || parse_device_events(&rx, &ty, dims, rotation));
     ry
 }
 
-pub fn parse_device_events(rx: &Receiver<InputEvent>, ty: &Sender<DeviceEvent>, dims: (u32, u32)) {
+pub fn parse_device_events(rx: &Receiver<InputEvent>, ty: &Sender<DeviceEvent>, dims: (u32, u32), rotation: Rotation) {
     let mut id = 0;
     let mut position = Point::default();
     let mut pressure = 0;
@@ -265,6 +270,7 @@ pub fn parse_device_events(rx: &Receiver<InputEvent>, ty: &Sender<DeviceEvent>,
                 pressure = evt.value;
             }
         } else if evt.kind == EV_SYN {
+            let position = rotation.inverse().transform(position, rotation.dims(dims));
             if evt.code == SYN_MT_REPORT || (proto == TouchProto::Single && evt.code == SYN_REPORT) {
                 if let Some(&p) = fingers.get(&id) {
                     if pressure > 0 {
@@ -0,0 +1,72 @@
+use std::time::{Duration, Instant};
+use lightsensor::LightSensor;
+use errors::*;
+
+// Returns the last reading of the wrapped sensor while it's younger than *ttl*.
+pub struct CachedLightSensor<S: LightSensor> {
+    inner: S,
+    ttl: Duration,
+    last: Option<(Instant, u16)>,
+}
+
+impl<S: LightSensor> CachedLightSensor<S> {
+    pub fn new(inner: S, ttl: Duration) -> CachedLightSensor<S> {
+        CachedLightSensor {
+            inner,
+            ttl,
+            last: None,
+        }
+    }
+}
+
+impl<S: LightSensor> LightSensor for CachedLightSensor<S> {
+    fn level(&mut self) -> Result<u16> {
+        if let Some((instant, level)) = self.last {
+            if instant.elapsed() < self.ttl {
+                return Ok(level);
+            }
+        }
+        let level = self.inner.level()?;
+        self.last = Some((Instant::now(), level));
+        Ok(level)
+    }
+
+    fn raw_level(&mut self) -> Result<u16> {
+        self.inner.raw_level()
+    }
+
+    fn reset(&mut self) {
+        self.last = None;
+        self.inner.reset();
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::time::Duration;
+    use lightsensor::LightSensor;
+    use errors::*;
+    use super::CachedLightSensor;
+
+    // Each reading is one more than the previous one.
+    struct CountingSensor(u16);
+
+    impl LightSensor for CountingSensor {
+        fn level(&mut self) -> Result<u16> {
+            self.0 += 1;
+            Ok(self.0)
+        }
+    }
+
+    #[test]
+    fn cached_readings() {
+        let mut lightsensor = CachedLightSensor::new(CountingSensor(0), Duration::from_secs(3600));
+        assert_eq!(lightsensor.level().ok(), Some(1));
+        assert_eq!(lightsensor.level().ok(), Some(1));
+        lightsensor.reset();
+        assert_eq!(lightsensor.level().ok(), Some(2));
+        let mut lightsensor = CachedLightSensor::new(CountingSensor(0), Duration::from_secs(0));
+        assert_eq!(lightsensor.level().ok(), Some(1));
+        assert_eq!(lightsensor.level().ok(), Some(2));
+    }
+}
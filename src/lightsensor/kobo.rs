@@ -1,27 +1,93 @@
 use std::io::{Read, Seek, SeekFrom};
 use std::fs::File;
-use lightsensor::LightSensor;
+use std::path::Path;
+use lightsensor::{LightSensor, sensor_error};
 use errors::*;
 
 // The Aura ONE uses a Silicon Graphics light sensor,
 // the model code is si114x (where x is 5, 6, or 7).
-const VISIBLE_PHOTODIODE: &str = "/sys/devices/virtual/input/input3/als_vis_data";
+// The number of its input node varies across firmware versions and units.
+const INPUT_DEVICES: &str = "/sys/devices/virtual/input";
+const VISIBLE_PHOTODIODE: &str = "als_vis_data";
+const MAX_INPUT_NODES: usize = 16;
 
-pub struct KoboLightSensor(File);
+// The readings are smoothed by an exponential moving average: *alpha* is the
+// weight of the latest reading, hence 1.0 disables the smoothing.
+pub struct KoboLightSensor {
+    file: File,
+    path: String,
+    alpha: f32,
+    average: Option<f32>,
+}
 
 impl KoboLightSensor {
-    pub fn new() -> Result<Self> {
-        let file = File::open(VISIBLE_PHOTODIODE)?;
-        Ok(KoboLightSensor(file))
+    pub fn new(alpha: f32) -> Result<Self> {
+        // The first node that yields a valid reading is kept.
+        let (file, path) = (0..MAX_INPUT_NODES).filter_map(|n| {
+            let path = Path::new(INPUT_DEVICES).join(format!("input{}", n)).join(VISIBLE_PHOTODIODE);
+            let mut file = File::open(&path).ok()?;
+            let mut buf = String::new();
+            file.read_to_string(&mut buf).ok()?;
+            parse_level(&buf).ok().map(|_| (file, path.display().to_string()))
+        }).next().ok_or_else(|| {
+            let pattern = Path::new(INPUT_DEVICES).join("input*").join(VISIBLE_PHOTODIODE);
+            Error::from_kind(ErrorKind::SensorUndetected(pattern.display().to_string()))
+        })?;
+        Ok(KoboLightSensor { file, path, alpha, average: None })
     }
 }
 
 impl LightSensor for KoboLightSensor {
     fn level(&mut self) -> Result<u16> {
+        let level = self.raw_level()?;
+        let average = smoothed(self.average, level, self.alpha);
+        self.average = Some(average);
+        Ok(average.round() as u16)
+    }
+
+    fn raw_level(&mut self) -> Result<u16> {
         let mut buf = String::new();
-        self.0.seek(SeekFrom::Start(0))?;
-        self.0.read_to_string(&mut buf)?;
-        let value = buf.trim_right().parse()?;
-        Ok(value)
+        self.file.seek(SeekFrom::Start(0)).map_err(|e| sensor_error(e, &self.path))?;
+        self.file.read_to_string(&mut buf).map_err(|e| sensor_error(e, &self.path))?;
+        parse_level(&buf)
+    }
+
+    fn reset(&mut self) {
+        self.average = None;
+    }
+}
+
+// The first reading seeds the average.
+fn smoothed(average: Option<f32>, level: u16, alpha: f32) -> f32 {
+    let alpha = alpha.max(0.0).min(1.0);
+    match average {
+        Some(average) => average + alpha * (level as f32 - average),
+        None => level as f32,
+    }
+}
+
+fn parse_level(buf: &str) -> Result<u16> {
+    let value = buf.trim_right();
+    value.parse().map_err(|e| Error::with_chain(e, ErrorKind::SensorParse(value.to_string())))
+}
+
+#[cfg(test)]
+mod tests {
+    use errors::ErrorKind;
+    use super::{parse_level, smoothed};
+
+    #[test]
+    fn parse_sensor_level() {
+        assert_eq!(parse_level("42\n").ok(), Some(42));
+        let err = parse_level("n/a\n").unwrap_err();
+        assert!(matches!(*err.kind(), ErrorKind::SensorParse(ref v) if v == "n/a"));
+    }
+
+    #[test]
+    fn smooth_sensor_levels() {
+        assert_eq!(smoothed(None, 40, 0.25), 40.0);
+        assert_eq!(smoothed(Some(40.0), 80, 0.25), 50.0);
+        assert_eq!(smoothed(Some(40.0), 80, 1.0), 80.0);
+        assert_eq!(smoothed(Some(40.0), 80, 0.0), 40.0);
     }
 }
@@ -0,0 +1,81 @@
+use std::collections::VecDeque;
+use lightsensor::LightSensor;
+use errors::*;
+
+// Returns the median of the last readings of the wrapped sensor, which rejects
+// the brief spikes. The window is primed with the first reading, and its size is
+// made odd so that the median is one of the readings.
+pub struct MedianLightSensor<S: LightSensor> {
+    inner: S,
+    size: usize,
+    window: VecDeque<u16>,
+}
+
+impl<S: LightSensor> MedianLightSensor<S> {
+    pub fn new(inner: S, size: usize) -> MedianLightSensor<S> {
+        let size = size.max(1) | 1;
+        MedianLightSensor {
+            inner,
+            size,
+            window: VecDeque::with_capacity(size),
+        }
+    }
+}
+
+impl<S: LightSensor> LightSensor for MedianLightSensor<S> {
+    fn level(&mut self) -> Result<u16> {
+        let level = self.inner.level()?;
+        if self.window.is_empty() {
+            self.window.extend((0..self.size).map(|_| level));
+        } else {
+            self.window.pop_front();
+            self.window.push_back(level);
+        }
+        let mut levels: Vec<u16> = self.window.iter().cloned().collect();
+        levels.sort();
+        Ok(levels[levels.len() / 2])
+    }
+
+    fn raw_level(&mut self) -> Result<u16> {
+        self.inner.raw_level()
+    }
+
+    fn reset(&mut self) {
+        self.window.clear();
+        self.inner.reset();
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use lightsensor::LightSensor;
+    use errors::*;
+    use super::MedianLightSensor;
+
+    // Yields the given readings, in order.
+    struct ScriptedSensor(Vec<u16>);
+
+    impl LightSensor for ScriptedSensor {
+        fn level(&mut self) -> Result<u16> {
+            Ok(self.0.remove(0))
+        }
+    }
+
+    fn medians(readings: &[u16], size: usize) -> Vec<u16> {
+        let mut lightsensor = MedianLightSensor::new(ScriptedSensor(readings.to_vec()), size);
+        readings.iter().map(|_| lightsensor.level().unwrap()).collect()
+    }
+
+    #[test]
+    fn reject_spikes() {
+        assert_eq!(medians(&[40, 2, 40, 40, 300, 39], 3), vec![40, 40, 40, 40, 40, 40]);
+        assert_eq!(medians(&[40, 0, 0, 41, 42, 43], 5), vec![40, 40, 40, 40, 40, 41]);
+    }
+
+    #[test]
+    fn follow_lasting_changes() {
+        assert_eq!(medians(&[10, 80, 80, 80], 3), vec![10, 10, 80, 80]);
+        assert_eq!(medians(&[10, 80, 20], 1), vec![10, 80, 20]);
+        assert_eq!(medians(&[10, 80, 80, 20], 2), vec![10, 10, 80, 80]);
+    }
+}
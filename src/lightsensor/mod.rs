@@ -1,11 +1,32 @@
 mod kobo;
+mod cached;
+mod median;
 
+use std::io;
+use std::time::Duration;
+use device::CURRENT_DEVICE;
+use settings::Settings;
 use errors::*;
 
 pub use self::kobo::KoboLightSensor;
+pub use self::cached::CachedLightSensor;
+pub use self::median::MedianLightSensor;
+
+// Coefficients of the visible photodiode of the si114x, from its datasheet:
+// the reading in the dark, and the illuminance of each count above it.
+const SI114X_DARK_COUNTS: u16 = 256;
+const SI114X_LUX_PER_COUNT: f32 = 0.282;
 
 pub trait LightSensor {
     fn level(&mut self) -> Result<u16>;
+
+    // The unprocessed reading, for debugging purposes.
+    fn raw_level(&mut self) -> Result<u16> {
+        self.level()
+    }
+
+    // Forgets the past readings.
+    fn reset(&mut self) {}
 }
 
 impl LightSensor for u16 {
@@ -13,3 +34,122 @@ impl LightSensor for u16 {
         Ok(*self)
     }
 }
+
+// Stands for a missing light sensor: every reading fails.
+pub struct NullLightSensor;
+
+impl LightSensor for NullLightSensor {
+    fn level(&mut self) -> Result<u16> {
+        Err(ErrorKind::SensorNotFound("none".to_string()).into())
+    }
+}
+
+// Falls back to the null sensor when the device has no light sensor,
+// or when it can't be opened. The median filter comes after the moving average.
+pub fn light_sensor(settings: &Settings) -> Box<LightSensor> {
+    if !CURRENT_DEVICE.has_lightsensor() {
+        return Box::new(NullLightSensor) as Box<LightSensor>;
+    }
+    match KoboLightSensor::new(settings.lightsensor_smoothing) {
+        Ok(lightsensor) => {
+            let lightsensor = MedianLightSensor::new(lightsensor, settings.lightsensor_median_window);
+            let ttl = Duration::from_millis(settings.lightsensor_cache_duration);
+            Box::new(CachedLightSensor::new(lightsensor, ttl)) as Box<LightSensor>
+        },
+        Err(e) => {
+            eprintln!("Can't create light sensor: {}", e);
+            Box::new(NullLightSensor) as Box<LightSensor>
+        },
+    }
+}
+
+// Converts a raw reading of the current device's light sensor to lux.
+// The readings of unknown sensors are returned as is.
+pub fn to_lux(raw: u16) -> f32 {
+    lux_from_counts(raw, CURRENT_DEVICE.lightsensor_transmittance())
+}
+
+fn lux_from_counts(raw: u16, transmittance: Option<f32>) -> f32 {
+    match transmittance {
+        Some(transmittance) => raw.saturating_sub(SI114X_DARK_COUNTS) as f32 * SI114X_LUX_PER_COUNT / transmittance,
+        None => raw as f32,
+    }
+}
+
+// Maps a raw reading through the calibration points, which are pairs of raw and
+// calibrated levels, interpolating linearly between them. The readings outside
+// of the points are clamped, and an empty calibration leaves the readings as is.
+pub fn calibrated_level(level: u16, calibration: &[(u16, u16)]) -> u16 {
+    let mut points = calibration.to_vec();
+    points.sort_by_key(|&(raw, _)| raw);
+
+    let (first, last) = match (points.first(), points.last()) {
+        (Some(&first), Some(&last)) => (first, last),
+        _ => return level,
+    };
+
+    if level <= first.0 {
+        return first.1;
+    }
+    if level >= last.0 {
+        return last.1;
+    }
+
+    let index = points.iter().position(|&(raw, _)| raw > level).unwrap();
+    let (x0, y0) = points[index - 1];
+    let (x1, y1) = points[index];
+    let t = (level - x0) as f32 / (x1 - x0) as f32;
+    (y0 as f32 + t * (y1 as f32 - y0 as f32)).round() as u16
+}
+
+pub fn sensor_error(err: io::Error, path: &str) -> Error {
+    match err.kind() {
+        io::ErrorKind::NotFound => Error::with_chain(err, ErrorKind::SensorNotFound(path.to_string())),
+        io::ErrorKind::PermissionDenied => Error::with_chain(err, ErrorKind::SensorPermissionDenied(path.to_string())),
+        _ => err.into(),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::io;
+    use errors::ErrorKind;
+    use super::{sensor_error, calibrated_level, lux_from_counts};
+
+    #[test]
+    fn sensor_error_kinds() {
+        let err = sensor_error(io::Error::from(io::ErrorKind::NotFound), "als");
+        assert!(matches!(*err.kind(), ErrorKind::SensorNotFound(ref p) if p == "als"));
+        let err = sensor_error(io::Error::from(io::ErrorKind::PermissionDenied), "als");
+        assert!(matches!(*err.kind(), ErrorKind::SensorPermissionDenied(..)));
+        let err = sensor_error(io::Error::from(io::ErrorKind::Interrupted), "als");
+        assert!(matches!(*err.kind(), ErrorKind::Io(..)));
+    }
+
+    #[test]
+    fn interpolate_calibration() {
+        let calibration = vec![(100, 40), (0, 0), (300, 100)];
+        assert_eq!(calibrated_level(50, &calibration), 20);
+        assert_eq!(calibrated_level(100, &calibration), 40);
+        assert_eq!(calibrated_level(200, &calibration), 70);
+        assert_eq!(calibrated_level(123, &[]), 123);
+    }
+
+    #[test]
+    fn clamp_calibration() {
+        let calibration = vec![(10, 5), (90, 80)];
+        assert_eq!(calibrated_level(0, &calibration), 5);
+        assert_eq!(calibrated_level(500, &calibration), 80);
+        assert_eq!(calibrated_level(7, &[(20, 3)]), 3);
+        assert_eq!(calibrated_level(70, &[(20, 3)]), 3);
+    }
+
+    #[test]
+    fn counts_to_lux() {
+        assert_eq!(lux_from_counts(256, Some(1.0)), 0.0);
+        assert_eq!(lux_from_counts(100, Some(1.0)), 0.0);
+        assert!((lux_from_counts(1256, Some(1.0)) - 282.0).abs() < 1e-3);
+        assert!((lux_from_counts(1256, Some(0.5)) - 564.0).abs() < 1e-3);
+        assert_eq!(lux_from_counts(100, None), 100.0);
+    }
+}
@@ -23,17 +23,7 @@ extern crate png;
 extern crate isbn;
 extern crate titlecase;
 
-mod errors {
-    error_chain!{
-        foreign_links {
-            Io(::std::io::Error);
-            ParseInt(::std::num::ParseIntError);
-        }
-        links {
-            Font(::font::Error, ::font::ErrorKind);
-        }
-    }
-}
+mod errors;
 
 #[macro_use]
 mod geom;
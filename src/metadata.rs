@@ -9,6 +9,7 @@ use chrono::{Local, DateTime};
 use helpers::simple_date_format;
 use regex::Regex;
 use document::file_kind;
+use frontlight::LightLevels;
 use symbolic_path;
 use errors::*;
 
@@ -154,6 +155,8 @@ pub struct ReaderInfo {
     pub first_page: Option<usize>,
     #[serde(skip_serializing_if = "BTreeSet::is_empty")]
     pub bookmarks: BTreeSet<usize>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub frontlight_levels: Option<LightLevels>,
     pub finished: bool,
 }
 
@@ -173,6 +176,7 @@ impl Default for ReaderInfo {
             first_page: None,
             cropping_margins: None,
             bookmarks: BTreeSet::new(),
+            frontlight_levels: None,
             finished: false,
         }
     }
@@ -1,12 +1,23 @@
 mod preset;
+mod snapshot;
 
 use std::path::PathBuf;
 use fnv::FnvHashSet;
-use frontlight::LightLevels;
+use serde::{Deserialize, Deserializer};
+use frontlight::{LightLevels, WarmthRamp, ScheduleTime};
+use framebuffer::Dithering;
 
-pub use self::preset::{LightPreset, guess_frontlight};
+pub use self::preset::{LightPreset, NightHours, CycleOrder, guess_frontlight, cycle_preset_index};
+pub use self::preset::steady_lightsensor_level;
+pub use self::preset::{insert_preset, merge_presets, move_preset, can_guess};
+pub use self::preset::{PRESETS_DIRNAME, PRESETS_FILENAME, is_preset_file_name};
+pub use self::snapshot::Change;
 
 pub const SETTINGS_PATH: &str = "settings.json";
+// Beyond these values, the glyph edges either vanish or turn solid.
+pub const MIN_TEXT_GAMMA: f32 = 0.1;
+pub const MAX_TEXT_GAMMA: f32 = 10.0;
+pub const DEFAULT_GLYPH_CACHE_CAPACITY: usize = 512;
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
 #[serde(default, rename_all = "camelCase")]
@@ -19,10 +30,132 @@ pub struct Settings {
     pub frontlight_levels: LightLevels,
     #[serde(skip_serializing_if = "Vec::is_empty")]
     pub frontlight_presets: Vec<LightPreset>,
+    pub frontlight_presets_cycle: CycleOrder,
+    pub frontlight_presets_wrap: bool,
+    // Whether the presets are kept sorted by time of day.
+    // This is turned off as soon as the presets are reordered by hand.
+    pub frontlight_presets_sorted: bool,
+    pub confirm_preset_removal: bool,
+    pub frontlight_min_intensity: f32,
+    // The warmth slider only selects multiples of this step, when it's given.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub frontlight_warmth_step: Option<f32>,
+    // The gamma applied to the position of the intensity slider.
+    pub frontlight_gamma: f32,
+    // The levels applied automatically at given times of day.
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    pub frontlight_schedule: Vec<(ScheduleTime, LightLevels)>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub frontlight_warmth_ramp: Option<WarmthRamp>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub frontlight_cooled_warmth: Option<f32>,
     pub frontlight: bool,
+    pub frontlight_anchored: bool,
+    pub frontlight_keys: FrontlightKeys,
+    pub frontlight_fade: FrontlightFade,
+    pub frontlight_edge_drag: FrontlightEdgeDrag,
+    // The weight of the latest reading in the running average of the light sensor levels.
+    pub lightsensor_smoothing: f32,
+    // The number of readings whose median is taken, 1 disables the median filter.
+    pub lightsensor_median_window: usize,
+    // How long a light sensor reading is reused, in milliseconds.
+    pub lightsensor_cache_duration: u64,
+    // The change in the light sensor level needed to guess other frontlight levels.
+    pub lightsensor_hysteresis: u16,
+    // The interval between the light sensor readings broadcast to the views,
+    // in milliseconds. A null interval disables the readings.
+    pub lightsensor_poll_interval: u64,
+    // Pairs of raw and calibrated light sensor levels, interpolated linearly.
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    pub lightsensor_calibration: Vec<(u16, u16)>,
+    pub night_hours: NightHours,
+    // Used to compute the times of the sunrise and the sunset.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub location: Option<Location>,
+    pub reduce_motion: bool,
+    // The gamma applied to the edges of the glyphs, values above 1 darken the text.
+    #[serde(deserialize_with = "deserialize_text_gamma")]
+    pub text_gamma: f32,
+    // The number of rendered glyphs kept by each font.
+    pub glyph_cache_capacity: usize,
+    // The fonts consulted, in order, for the characters missing from the bundled fonts.
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    pub fallback_fonts: Vec<PathBuf>,
+    // Replaces the sans serif fonts of the interface.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub user_fonts: Option<UserFonts>,
+    // The language whose patterns, read from *hyphenation/<language>.pat*,
+    // hyphenate the wrapped labels, e.g. *en-us*.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub hyphenation_language: Option<String>,
     pub wifi: bool,
 }
 
+// Raw codes of the hardware keys that step the frontlight intensity
+// while the frontlight window is open.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(default, rename_all = "camelCase")]
+pub struct FrontlightKeys {
+    pub increase: Vec<u16>,
+    pub decrease: Vec<u16>,
+    pub step: f32,
+}
+
+// Transition used when a preset is loaded.
+// The duration is in milliseconds, a null duration disables the transition.
+// The wake transition follows sleeps lasting at least *wake_threshold* seconds.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(default, rename_all = "camelCase")]
+pub struct FrontlightFade {
+    pub duration: u64,
+    pub frames: u32,
+    pub wake_duration: u64,
+    pub wake_threshold: u64,
+}
+
+// Sliding a finger vertically along an edge of the screen, in the reader,
+// changes the frontlight intensity. The width of the edge strip and the travel
+// needed before the intensity starts to change are in millimeters.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(default, rename_all = "camelCase")]
+pub struct FrontlightEdgeDrag {
+    pub enabled: bool,
+    pub side: EdgeSide,
+    pub width: f32,
+    pub min_length: f32,
+}
+
+// A directory of font files and the names of the files used for each style.
+// The styles left unset are given the files named after them, e.g. *Inter-BoldItalic.otf*.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct UserFonts {
+    pub path: PathBuf,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub regular: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub italic: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub bold: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub bold_italic: Option<String>,
+}
+
+// Latitude and longitude, in degrees, positive towards the north and the east.
+#[derive(Debug, Copy, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct Location {
+    pub latitude: f64,
+    pub longitude: f64,
+}
+
+#[derive(Debug, Copy, Clone, Eq, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub enum EdgeSide {
+    Left,
+    Right,
+}
+
 #[derive(Debug, Clone, Serialize, Deserialize)]
 #[serde(default, rename_all = "camelCase")]
 pub struct ImportSettings {
@@ -34,6 +167,11 @@ pub struct ImportSettings {
 #[serde(default, rename_all = "camelCase")]
 pub struct ReaderSettings {
     pub finished: FinishedAction,
+    // Whether a title too wide for the top bar scrolls.
+    pub scroll_title: bool,
+    // Smooths the gray levels of the pages, at the cost of slower renderings.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub dithering: Option<Dithering>,
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
@@ -47,6 +185,40 @@ impl Default for ReaderSettings {
     fn default() -> Self {
         ReaderSettings {
             finished: FinishedAction::Notify,
+            scroll_title: false,
+            dithering: None,
+        }
+    }
+}
+
+impl Default for FrontlightKeys {
+    fn default() -> Self {
+        FrontlightKeys {
+            increase: vec![115],
+            decrease: vec![114],
+            step: 5.0,
+        }
+    }
+}
+
+impl Default for FrontlightFade {
+    fn default() -> Self {
+        FrontlightFade {
+            duration: 300,
+            frames: 10,
+            wake_duration: 1500,
+            wake_threshold: 60,
+        }
+    }
+}
+
+impl Default for FrontlightEdgeDrag {
+    fn default() -> Self {
+        FrontlightEdgeDrag {
+            enabled: false,
+            side: EdgeSide::Left,
+            width: 6.0,
+            min_length: 5.0,
         }
     }
 }
@@ -61,6 +233,11 @@ impl Default for ImportSettings {
     }
 }
 
+fn deserialize_text_gamma<'de, D>(deserializer: D) -> Result<f32, D::Error> where D: Deserializer<'de> {
+    let gamma = f32::deserialize(deserializer)?;
+    Ok(gamma.max(MIN_TEXT_GAMMA).min(MAX_TEXT_GAMMA))
+}
+
 impl Default for Settings {
     fn default() -> Self {
         Settings {
@@ -71,7 +248,35 @@ impl Default for Settings {
             reader: ReaderSettings::default(),
             frontlight_levels: LightLevels::default(),
             frontlight_presets: Vec::new(),
+            frontlight_presets_cycle: CycleOrder::default(),
+            frontlight_presets_wrap: false,
+            frontlight_presets_sorted: true,
+            confirm_preset_removal: true,
+            frontlight_min_intensity: 0.0,
+            frontlight_warmth_step: None,
+            frontlight_gamma: 1.0,
+            frontlight_schedule: Vec::new(),
+            frontlight_warmth_ramp: None,
+            frontlight_cooled_warmth: None,
             frontlight: true,
+            frontlight_anchored: false,
+            frontlight_keys: FrontlightKeys::default(),
+            frontlight_fade: FrontlightFade::default(),
+            frontlight_edge_drag: FrontlightEdgeDrag::default(),
+            lightsensor_smoothing: 0.3,
+            lightsensor_median_window: 1,
+            lightsensor_cache_duration: 500,
+            lightsensor_hysteresis: 3,
+            lightsensor_poll_interval: 0,
+            lightsensor_calibration: Vec::new(),
+            night_hours: NightHours::default(),
+            location: None,
+            reduce_motion: false,
+            text_gamma: 1.0,
+            glyph_cache_capacity: DEFAULT_GLYPH_CACHE_CAPACITY,
+            fallback_fonts: Vec::new(),
+            user_fonts: None,
+            hyphenation_language: None,
             wifi: false,
         }
     }
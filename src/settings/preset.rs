@@ -5,38 +5,286 @@ use geom::circular_distances;
 
 const MINUTES_PER_DAY: u16 = 24 * 60;
 
-#[derive(Debug, Clone, Serialize, Deserialize)]
+// The directory where single presets are exported to and imported from.
+pub const PRESETS_DIRNAME: &str = "presets";
+// The file, within the presets directory, that holds a whole list of presets.
+pub const PRESETS_FILENAME: &str = "presets.json";
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
 #[serde(default, rename_all = "camelCase")]
 pub struct LightPreset {
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub name: Option<String>,
     pub timestamp: u16,
     pub frontlight_levels: LightLevels,
     #[serde(skip_serializing_if = "Option::is_none")]
     pub lightsensor_level: Option<u16>,
+    // Only apply the warmth during the night hours.
+    pub warmth_at_night: bool,
+}
+
+// The time window, in minutes since midnight, during which it's night.
+// The window wraps around midnight when *start* is greater than *end*.
+#[derive(Debug, Copy, Clone, Eq, PartialEq, Serialize, Deserialize)]
+#[serde(default, rename_all = "camelCase")]
+pub struct NightHours {
+    pub start: u16,
+    pub end: u16,
+}
+
+impl Default for NightHours {
+    fn default() -> Self {
+        NightHours {
+            start: 20 * 60,
+            end: 7 * 60,
+        }
+    }
+}
+
+impl NightHours {
+    pub fn contains<T: Timelike>(&self, time: &T) -> bool {
+        let minutes = (60 * time.hour() + time.minute()) as u16;
+        if self.start <= self.end {
+            minutes >= self.start && minutes < self.end
+        } else {
+            minutes >= self.start || minutes < self.end
+        }
+    }
 }
 
 impl Default for LightPreset {
     fn default() -> Self {
-        let now = Local::now();
+        LightPreset::at(&Local::now())
+    }
+}
+
+#[derive(Debug, Copy, Clone, Eq, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub enum CycleOrder {
+    Forward,
+    Reverse,
+    SensorLevel,
+}
+
+impl Default for CycleOrder {
+    fn default() -> Self {
+        CycleOrder::Forward
+    }
+}
+
+impl LightPreset {
+    pub fn at<T: Timelike>(time: &T) -> LightPreset {
         LightPreset {
-            timestamp: (60 * now.hour() + now.minute()) as u16,
+            name: None,
+            timestamp: (60 * time.hour() + time.minute()) as u16,
             frontlight_levels: LightLevels::default(),
             lightsensor_level: None,
+            warmth_at_night: false,
         }
     }
-}
 
-impl LightPreset {
+    // The levels to apply at the given time. The warmth is left at *current_warmth*
+    // outside of the night hours if the preset only applies it at night.
+    pub fn levels_at<T: Timelike>(&self, time: &T, night_hours: &NightHours, current_warmth: f32) -> LightLevels {
+        if self.warmth_at_night && !night_hours.contains(time) {
+            LightLevels {
+                warmth: current_warmth,
+                .. self.frontlight_levels
+            }
+        } else {
+            self.frontlight_levels
+        }
+    }
+
+    pub fn is_duplicate_of(&self, other: &LightPreset) -> bool {
+        self.frontlight_levels == other.frontlight_levels &&
+        self.lightsensor_level == other.lightsensor_level
+    }
+
+    // The sensor level only makes sense on the device that measured it,
+    // hence it's only shared on demand.
+    pub fn shared(&self, with_lightsensor_level: bool) -> LightPreset {
+        LightPreset {
+            lightsensor_level: if with_lightsensor_level { self.lightsensor_level } else { None },
+            .. self.clone()
+        }
+    }
+
+    // Brings the values of an imported preset back into their valid ranges.
+    pub fn sanitized(&self) -> LightPreset {
+        let LightLevels { intensity, warmth } = self.frontlight_levels;
+        LightPreset {
+            name: self.name.clone(),
+            timestamp: self.timestamp.min(MINUTES_PER_DAY - 1),
+            frontlight_levels: LightLevels {
+                intensity: if intensity.is_nan() { 0.0 } else { intensity.max(0.0).min(100.0) },
+                warmth: if warmth.is_nan() { 0.0 } else { warmth.max(0.0).min(100.0) },
+            },
+            lightsensor_level: self.lightsensor_level,
+            warmth_at_night: self.warmth_at_night,
+        }
+    }
+
+    pub fn file_name(&self) -> String {
+        let hours = self.timestamp / 60;
+        let minutes = self.timestamp - hours * 60;
+        format!("preset-{:02}{:02}.json", hours, minutes)
+    }
+
+    // The user given name, or the time of day at which the preset was saved.
     pub fn name(&self) -> String {
+        if let Some(ref name) = self.name {
+            return name.clone();
+        }
+        self.time()
+    }
+
+    // The time of day at which the preset was saved, followed by the
+    // light sensor level recorded at that time, if any.
+    pub fn details(&self) -> String {
+        if let Some(level) = self.lightsensor_level {
+            format!("Saved at {} · Sensor {}", self.time(), level)
+        } else {
+            format!("Saved at {}", self.time())
+        }
+    }
+
+    fn time(&self) -> String {
         let hours = self.timestamp / 60;
         let minutes = self.timestamp - hours * 60;
         format!("{:02}:{:02}", hours, minutes)
     }
 }
 
+pub fn insert_preset(light_presets: &mut Vec<LightPreset>, light_preset: LightPreset) {
+    light_presets.push(light_preset);
+    light_presets.sort_by(|a, b| a.timestamp.cmp(&b.timestamp));
+}
+
+// Moves the preset at *from* to the position *to*, clamped to the bounds of the list.
+pub fn move_preset(light_presets: &mut Vec<LightPreset>, from: usize, to: usize) {
+    if from >= light_presets.len() {
+        return;
+    }
+    let light_preset = light_presets.remove(from);
+    let to = to.min(light_presets.len());
+    light_presets.insert(to, light_preset);
+}
+
+// Whether the file, within the presets directory, was exported by the frontlight window.
+pub fn is_preset_file_name(name: &str) -> bool {
+    if name == PRESETS_FILENAME {
+        return true;
+    }
+    if !name.starts_with("preset-") || !name.ends_with(".json") {
+        return false;
+    }
+    let digits = &name["preset-".len()..name.len() - ".json".len()];
+    !digits.is_empty() && digits.chars().all(|c| c.is_ascii_digit())
+}
+
+// Adds the imported presets that don't duplicate an existing one, or share its timestamp.
+// The existing presets keep their names, unless they don't have any.
+// Returns the number of duplicates that were merged into the existing presets.
+pub fn merge_presets(light_presets: &mut Vec<LightPreset>, imported: Vec<LightPreset>) -> usize {
+    let mut merged = 0;
+
+    for light_preset in imported {
+        if let Some(lp) = light_presets.iter_mut().find(|lp| lp.is_duplicate_of(&light_preset) ||
+                                                             lp.timestamp == light_preset.timestamp) {
+            let unnamed = lp.name.as_ref().map_or(true, |name| name.trim().is_empty());
+            if unnamed && light_preset.name.as_ref().map_or(false, |name| !name.trim().is_empty()) {
+                lp.name = light_preset.name;
+            }
+            merged += 1;
+        } else {
+            insert_preset(light_presets, light_preset);
+        }
+    }
+
+    merged
+}
+
+#[inline]
+pub fn can_guess(light_presets: &[LightPreset]) -> bool {
+    light_presets.len() >= 2
+}
+
+// Returns the index of the preset that follows *current* in the given order.
+// The cycle wraps around at both ends.
+pub fn cycle_preset_index(current: Option<usize>, light_presets: &[LightPreset], order: CycleOrder) -> Option<usize> {
+    let len = light_presets.len();
+
+    if len == 0 {
+        return None;
+    }
+
+    let current = current.filter(|&i| i < len);
+
+    match order {
+        CycleOrder::Forward => Some(current.map_or(0, |i| (i + 1) % len)),
+        CycleOrder::Reverse => Some(current.map_or(len - 1, |i| (i + len - 1) % len)),
+        CycleOrder::SensorLevel => {
+            let mut indices: Vec<usize> = (0..len).collect();
+            indices.sort_by_key(|&i| light_presets[i].lightsensor_level.unwrap_or_default());
+            let position = current.and_then(|c| indices.iter().position(|&i| i == c));
+            Some(position.map_or(indices[0], |p| indices[(p + 1) % len]))
+        },
+    }
+}
+
+// Readings within *hysteresis* of the last applied one are replaced by it, so that
+// an ambient light hovering around a preset doesn't make the frontlight pulse.
+pub fn steady_lightsensor_level(level: u16, last: Option<u16>, hysteresis: u16) -> u16 {
+    match last {
+        Some(last) if (level as i32 - last as i32).abs() <= hysteresis as i32 => last,
+        _ => level,
+    }
+}
+
+// Interpolates the levels of the two presets whose light sensor levels bracket the
+// current one. Readings outside of the presets' range get the levels of the nearest
+// preset. Presets without light sensor levels are bracketed by time of day instead.
 pub fn guess_frontlight(lightsensor_level: Option<u16>, light_presets: &[LightPreset]) -> Option<LightLevels> {
-    if light_presets.len() < 2 {
+    if light_presets.is_empty() {
         return None;
     }
+
+    if light_presets.len() == 1 {
+        return Some(light_presets[0].frontlight_levels);
+    }
+
+    if light_presets[0].lightsensor_level.is_some() {
+        let s = lightsensor_level.unwrap_or_default();
+        let mut below: Option<(u16, usize)> = None;
+        let mut above: Option<(u16, usize)> = None;
+
+        for (i, lp) in light_presets.iter().enumerate() {
+            if let Some(p) = lp.lightsensor_level {
+                if p <= s && below.map_or(true, |(b, _)| p > b) {
+                    below = Some((p, i));
+                }
+                if p >= s && above.map_or(true, |(a, _)| p < a) {
+                    above = Some((p, i));
+                }
+            }
+        }
+
+        return match (below, above) {
+            (Some((b, i)), Some((a, j))) => {
+                let fl0 = light_presets[i].frontlight_levels;
+                if a == b {
+                    Some(fl0)
+                } else {
+                    let fl1 = light_presets[j].frontlight_levels;
+                    Some(fl0.interpolate(&fl1, (s - b) as f32 / (a - b) as f32))
+                }
+            },
+            (Some((_, i)), None) | (None, Some((_, i))) => Some(light_presets[i].frontlight_levels),
+            (None, None) => None,
+        };
+    }
+
     let cur = LightPreset {
         lightsensor_level,
         .. Default::default()
@@ -45,36 +293,17 @@ pub fn guess_frontlight(lightsensor_level: Option<u16>, light_presets: &[LightPr
     let mut dmin = [u16::max_value(); 2];
     let mut index = [usize::max_value(); 2];
 
-    if light_presets[0].lightsensor_level.is_some() {
-        let s = cur.lightsensor_level.unwrap_or_default();
-
-        for (i, lp) in light_presets.iter().enumerate() {
-            let p = lp.lightsensor_level.unwrap_or_default();
-            let d = if s >= p { s - p } else { p - s };
+    for (i, lp) in light_presets.iter().enumerate() {
+        let (d0, d1) = circular_distances(cur.timestamp, lp.timestamp, MINUTES_PER_DAY);
 
-            if p >= s && d < dmin[0] {
-                dmin[0] = d;
-                index[0] = i;
-            }
-
-            if p <= s && d < dmin[1] {
-                dmin[1] = d;
-                index[1] = i;
-            }
+        if d0 < dmin[0] {
+            dmin[0] = d0;
+            index[0] = i;
         }
-    } else {
-        for (i, lp) in light_presets.iter().enumerate() {
-            let (d0, d1) = circular_distances(cur.timestamp, lp.timestamp, MINUTES_PER_DAY);
 
-            if d0 < dmin[0] {
-                dmin[0] = d0;
-                index[0] = i;
-            }
-
-            if d1 < dmin[1] {
-                dmin[1] = d1;
-                index[1] = i;
-            }
+        if d1 < dmin[1] {
+            dmin[1] = d1;
+            index[1] = i;
         }
     }
 
@@ -92,3 +321,236 @@ pub fn guess_frontlight(lightsensor_level: Option<u16>, light_presets: &[LightPr
 
     Some(fl0.interpolate(&fl1, t))
 }
+
+#[cfg(test)]
+mod tests {
+    use serde_json;
+    use chrono::NaiveTime;
+    use frontlight::LightLevels;
+    use super::{LightPreset, NightHours, CycleOrder, cycle_preset_index, insert_preset, merge_presets, can_guess};
+    use super::is_preset_file_name;
+    use super::{move_preset, guess_frontlight, steady_lightsensor_level};
+
+    fn presets(levels: &[u16]) -> Vec<LightPreset> {
+        levels.iter().enumerate().map(|(i, &l)| {
+            LightPreset {
+                timestamp: i as u16,
+                lightsensor_level: Some(l),
+                .. Default::default()
+            }
+        }).collect()
+    }
+
+    #[test]
+    fn cycle_forward() {
+        let lp = presets(&[30, 10, 20]);
+        assert_eq!(cycle_preset_index(None, &lp, CycleOrder::Forward), Some(0));
+        assert_eq!(cycle_preset_index(Some(0), &lp, CycleOrder::Forward), Some(1));
+        assert_eq!(cycle_preset_index(Some(2), &lp, CycleOrder::Forward), Some(0));
+    }
+
+    #[test]
+    fn cycle_reverse() {
+        let lp = presets(&[30, 10, 20]);
+        assert_eq!(cycle_preset_index(None, &lp, CycleOrder::Reverse), Some(2));
+        assert_eq!(cycle_preset_index(Some(2), &lp, CycleOrder::Reverse), Some(1));
+        assert_eq!(cycle_preset_index(Some(0), &lp, CycleOrder::Reverse), Some(2));
+    }
+
+    #[test]
+    fn cycle_sensor_level() {
+        let lp = presets(&[30, 10, 20]);
+        assert_eq!(cycle_preset_index(None, &lp, CycleOrder::SensorLevel), Some(1));
+        assert_eq!(cycle_preset_index(Some(1), &lp, CycleOrder::SensorLevel), Some(2));
+        assert_eq!(cycle_preset_index(Some(2), &lp, CycleOrder::SensorLevel), Some(0));
+        assert_eq!(cycle_preset_index(Some(0), &lp, CycleOrder::SensorLevel), Some(1));
+    }
+
+    #[test]
+    fn insert_at_controlled_times() {
+        let mut lp = Vec::new();
+        insert_preset(&mut lp, LightPreset::at(&NaiveTime::from_hms(22, 0, 0)));
+        assert!(!can_guess(&lp));
+        insert_preset(&mut lp, LightPreset::at(&NaiveTime::from_hms(8, 15, 0)));
+        assert!(can_guess(&lp));
+        insert_preset(&mut lp, LightPreset::at(&NaiveTime::from_hms(13, 30, 0)));
+        let names: Vec<String> = lp.iter().map(|p| p.name()).collect();
+        assert_eq!(names, vec!["08:15", "13:30", "22:00"]);
+        lp.remove(0);
+        assert!(can_guess(&lp));
+        lp.remove(0);
+        assert!(!can_guess(&lp));
+    }
+
+    #[test]
+    fn merge_duplicates() {
+        let preset = |timestamp, intensity, level| {
+            LightPreset {
+                name: None,
+                timestamp,
+                frontlight_levels: LightLevels { intensity, warmth: 0.0 },
+                lightsensor_level: level,
+                warmth_at_night: false,
+            }
+        };
+        let mut lp = vec![preset(60, 10.0, Some(3)), preset(120, 20.0, Some(5))];
+        let imported = vec![preset(90, 10.0, Some(3)),
+                            preset(30, 20.0, Some(4)),
+                            preset(600, 20.0, Some(5)),
+                            preset(30, 20.0, Some(4))];
+        assert_eq!(merge_presets(&mut lp, imported), 3);
+        assert_eq!(lp.len(), 3);
+        assert_eq!(lp[0].timestamp, 30);
+        assert_eq!(lp[1].timestamp, 60);
+        assert_eq!(merge_presets(&mut lp, Vec::new()), 0);
+        let imported = vec![preset(700, 50.0, None), preset(60, 70.0, None), preset(700, 60.0, None)];
+        assert_eq!(merge_presets(&mut lp, imported), 2);
+        assert_eq!(lp.iter().map(|p| p.timestamp).collect::<Vec<u16>>(), vec![30, 60, 600, 700]);
+        assert_eq!(lp[3].frontlight_levels.intensity, 50.0);
+        lp[0].name = Some("Dusk".to_string());
+        lp[1].name = Some(" ".to_string());
+        let named = |name: &str, light_preset: LightPreset| LightPreset { name: Some(name.to_string()), .. light_preset };
+        let imported = vec![named("Evening", preset(30, 90.0, None)),
+                            named("Night", preset(60, 90.0, None)),
+                            named("Day", preset(800, 20.0, Some(5)))];
+        assert_eq!(merge_presets(&mut lp, imported), 3);
+        assert_eq!(lp.iter().map(|p| p.name()).collect::<Vec<String>>(), vec!["Dusk", "Night", "Day", "11:40"]);
+    }
+
+    #[test]
+    fn cycle_empty() {
+        assert_eq!(cycle_preset_index(Some(0), &[], CycleOrder::Forward), None);
+    }
+
+    #[test]
+    fn shared_preset_round_trip() {
+        let light_preset = LightPreset {
+            name: Some("Reading in bed".to_string()),
+            timestamp: 21 * 60 + 30,
+            frontlight_levels: LightLevels { intensity: 12.5, warmth: 60.0 },
+            lightsensor_level: Some(7),
+            warmth_at_night: true,
+        };
+        let text = serde_json::to_string(&light_preset.shared(false)).unwrap();
+        let imported: LightPreset = serde_json::from_str(&text).unwrap();
+        assert_eq!(imported.lightsensor_level, None);
+        assert_eq!(imported.sanitized(), LightPreset { lightsensor_level: None, .. light_preset.clone() });
+        let text = serde_json::to_string(&light_preset.shared(true)).unwrap();
+        let imported: LightPreset = serde_json::from_str(&text).unwrap();
+        assert_eq!(imported.sanitized(), light_preset);
+        assert_eq!(light_preset.file_name(), "preset-2130.json");
+        assert!(is_preset_file_name(&light_preset.file_name()));
+        assert!(is_preset_file_name("presets.json"));
+        assert!(!is_preset_file_name("preset-backup.json"));
+        assert!(!is_preset_file_name("preset-.json"));
+        assert!(!is_preset_file_name("settings.json"));
+        assert_eq!(imported.name(), "Reading in bed");
+        assert_eq!(LightPreset { name: None, .. imported }.name(), "21:30");
+    }
+
+    #[test]
+    fn preset_details() {
+        let light_preset = LightPreset {
+            lightsensor_level: Some(42),
+            .. LightPreset::at(&NaiveTime::from_hms(7, 5, 0))
+        };
+        assert_eq!(light_preset.details(), "Saved at 07:05 · Sensor 42");
+        let light_preset = LightPreset { lightsensor_level: None, .. light_preset };
+        assert_eq!(light_preset.details(), "Saved at 07:05");
+    }
+
+    #[test]
+    fn imported_preset_is_clamped() {
+        let text = r#"{"timestamp": 5000, "frontlightLevels": {"intensity": 140.0, "warmth": -3.0}}"#;
+        let imported: LightPreset = serde_json::from_str(text).unwrap();
+        let light_preset = imported.sanitized();
+        assert_eq!(light_preset.timestamp, 24 * 60 - 1);
+        assert_eq!(light_preset.frontlight_levels, LightLevels { intensity: 100.0, warmth: 0.0 });
+        assert_eq!(light_preset.lightsensor_level, None);
+    }
+
+    #[test]
+    fn warmth_only_at_night() {
+        let night_hours = NightHours::default();
+        let light_preset = LightPreset {
+            frontlight_levels: LightLevels { intensity: 30.0, warmth: 80.0 },
+            warmth_at_night: true,
+            .. LightPreset::at(&NaiveTime::from_hms(22, 0, 0))
+        };
+        let day = NaiveTime::from_hms(14, 30, 0);
+        let night = NaiveTime::from_hms(23, 15, 0);
+        let dawn = NaiveTime::from_hms(6, 59, 0);
+        assert_eq!(light_preset.levels_at(&day, &night_hours, 10.0),
+                   LightLevels { intensity: 30.0, warmth: 10.0 });
+        assert_eq!(light_preset.levels_at(&night, &night_hours, 10.0),
+                   LightLevels { intensity: 30.0, warmth: 80.0 });
+        assert_eq!(light_preset.levels_at(&dawn, &night_hours, 10.0).warmth, 80.0);
+        let light_preset = LightPreset { warmth_at_night: false, .. light_preset };
+        assert_eq!(light_preset.levels_at(&day, &night_hours, 10.0).warmth, 80.0);
+    }
+
+    #[test]
+    fn move_presets() {
+        let mut lp = presets(&[0, 1, 2, 3]);
+        let levels = |lp: &[LightPreset]| lp.iter().map(|p| p.lightsensor_level.unwrap()).collect::<Vec<u16>>();
+        move_preset(&mut lp, 0, 2);
+        assert_eq!(levels(&lp), vec![1, 2, 0, 3]);
+        move_preset(&mut lp, 3, 0);
+        assert_eq!(levels(&lp), vec![3, 1, 2, 0]);
+        move_preset(&mut lp, 1, 10);
+        assert_eq!(levels(&lp), vec![3, 2, 0, 1]);
+        move_preset(&mut lp, 10, 0);
+        assert_eq!(levels(&lp), vec![3, 2, 0, 1]);
+    }
+
+    // The intensity of each preset is its light sensor level.
+    fn sensor_presets(levels: &[u16]) -> Vec<LightPreset> {
+        presets(levels).into_iter().map(|lp| {
+            LightPreset {
+                frontlight_levels: LightLevels { intensity: lp.lightsensor_level.unwrap() as f32, warmth: 0.0 },
+                .. lp
+            }
+        }).collect()
+    }
+
+    #[test]
+    fn guess_between_presets() {
+        let lp = sensor_presets(&[40, 0, 20]);
+        assert_eq!(guess_frontlight(Some(10), &lp).map(|l| l.intensity), Some(10.0));
+        assert_eq!(guess_frontlight(Some(35), &lp).map(|l| l.intensity), Some(35.0));
+        assert_eq!(guess_frontlight(Some(20), &lp).map(|l| l.intensity), Some(20.0));
+        assert_eq!(guess_frontlight(Some(0), &lp).map(|l| l.intensity), Some(0.0));
+    }
+
+    #[test]
+    fn guess_out_of_range() {
+        let lp = sensor_presets(&[30, 10, 20]);
+        assert_eq!(guess_frontlight(Some(5), &lp).map(|l| l.intensity), Some(10.0));
+        assert_eq!(guess_frontlight(Some(500), &lp).map(|l| l.intensity), Some(30.0));
+    }
+
+    #[test]
+    fn guess_single_preset() {
+        let lp = sensor_presets(&[30]);
+        assert_eq!(guess_frontlight(Some(5), &lp).map(|l| l.intensity), Some(30.0));
+        assert_eq!(guess_frontlight(Some(30), &lp).map(|l| l.intensity), Some(30.0));
+        assert_eq!(guess_frontlight(Some(5), &[]), None);
+    }
+
+    #[test]
+    fn steady_guess() {
+        let lp = sensor_presets(&[10, 30]);
+        let mut last = None;
+        let mut guesses = Vec::new();
+        for &level in &[19, 21, 19, 21, 20] {
+            let level = steady_lightsensor_level(level, last, 3);
+            last = Some(level);
+            guesses.push(guess_frontlight(Some(level), &lp).map(|l| l.intensity));
+        }
+        assert!(guesses.iter().all(|&g| g == Some(19.0)));
+        assert_eq!(steady_lightsensor_level(24, Some(19), 3), 24);
+        assert_eq!(steady_lightsensor_level(16, Some(19), 3), 19);
+        assert_eq!(steady_lightsensor_level(15, Some(19), 3), 15);
+        assert_eq!(steady_lightsensor_level(7, None, 3), 7);
+    }
+}
@@ -0,0 +1,140 @@
+use std::fmt;
+use std::collections::BTreeMap;
+use serde_json::{self, Value};
+use super::{Settings, LightPreset};
+
+const PRESETS_KEY: &str = "frontlightPresets";
+
+// A frozen copy of the settings that can be compared with another one.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Snapshot {
+    fields: BTreeMap<String, Value>,
+    presets: Vec<LightPreset>,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum Change {
+    Field { name: String, before: Value, after: Value },
+    PresetAdded(LightPreset),
+    PresetRemoved(LightPreset),
+    PresetModified { before: LightPreset, after: LightPreset },
+}
+
+impl fmt::Display for Change {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match *self {
+            Change::Field { ref name, ref before, ref after } => write!(f, "{}: {} → {}", name, before, after),
+            Change::PresetAdded(ref lp) => write!(f, "preset {} added", lp.name()),
+            Change::PresetRemoved(ref lp) => write!(f, "preset {} removed", lp.name()),
+            Change::PresetModified { ref before, ref after } => {
+                write!(f, "preset {} modified: {:?} → {:?}", before.name(),
+                       before.frontlight_levels, after.frontlight_levels)
+            },
+        }
+    }
+}
+
+impl Settings {
+    pub fn snapshot(&self) -> Snapshot {
+        let mut fields: BTreeMap<String, Value> = match serde_json::to_value(self) {
+            Ok(Value::Object(map)) => map.into_iter().collect(),
+            _ => BTreeMap::new(),
+        };
+        fields.remove(PRESETS_KEY);
+        Snapshot {
+            fields,
+            presets: self.frontlight_presets.clone(),
+        }
+    }
+}
+
+impl Snapshot {
+    // Lists the changes that turn *self* into *other*.
+    // Presets are matched by time of day.
+    pub fn diff(&self, other: &Snapshot) -> Vec<Change> {
+        let mut changes = Vec::new();
+
+        for name in self.fields.keys().chain(other.fields.keys().filter(|k| !self.fields.contains_key(*k))) {
+            let before = self.fields.get(name).cloned().unwrap_or(Value::Null);
+            let after = other.fields.get(name).cloned().unwrap_or(Value::Null);
+            if before != after {
+                changes.push(Change::Field { name: name.clone(), before, after });
+            }
+        }
+
+        let mut matched = vec![false; other.presets.len()];
+
+        for lp in &self.presets {
+            let position = other.presets.iter().enumerate()
+                                .position(|(i, olp)| !matched[i] && olp.timestamp == lp.timestamp);
+            if let Some(i) = position {
+                matched[i] = true;
+                if other.presets[i] != *lp {
+                    changes.push(Change::PresetModified { before: lp.clone(),
+                                                          after: other.presets[i].clone() });
+                }
+            } else {
+                changes.push(Change::PresetRemoved(lp.clone()));
+            }
+        }
+
+        for (i, olp) in other.presets.iter().enumerate() {
+            if !matched[i] {
+                changes.push(Change::PresetAdded(olp.clone()));
+            }
+        }
+
+        changes
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use serde_json::Value;
+    use settings::{Settings, LightPreset};
+    use frontlight::LightLevels;
+    use super::Change;
+
+    fn preset(timestamp: u16, intensity: f32) -> LightPreset {
+        LightPreset {
+            name: None,
+            timestamp,
+            frontlight_levels: LightLevels { intensity, .. Default::default() },
+            lightsensor_level: None,
+            warmth_at_night: false,
+        }
+    }
+
+    #[test]
+    fn identical_settings() {
+        let settings = Settings::default();
+        assert!(settings.snapshot().diff(&settings.clone().snapshot()).is_empty());
+    }
+
+    #[test]
+    fn preset_added_and_removed() {
+        let mut settings = Settings::default();
+        settings.frontlight_presets = vec![preset(60, 10.0)];
+        let before = settings.snapshot();
+        settings.frontlight_presets = vec![preset(120, 20.0)];
+        let after = settings.snapshot();
+        assert_eq!(before.diff(&after), vec![Change::PresetRemoved(preset(60, 10.0)),
+                                             Change::PresetAdded(preset(120, 20.0))]);
+    }
+
+    #[test]
+    fn preset_and_field_edits() {
+        let mut settings = Settings::default();
+        settings.frontlight_presets = vec![preset(60, 10.0), preset(120, 20.0)];
+        let before = settings.snapshot();
+        settings.frontlight_presets[1].frontlight_levels.intensity = 30.0;
+        settings.wifi = !settings.wifi;
+        let after = settings.snapshot();
+        assert_eq!(before.diff(&after),
+                   vec![Change::Field { name: "wifi".to_string(),
+                                        before: Value::Bool(!settings.wifi),
+                                        after: Value::Bool(settings.wifi) },
+                        Change::PresetModified { before: preset(120, 20.0),
+                                                 after: preset(120, 30.0) }]);
+    }
+}
@@ -1,6 +1,6 @@
 use device::CURRENT_DEVICE;
 use framebuffer::{Framebuffer, UpdateMode};
-use geom::{Rectangle, BorderSpec, CornerSpec};
+use geom::{Rectangle, BorderSpec, BorderStyle, CornerSpec};
 use color::{BLACK, WHITE, BATTERY_FILL};
 use view::{View, Event, Hub, Bus};
 use view::THICKNESS_LARGE;
@@ -32,16 +32,25 @@ impl Battery {
             status,
         }
     }
+
+    // The battery is read on every status refresh, but only redrawn when it changed.
+    fn refresh(&mut self, hub: &Hub, context: &mut Context) {
+        let capacity = context.battery.capacity().unwrap_or(self.capacity);
+        let status = context.battery.status().unwrap_or(self.status);
+        if capacity != self.capacity || status != self.status {
+            self.capacity = capacity;
+            self.status = status;
+            hub.send(Event::Render(self.rect, UpdateMode::Gui)).unwrap();
+        }
+    }
 }
 
 impl View for Battery {
     fn handle_event(&mut self, evt: &Event, hub: &Hub, _bus: &mut Bus, context: &mut Context) -> bool {
         match *evt {
-            Event::BatteryTick => {
-                self.capacity = context.battery.capacity().unwrap_or(self.capacity);
-                self.status = context.battery.status().unwrap_or(self.status);
-                hub.send(Event::Render(self.rect, UpdateMode::Gui)).unwrap();
-                true
+            Event::RefreshStatus => {
+                self.refresh(hub, context);
+                false
             },
             _ => false,
         }
@@ -71,7 +80,8 @@ impl View for Battery {
         fb.draw_rounded_rectangle_with_border(&batt_rect,
                                               &CornerSpec::Uniform(border_radius),
                                               &BorderSpec { thickness: border_thickness as u16,
-                                                            color: BLACK },
+                                                            color: BLACK,
+                                                            style: BorderStyle::Solid },
                                               &WHITE);
 
         let pt = pt + pt!(batt_width - border_thickness as i32, (batt_height - bump_height) / 2);
@@ -80,7 +90,8 @@ impl View for Battery {
         fb.draw_rounded_rectangle_with_border(&bump_rect,
                                               &CornerSpec::Uniform(border_radius),
                                               &BorderSpec { thickness: border_thickness as u16,
-                                                            color: BLACK },
+                                                            color: BLACK,
+                                                            style: BorderStyle::Solid },
                                               &WHITE);
 
         let pt = pt + pt!(0, border_thickness);
@@ -1,8 +1,9 @@
 use device::CURRENT_DEVICE;
-use geom::{Rectangle, CornerSpec, BorderSpec};
-use font::{Fonts, font_from_style, NORMAL_STYLE};
+use geom::{Rectangle, CornerSpec, BorderSpec, BorderStyle};
+use font::{Fonts, font_from_style, NORMAL_STYLE, FONT_SIZES};
 use view::{View, Event, Hub, Bus};
 use view::{THICKNESS_MEDIUM, BORDER_RADIUS_LARGE};
+use view::icon::ICONS_PIXMAPS;
 use framebuffer::{Framebuffer, UpdateMode};
 use input::{DeviceEvent, FingerStatus};
 use gesture::GestureEvent;
@@ -15,10 +16,50 @@ pub struct Button {
     children: Vec<Box<View>>,
     event: Event,
     text: String,
+    // The name of the icon shown before the text.
+    icon: Option<String>,
     active: bool,
+    // Latched buttons flip on tap and stay inverted while toggled.
+    toggled: Option<bool>,
+    fit: LabelFit,
     pub disabled: bool,
 }
 
+// How labels wider than the button are handled.
+#[derive(Debug, Copy, Clone, Eq, PartialEq)]
+pub enum LabelFit {
+    Truncate,
+    Shrink,
+    Wrap,
+}
+
+// The font size at which a label of the given *width*, planned at *size*, fits within
+// *max_width*. The result is never smaller than *min_size*.
+pub fn shrunk_size(size: u32, width: u32, max_width: u32, min_size: u32) -> u32 {
+    if width <= max_width {
+        return size;
+    }
+    let scale = max_width as f32 / width as f32;
+    ((scale * size as f32) as u32).max(min_size)
+}
+
+// The width taken by the icon shown before the text of a button, gap included.
+pub fn button_icon_width(name: &str, padding: i32) -> i32 {
+    ICONS_PIXMAPS.get(name).map_or(0, |pixmap| pixmap.width + padding / 2)
+}
+
+// Returns the byte index of the space at which *text* should be split so that the
+// widest of the two resulting lines is as narrow as possible.
+pub fn wrap_point<F>(text: &str, mut width_of: F) -> Option<usize> where F: FnMut(&str) -> u32 {
+    text.match_indices(' ')
+        .map(|(index, _)| {
+            let widest = width_of(&text[..index]).max(width_of(text[index+1..].trim()));
+            (index, widest)
+        })
+        .min_by_key(|&(_, widest)| widest)
+        .map(|(index, _)| index)
+}
+
 impl Button {
     pub fn new(rect: Rectangle, event: Event, text: String) -> Button {
         Button {
@@ -26,11 +67,36 @@ impl Button {
             children: vec![],
             event,
             text,
+            icon: None,
             active: false,
+            toggled: None,
+            fit: LabelFit::Truncate,
             disabled: false,
         }
     }
 
+    pub fn fit(mut self, fit: LabelFit) -> Button {
+        self.fit = fit;
+        self
+    }
+
+    pub fn icon(mut self, name: &str) -> Button {
+        self.icon = Some(name.to_string());
+        self
+    }
+
+    pub fn toggled(mut self, value: bool) -> Button {
+        self.toggled = Some(value);
+        self
+    }
+
+    pub fn set_toggled(&mut self, value: bool, hub: &Hub) {
+        if self.toggled.is_some() && self.toggled != Some(value) {
+            self.toggled = Some(value);
+            hub.send(Event::Render(self.rect, UpdateMode::Gui)).unwrap();
+        }
+    }
+
     pub fn disabled(mut self, value: bool) -> Button {
         self.disabled = value;
         self
@@ -57,6 +123,9 @@ impl View for Button {
             },
             Event::Gesture(GestureEvent::Tap(ref center)) if self.rect.includes(center) => {
                 if !self.disabled {
+                    if let Some(toggled) = self.toggled {
+                        self.set_toggled(!toggled, hub);
+                    }
                     bus.push_back(self.event.clone());
                 }
                 true
@@ -68,7 +137,7 @@ impl View for Button {
     fn render(&self, fb: &mut Framebuffer, fonts: &mut Fonts) {
         let dpi = CURRENT_DEVICE.dpi;
 
-        let scheme = if self.active {
+        let scheme = if self.active || self.toggled == Some(true) {
             TEXT_INVERTED_HARD
         } else {
             TEXT_NORMAL
@@ -80,22 +149,65 @@ impl View for Button {
         fb.draw_rounded_rectangle_with_border(&self.rect,
                                               &CornerSpec::Uniform(border_radius),
                                               &BorderSpec { thickness: border_thickness,
-                                                            color: BLACK },
+                                                            color: BLACK,
+                                                            style: BorderStyle::Solid },
                                               &scheme[0]);
 
         let font = font_from_style(fonts, &NORMAL_STYLE, dpi);
-        let x_height = font.x_heights.0 as i32;
         let padding = font.em() as i32;
-        let max_width = self.rect.width() as i32 - padding;
+        let icon_width = self.icon.as_ref().map_or(0, |name| button_icon_width(name, padding));
+        let max_width = (self.rect.width() as i32 - padding - icon_width).max(0) as u32;
+        let foreground = if self.disabled { scheme[2] } else { scheme[1] };
 
-        let plan = font.plan(&self.text, Some(max_width as u32), None);
+        let mut plans = match self.fit {
+            LabelFit::Truncate => vec![],
+            LabelFit::Shrink => {
+                let width = font.plan(&self.text, None, None).width;
+                let size = shrunk_size(NORMAL_STYLE.size, width, max_width, FONT_SIZES[0]);
+                font.set_size(size, dpi);
+                vec![]
+            },
+            LabelFit::Wrap => {
+                if font.plan(&self.text, None, None).width > max_width {
+                    wrap_point(&self.text, |t| font.plan(t, None, None).width).map(|index| {
+                        vec![font.plan(&self.text[..index], Some(max_width), None),
+                             font.plan(self.text[index+1..].trim(), Some(max_width), None)]
+                    }).unwrap_or_default()
+                } else {
+                    vec![]
+                }
+            },
+        };
 
-        let dx = ((self.rect.width() - plan.width) / 2) as i32;
-        let dy = (self.rect.height() as i32 - x_height) / 2;
-        let pt = pt!(self.rect.min.x + dx, self.rect.max.y - dy);
+        if plans.is_empty() {
+            plans.push(font.plan(&self.text, Some(max_width), None));
+        }
 
-        let foreground = if self.disabled { scheme[2] } else { scheme[1] };
-        font.render(fb, foreground, &plan, &pt);
+        let x_height = font.x_heights.0 as i32;
+        let line_height = 5 * x_height / 2;
+        let text_height = x_height + (plans.len() as i32 - 1) * line_height;
+        let dy = (self.rect.height() as i32 - text_height) / 2;
+
+        // The icon and the text are centered together.
+        let widest = plans.iter().map(|plan| plan.width).max().unwrap_or(0) as i32;
+        let group_dx = (self.rect.width() as i32 - icon_width - widest) / 2;
+
+        if let Some(pixmap) = self.icon.as_ref().and_then(|name| ICONS_PIXMAPS.get(&name[..])) {
+            let pt = pt!(self.rect.min.x + group_dx,
+                         self.rect.min.y + (self.rect.height() as i32 - pixmap.height) / 2);
+            fb.draw_blended_pixmap(pixmap, &pt, foreground);
+        }
+
+        for (i, plan) in plans.iter().enumerate() {
+            let dx = if self.icon.is_some() {
+                group_dx + icon_width + (widest - plan.width as i32) / 2
+            } else {
+                ((self.rect.width() - plan.width) / 2) as i32
+            };
+            let pt = pt!(self.rect.min.x + dx,
+                         self.rect.min.y + dy + x_height + i as i32 * line_height);
+            font.render(fb, foreground, plan, &pt);
+        }
     }
 
     fn rect(&self) -> &Rectangle {
@@ -114,3 +226,36 @@ impl View for Button {
         &mut self.children
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::{shrunk_size, wrap_point};
+
+    fn width_of(text: &str) -> u32 {
+        10 * text.chars().count() as u32
+    }
+
+    #[test]
+    fn shrink_long_label() {
+        let label = "Prendre une capture d'écran";
+        let max_width = 200;
+        let size = shrunk_size(524, width_of(label), max_width, 349);
+        assert!(size < 524);
+        assert!(width_of(label) * size / 524 <= max_width);
+        assert_eq!(shrunk_size(524, 150, max_width, 349), 524);
+        assert_eq!(shrunk_size(524, 1000, max_width, 349), 349);
+    }
+
+    #[test]
+    fn wrap_long_label() {
+        let label = "Bildschirmfoto aufnehmen";
+        let max_width = 150;
+        assert!(width_of(label) > max_width);
+        let index = wrap_point(label, width_of).unwrap();
+        let (first, second) = (&label[..index], label[index+1..].trim());
+        assert_eq!((first, second), ("Bildschirmfoto", "aufnehmen"));
+        assert!(width_of(first) <= max_width && width_of(second) <= max_width);
+        assert_eq!(wrap_point("Take a screenshot now", width_of), Some(6));
+        assert_eq!(wrap_point("Screenshot", width_of), None);
+    }
+}
@@ -24,15 +24,19 @@ impl Clock {
             time: Local::now(),
         }
     }
+
+    fn refresh(&mut self, hub: &Hub) {
+        self.time = Local::now();
+        hub.send(Event::Render(self.rect, UpdateMode::Gui)).unwrap();
+    }
 }
 
 impl View for Clock {
     fn handle_event(&mut self, evt: &Event, hub: &Hub, _bus: &mut Bus, _context: &mut Context) -> bool {
         match *evt {
-            Event::ClockTick => {
-                self.time = Local::now();
-                hub.send(Event::Render(self.rect, UpdateMode::Gui)).unwrap();
-                true
+            Event::RefreshStatus => {
+                self.refresh(hub);
+                false
             },
             _ => false,
         }
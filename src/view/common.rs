@@ -3,6 +3,9 @@ use view::{View, Event, Hub, ViewId, EntryId, EntryKind};
 use framebuffer::UpdateMode;
 use geom::{Point, Rectangle};
 use view::menu::{Menu, MenuKind};
+use view::frontlight::FrontlightWindow;
+use view::presets_list::PresetsSort;
+use device::CURRENT_DEVICE;
 use app::Context;
 
 pub fn shift(view: &mut View, delta: &Point) {
@@ -25,6 +28,12 @@ pub fn locate_by_id(view: &View, id: ViewId) -> Option<usize> {
     view.children().iter().position(|c| c.id().map_or(false, |i| i == id))
 }
 
+pub fn topmost_overlay(view: &View) -> Option<ViewId> {
+    view.children().iter().rev()
+        .filter_map(|c| c.id())
+        .find(|id| id.is_overlay())
+}
+
 pub fn overlapping_rectangle(view: &View) -> Rectangle {
     let mut rect = *view.rect();
     for child in view.children() {
@@ -33,9 +42,42 @@ pub fn overlapping_rectangle(view: &View) -> Rectangle {
     rect
 }
 
-pub fn toggle_main_menu(view: &mut View, rect: Rectangle, enable: Option<bool>, hub: &Hub, context: &mut Context) {
-    let fonts = &mut context.fonts;
+// The entries of the main menu shared by all the views.
+pub fn main_menu_entries(context: &Context) -> Vec<EntryKind> {
+    let mut entries = vec![EntryKind::Header("Display".to_string()),
+                           EntryKind::CheckBox("Invert Colors".to_string(),
+                                               EntryId::ToggleInverted,
+                                               context.inverted),
+                           EntryKind::CheckBox("Make Bitonal".to_string(),
+                                               EntryId::ToggleMonochrome,
+                                               context.monochrome),
+                           EntryKind::CheckBox("Enable Frontlight".to_string(),
+                                               EntryId::ToggleFrontlight,
+                                               context.settings.frontlight),
+                           EntryKind::Separator,
+                           EntryKind::Header("Connectivity".to_string()),
+                           EntryKind::CheckBox("Enable WiFi".to_string(),
+                                               EntryId::ToggleWifi,
+                                               context.settings.wifi),
+                           EntryKind::Separator,
+                           EntryKind::Header("System".to_string()),
+                           EntryKind::Command("Take Screenshot".to_string(),
+                                              EntryId::TakeScreenshot),
+                           EntryKind::Separator];
+    if env::var("PLATO_STANDALONE").is_ok() {
+        entries.extend_from_slice(&[EntryKind::Command("Start Nickel".to_string(),
+                                                       EntryId::StartNickel),
+                                    EntryKind::Command("Reboot".to_string(),
+                                                       EntryId::Reboot)]);
+    } else {
+        entries.push(EntryKind::Command("Quit".to_string(), EntryId::Quit));
+    }
+    entries
+}
 
+// The entries are given by *build*, which is only called when the menu is shown.
+pub fn toggle_main_menu<F>(view: &mut View, rect: Rectangle, enable: Option<bool>, hub: &Hub, context: &mut Context, build: F)
+                          where F: FnOnce(&Context) -> Vec<EntryKind> {
     if let Some(index) = locate_by_id(view, ViewId::MainMenu) {
         if let Some(true) = enable {
             return;
@@ -46,29 +88,127 @@ pub fn toggle_main_menu(view: &mut View, rect: Rectangle, enable: Option<bool>,
         if let Some(false) = enable {
             return;
         }
-        let mut entries = vec![EntryKind::CheckBox("Invert Colors".to_string(),
-                                                   EntryId::ToggleInverted,
-                                                   context.inverted),
-                               EntryKind::CheckBox("Make Bitonal".to_string(),
-                                                   EntryId::ToggleMonochrome,
-                                                   context.monochrome),
-                               EntryKind::CheckBox("Enable WiFi".to_string(),
-                                                   EntryId::ToggleWifi,
-                                                   context.settings.wifi),
-                               EntryKind::Separator,
-                               EntryKind::Command("Take Screenshot".to_string(),
-                                                  EntryId::TakeScreenshot),
-                               EntryKind::Separator];
-        if env::var("PLATO_STANDALONE").is_ok() {
-            entries.extend_from_slice(&[EntryKind::Command("Start Nickel".to_string(),
-                                                           EntryId::StartNickel),
-                                        EntryKind::Command("Reboot".to_string(),
-                                                           EntryId::Reboot)]);
-        } else {
-            entries.push(EntryKind::Command("Quit".to_string(), EntryId::Quit));
-        }
-        let main_menu = Menu::new(rect, ViewId::MainMenu, MenuKind::DropDown, entries, fonts);
+        let entries = build(context);
+        let main_menu = Menu::new(rect, ViewId::MainMenu, MenuKind::DropDown, entries, &mut context.fonts);
         hub.send(Event::Render(*main_menu.rect(), UpdateMode::Gui)).unwrap();
         view.children_mut().push(Box::new(main_menu) as Box<View>);
     }
 }
+
+// The entries of the menu of the preset at *index*, whose presets are sorted by *sort*.
+pub fn preset_menu_entries(index: usize, sort: PresetsSort, context: &Context) -> Vec<EntryKind> {
+    let light_preset = &context.settings.frontlight_presets[index];
+    let mut entries = vec![EntryKind::Message(light_preset.details()),
+                           EntryKind::Separator,
+                           EntryKind::Command("Rename".to_string(),
+                                              EntryId::RenamePreset(index)),
+                           EntryKind::Command("Duplicate".to_string(),
+                                              EntryId::DuplicatePreset(index)),
+                           EntryKind::Command("Remove".to_string(),
+                                              EntryId::RemovePreset(index)),
+                           EntryKind::Command("Export".to_string(),
+                                              EntryId::ExportPreset(index, false))];
+    if light_preset.lightsensor_level.is_some() {
+        entries.push(EntryKind::Command("Export with Sensor Level".to_string(),
+                                        EntryId::ExportPreset(index, true)));
+    }
+    if CURRENT_DEVICE.has_natural_light() {
+        entries.push(EntryKind::CheckBox("Warmth Only at Night".to_string(),
+                                         EntryId::ToggleNightWarmth(index),
+                                         light_preset.warmth_at_night));
+    }
+    entries.push(EntryKind::Separator);
+    let sorts = [PresetsSort::Stored, PresetsSort::Name, PresetsSort::Time];
+    let choices = vec![("Position".to_string(), EntryId::SortPresets(PresetsSort::Stored)),
+                       ("Name".to_string(), EntryId::SortPresets(PresetsSort::Name)),
+                       ("Time".to_string(), EntryId::SortPresets(PresetsSort::Time))];
+    let selected = sorts.iter().position(|&s| s == sort).unwrap_or(0);
+    entries.push(EntryKind::SubMenu("Sort By".to_string(),
+                                    vec![EntryKind::RadioGroup(choices, selected)]));
+    entries.push(EntryKind::Command("Export All".to_string(), EntryId::ExportPresets));
+    entries.push(EntryKind::SubMenu("Import".to_string(),
+                                    vec![EntryKind::Command("Merge".to_string(), EntryId::ImportPresets(false)),
+                                         EntryKind::Command("Replace".to_string(), EntryId::ImportPresets(true))]));
+    entries
+}
+
+pub fn toggle_preset_menu(view: &mut View, rect: Rectangle, index: usize, hub: &Hub, context: &mut Context) {
+    if let Some(menu_index) = locate_by_id(view, ViewId::PresetMenu) {
+        hub.send(Event::Expose(*view.child(menu_index).rect())).unwrap();
+        view.children_mut().remove(menu_index);
+    } else {
+        let sort = locate::<FrontlightWindow>(view)
+                       .and_then(|index| view.child(index).downcast_ref::<FrontlightWindow>())
+                       .map_or(PresetsSort::Stored, |flw| flw.presets_sort());
+        let entries = preset_menu_entries(index, sort, context);
+        let preset_menu = Menu::new(rect, ViewId::PresetMenu, MenuKind::Contextual,
+                                    entries, &mut context.fonts);
+        hub.send(Event::Render(*preset_menu.rect(), UpdateMode::Gui)).unwrap();
+        view.children_mut().push(Box::new(preset_menu) as Box<View>);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use view::{View, Event, Hub, Bus, ViewId};
+    use framebuffer::Framebuffer;
+    use font::Fonts;
+    use geom::Rectangle;
+    use app::Context;
+    use super::topmost_overlay;
+
+    struct Stub {
+        rect: Rectangle,
+        children: Vec<Box<View>>,
+        id: Option<ViewId>,
+    }
+
+    impl Stub {
+        fn new(id: Option<ViewId>, children: Vec<Box<View>>) -> Box<View> {
+            Box::new(Stub { rect: rect![0, 0, 10, 10], children, id }) as Box<View>
+        }
+    }
+
+    impl View for Stub {
+        fn handle_event(&mut self, _evt: &Event, _hub: &Hub, _bus: &mut Bus, _context: &mut Context) -> bool {
+            false
+        }
+
+        fn render(&self, _fb: &mut Framebuffer, _fonts: &mut Fonts) {
+        }
+
+        fn rect(&self) -> &Rectangle {
+            &self.rect
+        }
+
+        fn rect_mut(&mut self) -> &mut Rectangle {
+            &mut self.rect
+        }
+
+        fn children(&self) -> &Vec<Box<View>> {
+            &self.children
+        }
+
+        fn children_mut(&mut self) -> &mut Vec<Box<View>> {
+            &mut self.children
+        }
+
+        fn id(&self) -> Option<ViewId> {
+            self.id
+        }
+    }
+
+    #[test]
+    fn stacked_overlays() {
+        let root = Stub::new(None, vec![Stub::new(None, vec![]),
+                                        Stub::new(Some(ViewId::MainMenu), vec![]),
+                                        Stub::new(Some(ViewId::Frontlight), vec![]),
+                                        Stub::new(Some(ViewId::NetUpNotif), vec![])]);
+        assert_eq!(topmost_overlay(root.as_ref()), Some(ViewId::Frontlight));
+        let root = Stub::new(None, vec![Stub::new(Some(ViewId::PresetMenu), vec![]),
+                                        Stub::new(Some(ViewId::SubMenu(1)), vec![])]);
+        assert_eq!(topmost_overlay(root.as_ref()), Some(ViewId::SubMenu(1)));
+        let root = Stub::new(None, vec![Stub::new(Some(ViewId::NetUpNotif), vec![])]);
+        assert_eq!(topmost_overlay(root.as_ref()), None);
+    }
+}
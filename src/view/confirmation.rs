@@ -1,10 +1,10 @@
 use std::thread;
 use device::CURRENT_DEVICE;
-use geom::{Rectangle, CornerSpec, BorderSpec};
+use geom::{Rectangle, CornerSpec, BorderSpec, BorderStyle};
 use font::{Fonts, font_from_style, NORMAL_STYLE};
-use view::{View, Event, Hub, Bus, ViewId, Align};
+use view::{View, Event, Hub, Bus, ViewId, Align, VAlign};
 use view::{THICKNESS_LARGE, BORDER_RADIUS_MEDIUM, CLOSE_IGNITION_DELAY};
-use view::button::Button;
+use view::button::{Button, LabelFit};
 use view::label::Label;
 use framebuffer::Framebuffer;
 use gesture::GestureEvent;
@@ -15,11 +15,22 @@ use app::Context;
 const LABEL_VALIDATE: &str = "OK";
 const LABEL_CANCEL: &str = "Cancel";
 
+// Localized labels can be wider than the buttons.
+fn label_fit(label: &str) -> LabelFit {
+    if label.contains(' ') {
+        LabelFit::Wrap
+    } else {
+        LabelFit::Shrink
+    }
+}
+
 pub struct Confirmation {
     rect: Rectangle,
     children: Vec<Box<View>>,
     id: ViewId,
     event: Event,
+    // Sent when the dialog is canceled.
+    cancel_event: Option<Event>,
     will_close: bool,
 }
 
@@ -54,7 +65,9 @@ impl Confirmation {
                                rect.max.x - padding,
                                rect.min.y + padding + button_height];
 
-        let label = Label::new(rect_label, text, Align::Center);
+        // Messages broken into several lines read from the top.
+        let label = Label::new(rect_label, text, Align::Center).wrap(true)
+                                                               .valign(VAlign::Top(0));
 
         children.push(Box::new(label) as Box<View>);
 
@@ -73,10 +86,12 @@ impl Confirmation {
                                   rect.max.x - padding,
                                   rect.max.y - padding];
 
-        let button_cancel = Button::new(rect_cancel, Event::Cancel, LABEL_CANCEL.to_string()); 
+        let button_cancel = Button::new(rect_cancel, Event::Cancel, LABEL_CANCEL.to_string())
+                                   .fit(label_fit(LABEL_CANCEL));
         children.push(Box::new(button_cancel) as Box<View>);
 
-        let button_validate = Button::new(rect_validate, Event::Validate, LABEL_VALIDATE.to_string()); 
+        let button_validate = Button::new(rect_validate, Event::Validate, LABEL_VALIDATE.to_string())
+                                     .fit(label_fit(LABEL_VALIDATE));
         children.push(Box::new(button_validate) as Box<View>);
 
         Confirmation {
@@ -84,9 +99,15 @@ impl Confirmation {
             children,
             id,
             event,
+            cancel_event: None,
             will_close: false,
         }
     }
+
+    pub fn cancel_event(mut self, event: Event) -> Confirmation {
+        self.cancel_event = Some(event);
+        self
+    }
 }
 
 impl View for Confirmation {
@@ -104,6 +125,8 @@ impl View for Confirmation {
                 });
                 if let Event::Validate = *evt {
                     bus.push_back(self.event.clone());
+                } else if let Some(ref event) = self.cancel_event {
+                    bus.push_back(event.clone());
                 }
                 self.will_close = true;
                 true
@@ -126,7 +149,8 @@ impl View for Confirmation {
         fb.draw_rounded_rectangle_with_border(&self.rect,
                                               &CornerSpec::Uniform(border_radius),
                                               &BorderSpec { thickness: border_thickness,
-                                                            color: BLACK },
+                                                            color: BLACK,
+                                                            style: BorderStyle::Solid },
                                               &WHITE);
     }
 
@@ -1,37 +1,75 @@
+use std::fs;
+use std::thread;
+use std::path::Path;
 use std::sync::mpsc;
+use std::sync::Arc;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::time::Duration;
 use device::{CURRENT_DEVICE, BAR_SIZES};
+use lightsensor::{calibrated_level, to_lux};
 use framebuffer::{Framebuffer, UpdateMode};
-use geom::{Rectangle, CornerSpec, BorderSpec};
-use font::{Fonts, font_from_style, NORMAL_STYLE};
+use geom::{Point, Rectangle, CornerSpec, BorderSpec, BorderStyle, Dir};
+use font::{Fonts, font_from_style, NORMAL_STYLE, HEADER_STYLE};
 use view::{View, Event, Hub, Bus, ViewId, EntryId, SliderId, Align};
 use view::{THICKNESS_LARGE, BORDER_RADIUS_MEDIUM};
-use view::label::Label;
-use view::button::Button;
+use view::label::{Label, Truncation};
+use view::button::{Button, button_icon_width};
 use view::slider::Slider;
 use view::icon::Icon;
-use view::presets_list::PresetsList;
+use view::presets_list::{PresetsList, PresetsSort};
 use view::common::shift;
-use frontlight::LightLevels;
+use frontlight::{LightLevels, Fade, DEFAULT_LEVELS, floored_intensity};
+use frontlight::{intensity_from_position, position_from_intensity};
 use gesture::GestureEvent;
-use input::FingerStatus;
-use settings::{LightPreset, guess_frontlight};
-use color::{BLACK, WHITE};
+use input::{DeviceEvent, FingerStatus, ButtonCode, ButtonStatus};
+use settings::{LightPreset, guess_frontlight, cycle_preset_index, insert_preset, merge_presets, move_preset, can_guess};
+use settings::steady_lightsensor_level;
+use settings::{FrontlightKeys, PRESETS_DIRNAME, PRESETS_FILENAME, is_preset_file_name};
+use helpers::{load_json, save_json};
+use color::{BLACK, WHITE, TEXT_MUTED};
 use unit::scale_by_dpi;
 use app::Context;
+use errors::*;
 
 const LABEL_SAVE: &str = "Save";
+// Saving adds a preset.
+const ICON_SAVE: &str = "plus";
 const LABEL_GUESS: &str = "Guess";
+const LABEL_RESET: &str = "Reset";
+const LABEL_COOL: &str = "Cool";
+const LABEL_TITLE: &str = "Frontlight";
+const TITLE_INDEX: usize = 1;
+const REPEAT_INTERVAL: Duration = Duration::from_millis(150);
+const LOCK_ICON_INDEX: usize = 2;
+const BUTTON_COOL_INDEX: usize = 14;
+const NUDGE_STEP: f32 = 1.0;
 
 pub struct FrontlightWindow {
     rect: Rectangle,
     children: Vec<Box<View>>,
+    current_preset: Option<usize>,
+    presets_rows: usize,
+    presets_sort: PresetsSort,
+    fade: Option<Fade>,
+    repeat: Option<Arc<AtomicBool>>,
+    renaming: Option<usize>,
+    removing: Option<LightPreset>,
+    // The imported presets, while the user chooses whether to skip their duplicates.
+    importing: Option<Vec<LightPreset>>,
+    // The ratio of the warmth to the intensity, while both are locked together.
+    ratio: Option<f32>,
+    gamma: f32,
+    // The levels when the window was opened.
+    opening_levels: LightLevels,
 }
 
 impl FrontlightWindow {
-    pub fn new(context: &mut Context) -> FrontlightWindow {
+    pub fn new(context: &mut Context, anchor: Option<Point>) -> FrontlightWindow {
         let fonts = &mut context.fonts;
         let levels = context.frontlight.levels();
         let presets = &context.settings.frontlight_presets;
+        let gamma = context.settings.frontlight_gamma;
+        let min_position = position_from_intensity(context.settings.frontlight_min_intensity, gamma);
         let mut children = Vec::new();
         let dpi = CURRENT_DEVICE.dpi;
         let (width, height) = CURRENT_DEVICE.dims;
@@ -39,27 +77,29 @@ impl FrontlightWindow {
         let thickness = scale_by_dpi(THICKNESS_LARGE, dpi) as i32;
         let border_radius = scale_by_dpi(BORDER_RADIUS_MEDIUM, dpi) as i32;
 
-        let (x_height, padding) = {
+        let (x_height, padding, value_width) = {
             let font = font_from_style(fonts, &NORMAL_STYLE, dpi);
-            (font.x_heights.0 as i32, font.em() as i32)
+            let padding = font.em() as i32;
+            (font.x_heights.0 as i32, padding, font.plan("100", None, None).width as i32 + padding)
         };
 
-        let window_width = width as i32 - 2 * padding;
-
         let mut window_height = small_height as i32 * 3 + 2 * padding;
 
         if CURRENT_DEVICE.has_natural_light() {
             window_height += small_height as i32;
         }
 
-        if !presets.is_empty() {
-            window_height += small_height as i32;
-        }
+        let window_width = width as i32 - 2 * padding;
+        let presets_width = window_width - 2 * thickness - 8 * padding;
+        let wrap = context.settings.frontlight_presets_wrap;
+        let presets_rows = PresetsList::rows_count(presets, presets_width, wrap, PresetsSort::Stored, fonts);
+        window_height += presets_rows as i32 * small_height as i32;
 
-        let dx = (width as i32 - window_width) / 2;
-        let dy = (height as i32 - window_height) / 3;
+        let origin = window_origin((window_width, window_height),
+                                   (width as i32, height as i32),
+                                   anchor);
 
-        let rect = rect![dx, dy, dx + window_width, dy + window_height];
+        let rect = rect![origin.x, origin.y, origin.x + window_width, origin.y + window_height];
 
         let close_icon = Icon::new("close",
                                    rect![rect.max.x - small_height as i32,
@@ -75,14 +115,24 @@ impl FrontlightWindow {
                                      rect.min.y + thickness,
                                      rect.max.x - small_height as i32,
                                      rect.min.y + small_height as i32],
-                               "Frontlight".to_string(),
-                               Align::Center);
+                               LABEL_TITLE.to_string(),
+                               Align::Center).style(&HEADER_STYLE);
 
         children.push(Box::new(label) as Box<View>);
 
         let mut button_y = rect.min.y + 2 * small_height as i32;
 
         if CURRENT_DEVICE.has_natural_light() {
+            let lock_icon = Icon::new("lock-open",
+                                      rect![rect.min.x + thickness,
+                                            rect.min.y + thickness,
+                                            rect.min.x + small_height as i32,
+                                            rect.min.y + small_height as i32],
+                                      Event::ToggleLevelsLock)
+                                 .corners(Some(CornerSpec::Uniform(border_radius - thickness)));
+
+            children.push(Box::new(lock_icon) as Box<View>);
+
             let max_label_width = {
                 let font = font_from_style(fonts, &NORMAL_STYLE, dpi);
                 ["Intensity", "Warmth"].iter().map(|t| font.plan(t, None, None).width)
@@ -99,67 +149,141 @@ impl FrontlightWindow {
                                        Align::Right(padding / 2));
                 children.push(Box::new(label) as Box<View>);
 
-                let value = if *slider_id == SliderId::LightIntensity {
-                    levels.intensity
+                let (value, position, default_position) = if *slider_id == SliderId::LightIntensity {
+                    (levels.intensity, position_from_intensity(levels.intensity, gamma),
+                     position_from_intensity(DEFAULT_LEVELS.intensity, gamma))
                 } else {
-                    levels.warmth
+                    (levels.warmth, levels.warmth, DEFAULT_LEVELS.warmth)
                 };
 
-                let slider = Slider::new(rect![rect.min.x + max_label_width + 3 * padding,
-                                               min_y,
-                                               rect.max.x - padding,
-                                               min_y + small_height as i32],
-                                         *slider_id,
-                                         value,
-                                         0.0,
-                                         100.0);
+                let slider_min_x = rect.min.x + max_label_width + 3 * padding + small_height as i32;
+                let slider_max_x = rect.max.x - padding - value_width - small_height as i32;
+
+                let minus_icon = Icon::new("minus",
+                                           rect![slider_min_x - small_height as i32, min_y,
+                                                 slider_min_x, min_y + small_height as i32],
+                                           Event::Nudge(*slider_id, -NUDGE_STEP));
+                children.push(Box::new(minus_icon) as Box<View>);
+
+                let mut slider = Slider::new(rect![slider_min_x,
+                                                   min_y,
+                                                   slider_max_x,
+                                                   min_y + small_height as i32],
+                                             *slider_id,
+                                             position,
+                                             0.0,
+                                             100.0).default_value(default_position);
+                if let (SliderId::LightWarmth, Some(step)) = (*slider_id, context.settings.frontlight_warmth_step) {
+                    slider = slider.step(step).ticks((100.0 / step) as u32 + 1);
+                }
+                if *slider_id == SliderId::LightIntensity {
+                    slider = slider.markers(intensity_markers(presets, gamma))
+                                   .floor(min_position);
+                }
                 children.push(Box::new(slider) as Box<View>);
+
+                let plus_icon = Icon::new("plus",
+                                          rect![slider_max_x, min_y,
+                                                slider_max_x + small_height as i32, min_y + small_height as i32],
+                                          Event::Nudge(*slider_id, NUDGE_STEP));
+                children.push(Box::new(plus_icon) as Box<View>);
+
+                // The values are clipped rather than ellipsized, which would hide more digits.
+                let value_label = Label::new(rect![rect.max.x - padding - value_width,
+                                                   min_y,
+                                                   rect.max.x - padding,
+                                                   min_y + small_height as i32],
+                                             format!("{:.0}", value),
+                                             Align::Right(padding / 2)).truncation(Truncation::Clip)
+                                                                       .scheme(TEXT_MUTED);
+                children.push(Box::new(value_label) as Box<View>);
             }
 
             button_y += small_height as i32;
         } else {
                 let min_y = rect.min.y + small_height as i32;
-                let slider = Slider::new(rect![rect.min.x + padding,
+                let slider_min_x = rect.min.x + padding + small_height as i32;
+                let slider_max_x = rect.max.x - padding - value_width - small_height as i32;
+
+                let minus_icon = Icon::new("minus",
+                                           rect![slider_min_x - small_height as i32, min_y,
+                                                 slider_min_x, min_y + small_height as i32],
+                                           Event::Nudge(SliderId::LightIntensity, -NUDGE_STEP));
+                children.push(Box::new(minus_icon) as Box<View>);
+
+                let slider = Slider::new(rect![slider_min_x,
                                                min_y,
-                                               rect.max.x - padding,
+                                               slider_max_x,
                                                min_y + small_height as i32],
                                          SliderId::LightIntensity,
-                                         levels.intensity,
+                                         position_from_intensity(levels.intensity, gamma),
                                          0.0,
-                                         100.0);
+                                         100.0).markers(intensity_markers(presets, gamma))
+                                               .floor(min_position)
+                                               .default_value(position_from_intensity(DEFAULT_LEVELS.intensity, gamma));
                 children.push(Box::new(slider) as Box<View>);
+
+                let plus_icon = Icon::new("plus",
+                                          rect![slider_max_x, min_y,
+                                                slider_max_x + small_height as i32, min_y + small_height as i32],
+                                          Event::Nudge(SliderId::LightIntensity, NUDGE_STEP));
+                children.push(Box::new(plus_icon) as Box<View>);
+
+                // The values are clipped rather than ellipsized, which would hide more digits.
+                let value_label = Label::new(rect![rect.max.x - padding - value_width,
+                                                   min_y,
+                                                   rect.max.x - padding,
+                                                   min_y + small_height as i32],
+                                             format!("{:.0}", levels.intensity),
+                                             Align::Right(padding / 2)).truncation(Truncation::Clip)
+                                                                       .scheme(TEXT_MUTED);
+                children.push(Box::new(value_label) as Box<View>);
         }
 
         let max_label_width = {
             let font = font_from_style(fonts, &NORMAL_STYLE, dpi);
-            [LABEL_SAVE, LABEL_GUESS].iter().map(|t| font.plan(t, None, None).width)
-                                                         .max().unwrap() as i32
+            let save_width = font.plan(LABEL_SAVE, None, None).width as i32 + button_icon_width(ICON_SAVE, padding);
+            [LABEL_GUESS, LABEL_RESET, LABEL_COOL].iter().map(|t| font.plan(t, None, None).width as i32)
+                                                  .max().unwrap().max(save_width)
         };
 
         let button_height = 4 * x_height;
+        let button_width = max_label_width + 2 * padding;
+
+        let mut buttons = vec![(Event::Save, LABEL_SAVE)];
+
+        if CURRENT_DEVICE.has_natural_light() {
+            buttons.push((Event::ToggleWarmth, LABEL_COOL));
+        }
+
+        buttons.push((Event::ResetFrontlight, LABEL_RESET));
+        buttons.push((Event::Guess, LABEL_GUESS));
 
-        let button_save = Button::new(rect![rect.min.x + 3 * padding,
-                                            button_y + small_height as i32 - button_height,
-                                            rect.min.x + 5 * padding + max_label_width,
-                                            button_y + small_height as i32],
-                                      Event::Save,
-                                      LABEL_SAVE.to_string());
-        children.push(Box::new(button_save) as Box<View>);
-
-        let button_guess = Button::new(rect![rect.max.x - 5 * padding - max_label_width,
-                                             button_y + small_height as i32 - button_height,
-                                             rect.max.x - 3 * padding,
-                                             button_y + small_height as i32],
-                                       Event::Guess,
-                                       LABEL_GUESS.to_string()).disabled(presets.len() < 2);
-        children.push(Box::new(button_guess) as Box<View>);
-
-        if !presets.is_empty() {
+        let count = buttons.len() as i32;
+        let gap = ((window_width - 6 * padding - count * button_width) / (count - 1)).max(0);
+
+        for (i, (event, label)) in buttons.into_iter().enumerate() {
+            let disabled = button_disabled(&event, presets);
+            let x = rect.min.x + 3 * padding + i as i32 * (button_width + gap);
+            let mut button = Button::new(rect![x, button_y + small_height as i32 - button_height,
+                                               x + button_width, button_y + small_height as i32],
+                                         event.clone(),
+                                         label.to_string()).disabled(disabled);
+            match event {
+                Event::Save => button = button.icon(ICON_SAVE),
+                // The button stays pressed while the warmth is cooled.
+                Event::ToggleWarmth => button = button.toggled(context.settings.frontlight_cooled_warmth.is_some()),
+                _ => (),
+            }
+            children.push(Box::new(button) as Box<View>);
+        }
+
+        if presets_rows > 0 {
             let presets_rect = rect![rect.min.x + thickness + 4 * padding,
-                                     rect.max.y - small_height as i32 - 2 * padding,
+                                     rect.max.y - presets_rows as i32 * small_height as i32 - 2 * padding,
                                      rect.max.x - thickness - 4 * padding,
                                      rect.max.y - thickness - 2 * padding];
-            let mut presets_list = PresetsList::new(presets_rect);
+            let mut presets_list = PresetsList::new(presets_rect).wrap(wrap);
             let (tx, _rx) = mpsc::channel();
             presets_list.update(&presets, &tx, fonts);
             children.push(Box::new(presets_list) as Box<View>);
@@ -168,61 +292,341 @@ impl FrontlightWindow {
         FrontlightWindow {
             rect,
             children,
+            current_preset: None,
+            presets_rows,
+            presets_sort: PresetsSort::Stored,
+            fade: None,
+            repeat: None,
+            renaming: None,
+            removing: None,
+            importing: None,
+            ratio: None,
+            gamma,
+            opening_levels: levels,
+        }
+    }
+
+    fn remove_preset(&mut self, index: usize, hub: &Hub, context: &mut Context) {
+        if index < context.settings.frontlight_presets.len() {
+            context.settings.frontlight_presets.remove(index);
+            self.current_preset = None;
+            self.update_guess_button(hub, context);
+            self.resize_presets(hub, context);
+        }
+    }
+
+    // Grows or shrinks the window according to the number of rows
+    // needed by the presets, and updates the presets list.
+    // The duplicates are either merged into the existing presets, or added like the others.
+    fn add_presets(&mut self, imported: Vec<LightPreset>, skip_duplicates: bool, hub: &Hub, context: &mut Context) {
+        let count = imported.len();
+        let merged = if skip_duplicates {
+            merge_presets(&mut context.settings.frontlight_presets, imported)
+        } else {
+            for light_preset in imported {
+                insert_preset(&mut context.settings.frontlight_presets, light_preset);
+            }
+            0
+        };
+        if merged > 0 {
+            hub.send(Event::PresetsMerged(merged)).unwrap();
+        }
+        if merged < count {
+            // The imported presets are inserted in chronological order.
+            context.settings.frontlight_presets_sorted = true;
+            self.current_preset = None;
+            self.update_guess_button(hub, context);
+            self.resize_presets(hub, context);
         }
     }
 
-    fn toggle_presets(&mut self, enable: bool, hub: &Hub, context: &mut Context) {
+    fn resize_presets(&mut self, hub: &Hub, context: &mut Context) {
         let dpi = CURRENT_DEVICE.dpi;
-        let (_, height) = CURRENT_DEVICE.dims;
+        let (width, height) = CURRENT_DEVICE.dims;
         let &(small_height, _) = BAR_SIZES.get(&(height, dpi)).unwrap();
+        let thickness = scale_by_dpi(THICKNESS_LARGE, dpi) as i32;
+        let padding = {
+            let font = font_from_style(&mut context.fonts, &NORMAL_STYLE, dpi);
+            font.em() as i32
+        };
+        let wrap = context.settings.frontlight_presets_wrap;
+        let presets_width = self.rect.width() as i32 - 2 * thickness - 8 * padding;
+        let rows = PresetsList::rows_count(&context.settings.frontlight_presets,
+                                           presets_width, wrap, self.presets_sort, &mut context.fonts);
 
-        if enable {
-            let thickness = scale_by_dpi(THICKNESS_LARGE, dpi) as i32;
-            let padding = {
-                let font = font_from_style(&mut context.fonts, &NORMAL_STYLE, dpi);
-                font.em() as i32
-            };
-            shift(self, &pt!(0, -(small_height as i32) / 2));
-            self.rect.max.y += small_height as i32;
+        if rows == self.presets_rows {
+            self.update_presets(hub, context);
+            return;
+        }
+
+        self.update_markers(context);
+
+        if self.presets_rows > 0 {
+            self.children.pop();
+        }
+
+        let delta = (rows as i32 - self.presets_rows as i32) * small_height as i32;
+
+        if delta < 0 {
+            hub.send(Event::Expose(self.rect)).unwrap();
+        }
+
+        // The window keeps its center, unless it would leave the screen.
+        let origin = window_origin((self.rect.width() as i32, self.rect.height() as i32 + delta),
+                                   (width as i32, height as i32),
+                                   Some(self.rect.center()));
+        let offset = origin - self.rect.min;
+        shift(self, &offset);
+        self.rect.max.y += delta;
+        self.presets_rows = rows;
+
+        if rows > 0 {
             let (tx, _rx) = mpsc::channel();
             let presets_rect = rect![self.rect.min.x + thickness + 4 * padding,
-                                     self.rect.max.y - small_height as i32 - 2 * padding,
+                                     self.rect.max.y - rows as i32 * small_height as i32 - 2 * padding,
                                      self.rect.max.x - thickness - 4 * padding,
                                      self.rect.max.y - thickness - 2 * padding];
-            let mut presets_list = PresetsList::new(presets_rect);
+            let mut presets_list = PresetsList::new(presets_rect).wrap(wrap).sort(self.presets_sort);
             presets_list.update(&context.settings.frontlight_presets, &tx, &mut context.fonts);
             self.children.push(Box::new(presets_list) as Box<View>);
             hub.send(Event::Render(self.rect, UpdateMode::Gui)).unwrap();
-        } else {
-            self.children.pop();
-            hub.send(Event::Expose(self.rect)).unwrap();
-            shift(self, &pt!(0, small_height as i32 / 2));
-            self.rect.max.y -= small_height as i32;
         }
     }
 
     fn set_frontlight_levels(&mut self, frontlight_levels: &LightLevels, hub: &Hub, context: &mut Context) {
-        let LightLevels { intensity, warmth } = *frontlight_levels;
+        let LightLevels { intensity, warmth } = frontlight_levels.floored(context.settings.frontlight_min_intensity);
         context.frontlight.set_intensity(intensity);
         context.frontlight.set_warmth(warmth);
+        self.set_slider_value(SliderId::LightIntensity, intensity, hub);
         if CURRENT_DEVICE.has_natural_light() {
-            if let Some(slider_intensity) = self.child_mut(3).downcast_mut::<Slider>() {
-                slider_intensity.value = intensity;
-                hub.send(Event::Render(*slider_intensity.rect(), UpdateMode::Gui)).unwrap();
+            self.forget_cooled_warmth(hub, context);
+            self.set_slider_value(SliderId::LightWarmth, warmth, hub);
+        }
+    }
+
+    fn set_slider_value(&mut self, id: SliderId, value: f32, hub: &Hub) {
+        let index = slider_index(id);
+        let position = self.position(id, value);
+        if let Some(slider) = self.child_mut(index).downcast_mut::<Slider>() {
+            slider.value = position;
+            hub.send(Event::Render(*slider.rect(), UpdateMode::Gui)).unwrap();
+        }
+        self.update_value_label(id, value, hub);
+    }
+
+    // Converts between the positions of the sliders and the levels they stand for.
+    fn position(&self, id: SliderId, value: f32) -> f32 {
+        if id == SliderId::LightIntensity {
+            position_from_intensity(value, self.gamma)
+        } else {
+            value
+        }
+    }
+
+    fn level(&self, id: SliderId, position: f32) -> f32 {
+        if id == SliderId::LightIntensity {
+            intensity_from_position(position, self.gamma)
+        } else {
+            position
+        }
+    }
+
+    fn update_value_label(&mut self, id: SliderId, value: f32, hub: &Hub) {
+        let index = slider_index(id) + 2;
+        if let Some(value_label) = self.child_mut(index).downcast_mut::<Label>() {
+            value_label.update(format!("{:.0}", value), hub);
+        }
+    }
+
+    fn fade_to(&mut self, frontlight_levels: &LightLevels, duration: Duration, hub: &Hub, context: &mut Context) {
+        self.cancel_fade(hub);
+        let frames = context.settings.frontlight_fade.frames;
+        if duration == Duration::default() || frames == 0 {
+            self.set_frontlight_levels(frontlight_levels, hub, context);
+            return;
+        }
+        let target = frontlight_levels.floored(context.settings.frontlight_min_intensity);
+        let mut start = context.frontlight.levels();
+        // Without natural light, only the intensity is ramped.
+        if !CURRENT_DEVICE.has_natural_light() {
+            start.warmth = target.warmth;
+        }
+        let fade = Fade::new(start, target, frames);
+        let hub2 = hub.clone();
+        fade.run(duration, move || hub2.send(Event::FadeTick).is_ok());
+        self.fade = Some(fade);
+    }
+
+    // Stops the running fade, if any, and returns its target.
+    fn cancel_fade(&mut self, hub: &Hub) -> Option<LightLevels> {
+        let target = self.fade.take().map(|fade| {
+            fade.cancel();
+            fade.target()
+        });
+        if target.is_some() {
+            self.show_fade_progress(None, hub);
+        }
+        target
+    }
+
+    // Jumps to the target of the running fade, so that no intermediate levels linger.
+    fn finish_fade(&mut self, hub: &Hub, context: &mut Context) {
+        if let Some(ref frontlight_levels) = self.cancel_fade(hub) {
+            self.set_frontlight_levels(frontlight_levels, hub, context);
+        }
+    }
+
+    // Leaves the frontlight at the target of the running fade before the window is closed.
+    // Returns whether the levels were changed while the window was open.
+    pub fn close(&mut self, hub: &Hub, context: &mut Context) -> bool {
+        self.stop_repeat();
+        self.finish_fade(hub, context);
+        context.frontlight.levels() != self.opening_levels
+    }
+
+    // Draws the progress of the running fade around the current preset.
+    fn show_fade_progress(&mut self, progress: Option<f32>, hub: &Hub) {
+        if self.presets_rows == 0 {
+            return;
+        }
+        if let Some(index) = self.current_preset {
+            if let Some(presets_list) = self.children.last_mut().and_then(|c| c.downcast_mut::<PresetsList>()) {
+                presets_list.set_progress(index, progress, hub);
+            }
+        }
+    }
+
+    fn update_guess_button(&mut self, hub: &Hub, context: &Context) {
+        let disabled = button_disabled(&Event::Guess, &context.settings.frontlight_presets);
+        let index = self.len() - 1 - self.presets_rows.min(1);
+        if let Some(button_guess) = self.child_mut(index).downcast_mut::<Button>() {
+            if button_guess.disabled != disabled {
+                button_guess.disabled = disabled;
+                hub.send(Event::Render(*button_guess.rect(), UpdateMode::Gui)).unwrap();
+            }
+        }
+    }
+
+    fn step_intensity(&mut self, delta: f32, hub: &Hub, context: &mut Context) {
+        self.finish_fade(hub, context);
+        let intensity = stepped_intensity(context.frontlight.levels().intensity, delta,
+                                          context.settings.frontlight_min_intensity);
+        context.frontlight.set_intensity(intensity);
+        self.set_slider_value(SliderId::LightIntensity, intensity, hub);
+        self.follow_ratio(SliderId::LightIntensity, intensity, hub, context);
+    }
+
+    // The ratio can only be captured when both levels are positive.
+    fn toggle_levels_lock(&mut self, hub: &Hub, context: &Context) {
+        self.ratio = if self.ratio.is_some() {
+            None
+        } else {
+            let LightLevels { intensity, warmth } = context.frontlight.levels();
+            if intensity > 0.0 && warmth > 0.0 {
+                Some(warmth / intensity)
+            } else {
+                None
             }
-            if let Some(slider_warmth) = self.child_mut(5).downcast_mut::<Slider>() {
-                slider_warmth.value = warmth;
-                hub.send(Event::Render(*slider_warmth.rect(), UpdateMode::Gui)).unwrap();
+        };
+        let name = if self.ratio.is_some() { "lock" } else { "lock-open" };
+        if let Some(lock_icon) = self.child_mut(LOCK_ICON_INDEX).downcast_mut::<Icon>() {
+            lock_icon.name = name.to_string();
+            hub.send(Event::Render(*lock_icon.rect(), UpdateMode::Gui)).unwrap();
+        }
+    }
+
+    // Makes the other level follow the level *id* that was just set to *value*.
+    fn follow_ratio(&mut self, id: SliderId, value: f32, hub: &Hub, context: &mut Context) {
+        if let Some(ratio) = self.ratio {
+            match id {
+                SliderId::LightIntensity => {
+                    let warmth = (value * ratio).min(100.0);
+                    context.frontlight.set_warmth(warmth);
+                    self.set_slider_value(SliderId::LightWarmth, warmth, hub);
+                    self.forget_cooled_warmth(hub, context);
+                },
+                SliderId::LightWarmth => {
+                    let intensity = floored_intensity((value / ratio).min(100.0), context.settings.frontlight_min_intensity);
+                    context.frontlight.set_intensity(intensity);
+                    self.set_slider_value(SliderId::LightIntensity, intensity, hub);
+                },
+                _ => (),
             }
+        }
+    }
+
+    fn nudge(&mut self, id: SliderId, delta: f32, hub: &Hub) {
+        let index = slider_index(id);
+        if let Some(slider) = self.child_mut(index).downcast_mut::<Slider>() {
+            let value = slider.nudge(delta);
+            hub.send(Event::Render(*slider.rect(), UpdateMode::Gui)).unwrap();
+            hub.send(Event::Slider(id, value, FingerStatus::Up)).unwrap();
+        }
+    }
+
+    // Sends the given event repeatedly until the key or icon is released.
+    fn start_repeat(&mut self, evt: Event, hub: &Hub) {
+        self.stop_repeat();
+        let stopped = Arc::new(AtomicBool::new(false));
+        let stopped2 = Arc::clone(&stopped);
+        let hub2 = hub.clone();
+        thread::spawn(move || {
+            loop {
+                thread::sleep(REPEAT_INTERVAL);
+                if stopped2.load(Ordering::Relaxed) || hub2.send(evt.clone()).is_err() {
+                    break;
+                }
+            }
+        });
+        self.repeat = Some(stopped);
+    }
+
+    fn stop_repeat(&mut self) {
+        if let Some(stopped) = self.repeat.take() {
+            stopped.store(true, Ordering::Relaxed);
+        }
+    }
+
+    fn toggle_warmth(&mut self, hub: &Hub, context: &mut Context) {
+        let warmth = if let Some(warmth) = context.settings.frontlight_cooled_warmth.take() {
+            warmth
         } else {
-            if let Some(slider_intensity) = self.child_mut(2).downcast_mut::<Slider>() {
-                slider_intensity.value = intensity;
-                hub.send(Event::Render(*slider_intensity.rect(), UpdateMode::Gui)).unwrap();
+            context.settings.frontlight_cooled_warmth = Some(context.frontlight.levels().warmth);
+            0.0
+        };
+        context.frontlight.set_warmth(warmth);
+        self.set_slider_value(SliderId::LightWarmth, warmth, hub);
+        let cooled = context.settings.frontlight_cooled_warmth.is_some();
+        if let Some(button_cool) = self.child_mut(BUTTON_COOL_INDEX).downcast_mut::<Button>() {
+            button_cool.set_toggled(cooled, hub);
+        }
+    }
+
+    // Forget the remembered warmth once the warmth is set by other means.
+    fn forget_cooled_warmth(&mut self, hub: &Hub, context: &mut Context) {
+        if context.settings.frontlight_cooled_warmth.take().is_some() {
+            if let Some(button_cool) = self.child_mut(BUTTON_COOL_INDEX).downcast_mut::<Button>() {
+                button_cool.set_toggled(false, hub);
             }
         }
     }
 
+    fn update_markers(&mut self, context: &Context) -> Option<Rectangle> {
+        let index = slider_index(SliderId::LightIntensity);
+        let gamma = self.gamma;
+        let slider_intensity = self.child_mut(index).downcast_mut::<Slider>()?;
+        slider_intensity.set_markers(intensity_markers(&context.settings.frontlight_presets, gamma));
+        Some(*slider_intensity.rect())
+    }
+
+    pub fn presets_sort(&self) -> PresetsSort {
+        self.presets_sort
+    }
+
     fn update_presets(&mut self, hub: &Hub, context: &mut Context) {
+        if let Some(rect) = self.update_markers(context) {
+            hub.send(Event::Render(rect, UpdateMode::Gui)).unwrap();
+        }
         let len = self.len();
         if let Some(presets_list) = self.child_mut(len - 1).downcast_mut::<PresetsList>() {
             presets_list.update(&context.settings.frontlight_presets, hub, &mut context.fonts);
@@ -230,78 +634,393 @@ impl FrontlightWindow {
     }
 }
 
+// The window is centered horizontally and placed in the upper third of the screen,
+// unless an anchor is given, in which case it's centered on the anchor. It never leaves the screen.
+fn window_origin(size: (i32, i32), screen: (i32, i32), anchor: Option<Point>) -> Point {
+    let (window_width, window_height) = size;
+    let (width, height) = screen;
+    match anchor {
+        Some(pt) => {
+            let x = (pt.x - window_width / 2).max(0).min(width - window_width);
+            let y = (pt.y - window_height / 2).max(0).min(height - window_height);
+            pt!(x, y)
+        },
+        None => pt!((width - window_width) / 2, (height - window_height) / 3),
+    }
+}
+
+// Guessing the levels requires at least two presets, the other buttons are always enabled.
+fn button_disabled(event: &Event, presets: &[LightPreset]) -> bool {
+    match *event {
+        Event::Guess => !can_guess(presets),
+        _ => false,
+    }
+}
+
+// Whether the event ends the renaming of a preset without a new name.
+fn cancels_renaming(evt: &Event) -> bool {
+    match *evt {
+        Event::Close(ViewId::RenamePreset) | Event::Focus(None) => true,
+        _ => false,
+    }
+}
+
+fn intensity_markers(presets: &[LightPreset], gamma: f32) -> Vec<f32> {
+    presets.iter().map(|p| position_from_intensity(p.frontlight_levels.intensity, gamma)).collect()
+}
+
+// Each slider sits between its minus and plus icons, and is followed by the label showing its value.
+fn slider_index(id: SliderId) -> usize {
+    match id {
+        SliderId::LightIntensity if CURRENT_DEVICE.has_natural_light() => 5,
+        SliderId::LightWarmth => 10,
+        _ => 3,
+    }
+}
+
+// The intensity change triggered by the given hardware key, if any.
+fn key_delta(code: ButtonCode, keys: &FrontlightKeys) -> Option<f32> {
+    if let ButtonCode::Raw(raw) = code {
+        if keys.increase.contains(&raw) {
+            return Some(keys.step);
+        } else if keys.decrease.contains(&raw) {
+            return Some(-keys.step);
+        }
+    }
+    None
+}
+
+fn stepped_intensity(intensity: f32, delta: f32, min_intensity: f32) -> f32 {
+    let value = (intensity + delta).max(0.0).min(100.0);
+    // Stepping down from the floor turns the light off.
+    if value < min_intensity && delta < 0.0 && intensity <= min_intensity {
+        0.0
+    } else {
+        floored_intensity(value, min_intensity)
+    }
+}
+
+fn export_preset(light_preset: &LightPreset) -> Result<()> {
+    fs::create_dir_all(PRESETS_DIRNAME)?;
+    save_json(light_preset, Path::new(PRESETS_DIRNAME).join(light_preset.file_name()))
+}
+
+fn export_presets(light_presets: &[LightPreset]) -> Result<()> {
+    fs::create_dir_all(PRESETS_DIRNAME)?;
+    let shared: Vec<LightPreset> = light_presets.iter().map(|lp| lp.shared(false)).collect();
+    save_json(&shared, Path::new(PRESETS_DIRNAME).join(PRESETS_FILENAME))
+}
+
+// Reads the presets files found in the presets directory, other files are ignored.
+fn import_presets() -> Result<Vec<LightPreset>> {
+    let mut light_presets = Vec::new();
+    for entry in fs::read_dir(PRESETS_DIRNAME)? {
+        let path = entry?.path();
+        if !path.file_name().and_then(|name| name.to_str()).map_or(false, is_preset_file_name) {
+            continue;
+        }
+        if path.file_name().map_or(false, |name| name == PRESETS_FILENAME) {
+            match load_json::<Vec<LightPreset>, _>(&path) {
+                Ok(imported) => light_presets.extend(imported.iter().map(|lp| lp.sanitized())),
+                Err(e) => eprintln!("Can't load presets from {}: {}", path.display(), e),
+            }
+            continue;
+        }
+        match load_json::<LightPreset, _>(&path) {
+            Ok(light_preset) => light_presets.push(light_preset.sanitized()),
+            Err(e) => eprintln!("Can't load preset from {}: {}", path.display(), e),
+        }
+    }
+    Ok(light_presets)
+}
+
+// A missing sensor is expected on some firmwares and isn't worth reporting.
+fn read_lightsensor(context: &mut Context) -> Option<u16> {
+    match context.lightsensor.level() {
+        Ok(level) => Some(calibrated_level(level, &context.settings.lightsensor_calibration)),
+        Err(Error(ErrorKind::SensorNotFound(..), _)) => None,
+        Err(e) => {
+            eprintln!("Can't read light sensor: {}", e);
+            None
+        },
+    }
+}
+
 impl View for FrontlightWindow {
     fn handle_event(&mut self, evt: &Event, hub: &Hub, _bus: &mut Bus, context: &mut Context) -> bool {
+        if let Event::Slider(id, value, _) = *evt {
+            let level = self.level(id, value);
+            self.update_value_label(id, level, hub);
+        }
+
         match *evt {
+            Event::Slider(_, _, FingerStatus::Down) if self.fade.is_some() => {
+                self.finish_fade(hub, context);
+                false
+            },
             Event::Slider(SliderId::LightIntensity, value, FingerStatus::Up) => {
-                context.frontlight.set_intensity(value);
+                let intensity = floored_intensity(self.level(SliderId::LightIntensity, value),
+                                                  context.settings.frontlight_min_intensity);
+                context.frontlight.set_intensity(intensity);
+                self.follow_ratio(SliderId::LightIntensity, intensity, hub, context);
                 true
             },
             Event::Slider(SliderId::LightWarmth, value, FingerStatus::Up) => {
                 context.frontlight.set_warmth(value);
+                self.forget_cooled_warmth(hub, context);
+                self.follow_ratio(SliderId::LightWarmth, value, hub, context);
+                true
+            },
+            // The ambient brightness is shown next to the title.
+            Event::LightSensorReading(raw, _) => {
+                if let Some(title) = self.child_mut(TITLE_INDEX).downcast_mut::<Label>() {
+                    title.update(format!("{} · {:.0} lx", LABEL_TITLE, to_lux(raw)), hub);
+                }
                 true
             },
+            Event::ToggleLevelsLock => {
+                self.toggle_levels_lock(hub, context);
+                true
+            },
+            Event::Device(DeviceEvent::Button { code, status, .. }) => {
+                if let Some(delta) = key_delta(code, &context.settings.frontlight_keys) {
+                    match status {
+                        ButtonStatus::Pressed => self.step_intensity(delta, hub, context),
+                        ButtonStatus::Released => self.stop_repeat(),
+                    }
+                    true
+                } else {
+                    false
+                }
+            },
+            Event::Gesture(GestureEvent::HoldButton(code)) if key_delta(code, &context.settings.frontlight_keys).is_some() => {
+                self.start_repeat(Event::KeyRepeat(code), hub);
+                true
+            },
+            Event::KeyRepeat(code) => {
+                if self.repeat.is_some() {
+                    if let Some(delta) = key_delta(code, &context.settings.frontlight_keys) {
+                        self.step_intensity(delta, hub, context);
+                    }
+                }
+                true
+            },
+            Event::Nudge(id, delta) => {
+                // Repeated nudges stop once the icon is released.
+                if self.repeat.is_some() && !self.children.iter().any(|c| c.downcast_ref::<Icon>().map_or(false, |i| i.is_active())) {
+                    self.stop_repeat();
+                } else {
+                    self.finish_fade(hub, context);
+                    self.nudge(id, delta, hub);
+                }
+                true
+            },
+            Event::HoldNudge(id, delta) => {
+                self.finish_fade(hub, context);
+                self.nudge(id, delta, hub);
+                self.start_repeat(Event::Nudge(id, delta), hub);
+                true
+            },
+            Event::FadeTick => {
+                if let Some(frontlight_levels) = self.fade.as_mut().and_then(|fade| fade.next()) {
+                    self.set_frontlight_levels(&frontlight_levels, hub, context);
+                }
+                let progress = self.fade.as_ref().map(|fade| fade.progress());
+                match progress {
+                    Some(p) if p < 1.0 => {
+                        if !context.settings.reduce_motion {
+                            self.show_fade_progress(Some(p), hub);
+                        }
+                    },
+                    Some(_) => {
+                        self.cancel_fade(hub);
+                    },
+                    None => (),
+                }
+                true
+            },
+            Event::ToggleWarmth => {
+                self.finish_fade(hub, context);
+                self.toggle_warmth(hub, context);
+                true
+            },
+            // The keyboard used to rename a preset lies outside of the window.
+            Event::Gesture(..) if self.renaming.is_some() => false,
+            _ if cancels_renaming(evt) => {
+                self.renaming = None;
+                false
+            },
             Event::Gesture(GestureEvent::Tap(ref center)) if !self.rect.includes(center) => {
                 hub.send(Event::Close(ViewId::Frontlight)).unwrap();
                 true
             },
+            Event::Gesture(GestureEvent::Swipe { dir, ref start, .. }) if self.rect.includes(start) &&
+                                                                          (dir == Dir::North || dir == Dir::South) => {
+                let order = context.settings.frontlight_presets_cycle;
+                if let Some(index) = cycle_preset_index(self.current_preset, &context.settings.frontlight_presets, order) {
+                    hub.send(Event::LoadPreset(index)).unwrap();
+                }
+                true
+            },
             Event::Gesture(..) => true,
             Event::Save => {
-                let lightsensor_level = if CURRENT_DEVICE.has_lightsensor() {
-                    context.lightsensor.level().ok()
-                } else {
-                    None
-                };
+                self.finish_fade(hub, context);
+                let lightsensor_level = read_lightsensor(context);
                 let light_preset = LightPreset {
                     lightsensor_level,
                     frontlight_levels: context.frontlight.levels(),
-                    .. Default::default()
+                    .. LightPreset::at(&(context.clock)())
                 };
-                context.settings.frontlight_presets.push(light_preset);
-                context.settings.frontlight_presets.sort_by(|a, b| a.timestamp.cmp(&b.timestamp));
-                if context.settings.frontlight_presets.len() == 1 {
-                    self.toggle_presets(true, hub, context);
+                if context.settings.frontlight_presets_sorted {
+                    insert_preset(&mut context.settings.frontlight_presets, light_preset);
                 } else {
-                    if context.settings.frontlight_presets.len() == 2 {
-                        let index = self.len() - 2;
-                        if let Some(button_guess) = self.child_mut(index).downcast_mut::<Button>() {
-                            button_guess.disabled = false;
-                            hub.send(Event::Render(*button_guess.rect(), UpdateMode::Gui)).unwrap();
-                        }
-                    }
-                    self.update_presets(hub, context);
+                    context.settings.frontlight_presets.push(light_preset);
                 }
+                self.current_preset = None;
+                self.update_guess_button(hub, context);
+                self.resize_presets(hub, context);
                 true
             },
             Event::Select(EntryId::RemovePreset(index)) => {
-                if index < context.settings.frontlight_presets.len() {
-                    context.settings.frontlight_presets.remove(index);
-                    if context.settings.frontlight_presets.is_empty() {
-                        self.toggle_presets(false, hub, context);
+                if !context.settings.confirm_preset_removal {
+                    self.remove_preset(index, hub, context);
+                } else if let Some(light_preset) = context.settings.frontlight_presets.get(index) {
+                    self.removing = Some(light_preset.clone());
+                    hub.send(Event::Show(ViewId::ConfirmRemovePreset)).unwrap();
+                }
+                true
+            },
+            Event::RemovePreset => {
+                // The presets might have changed while the confirmation was shown.
+                if let Some(index) = self.removing.take().and_then(|light_preset| {
+                    context.settings.frontlight_presets.iter().position(|lp| *lp == light_preset)
+                }) {
+                    self.remove_preset(index, hub, context);
+                }
+                true
+            },
+            Event::MovePreset(from, to) => {
+                move_preset(&mut context.settings.frontlight_presets, from, to);
+                context.settings.frontlight_presets_sorted = false;
+                self.current_preset = None;
+                self.resize_presets(hub, context);
+                true
+            },
+            Event::Select(EntryId::DuplicatePreset(index)) => {
+                if let Some(light_preset) = context.settings.frontlight_presets.get(index).cloned() {
+                    let light_preset = LightPreset {
+                        timestamp: LightPreset::at(&(context.clock)()).timestamp,
+                        .. light_preset
+                    };
+                    if context.settings.frontlight_presets_sorted {
+                        insert_preset(&mut context.settings.frontlight_presets, light_preset);
                     } else {
-                        if context.settings.frontlight_presets.len() == 1 {
-                            let index = self.len() - 2;
-                            if let Some(button_guess) = self.child_mut(index).downcast_mut::<Button>() {
-                                button_guess.disabled = true;
-                                hub.send(Event::Render(*button_guess.rect(), UpdateMode::Gui)).unwrap();
-                            }
-                        }
-                        self.update_presets(hub, context);
+                        context.settings.frontlight_presets.insert(index + 1, light_preset);
                     }
+                    self.current_preset = None;
+                    self.update_guess_button(hub, context);
+                    self.resize_presets(hub, context);
+                }
+                true
+            },
+            Event::Select(EntryId::RenamePreset(index)) => {
+                if index < context.settings.frontlight_presets.len() {
+                    self.renaming = Some(index);
+                    hub.send(Event::Show(ViewId::RenamePreset)).unwrap();
+                }
+                true
+            },
+            Event::Submit(ViewId::RenamePresetInput, ref text) => {
+                if let Some(light_preset) = self.renaming.take()
+                                                .and_then(|index| context.settings.frontlight_presets.get_mut(index)) {
+                    let name = text.trim();
+                    light_preset.name = if name.is_empty() { None } else { Some(name.to_string()) };
+                }
+                hub.send(Event::Focus(None)).unwrap();
+                self.resize_presets(hub, context);
+                true
+            },
+            Event::Select(EntryId::ExportPreset(index, with_lightsensor_level)) => {
+                if let Some(light_preset) = context.settings.frontlight_presets.get(index) {
+                    export_preset(&light_preset.shared(with_lightsensor_level))
+                        .map_err(|e| eprintln!("Can't export preset: {}", e)).ok();
+                }
+                true
+            },
+            Event::Select(EntryId::ToggleNightWarmth(index)) => {
+                if let Some(light_preset) = context.settings.frontlight_presets.get_mut(index) {
+                    light_preset.warmth_at_night = !light_preset.warmth_at_night;
+                }
+                true
+            },
+            Event::Select(EntryId::SortPresets(sort)) => {
+                if sort != self.presets_sort {
+                    self.presets_sort = sort;
+                    if let Some(presets_list) = self.children.last_mut().and_then(|c| c.downcast_mut::<PresetsList>()) {
+                        presets_list.set_sort(sort);
+                    }
+                    self.resize_presets(hub, context);
+                }
+                true
+            },
+            Event::Select(EntryId::ExportPresets) => {
+                export_presets(&context.settings.frontlight_presets)
+                    .map_err(|e| eprintln!("Can't export presets: {}", e)).ok();
+                true
+            },
+            Event::Select(EntryId::ImportPresets(replace)) => {
+                // Never replace the presets with nothing.
+                let imported = match import_presets() {
+                    Ok(ref imported) if imported.is_empty() => return true,
+                    Ok(imported) => imported,
+                    Err(e) => {
+                        eprintln!("Can't import presets: {}", e);
+                        return true;
+                    },
+                };
+                if replace {
+                    context.settings.frontlight_presets.clear();
+                } else if merge_presets(&mut context.settings.frontlight_presets.clone(), imported.clone()) > 0 {
+                    self.importing = Some(imported);
+                    hub.send(Event::Show(ViewId::ConfirmSkipDuplicates)).unwrap();
+                    return true;
+                }
+                self.add_presets(imported, true, hub, context);
+                true
+            },
+            Event::SkipDuplicatePresets(skip) => {
+                if let Some(imported) = self.importing.take() {
+                    self.add_presets(imported, skip, hub, context);
                 }
                 true
             },
             Event::LoadPreset(index) => {
-                let frontlight_levels = context.settings.frontlight_presets[index].frontlight_levels;
-                self.set_frontlight_levels(&frontlight_levels, hub, context);
+                let time = (context.clock)();
+                let current_warmth = context.frontlight.levels().warmth;
+                let frontlight_levels = context.settings.frontlight_presets[index]
+                                               .levels_at(&time, &context.settings.night_hours, current_warmth);
+                // A new command during a fade jumps straight to its target.
+                if self.cancel_fade(hub).is_some() {
+                    self.current_preset = Some(index);
+                    self.set_frontlight_levels(&frontlight_levels, hub, context);
+                } else {
+                    self.current_preset = Some(index);
+                    let duration = Duration::from_millis(context.settings.frontlight_fade.duration);
+                    self.fade_to(&frontlight_levels, duration, hub, context);
+                }
+                true
+            },
+            Event::ResetFrontlight => {
+                self.cancel_fade(hub);
+                self.set_frontlight_levels(&DEFAULT_LEVELS, hub, context);
                 true
             },
             Event::Guess => {
-                let lightsensor_level = if CURRENT_DEVICE.has_lightsensor() {
-                    context.lightsensor.level().ok()
-                } else {
-                    None
-                };
+                self.cancel_fade(hub);
+                let lightsensor_level = read_lightsensor(context).map(|level| {
+                    steady_lightsensor_level(level, context.lightsensor_level, context.settings.lightsensor_hysteresis)
+                });
+                context.lightsensor_level = lightsensor_level;
                 if let Some(ref frontlight_levels) = guess_frontlight(lightsensor_level, &context.settings.frontlight_presets) {
                     self.set_frontlight_levels(frontlight_levels, hub, context);
                 }
@@ -320,7 +1039,8 @@ impl View for FrontlightWindow {
         fb.draw_rounded_rectangle_with_border(&self.rect,
                                               &CornerSpec::Uniform(border_radius),
                                               &BorderSpec { thickness: border_thickness,
-                                                            color: BLACK },
+                                                            color: BLACK,
+                                                            style: BorderStyle::Solid },
                                               &WHITE);
     }
 
@@ -328,6 +1048,10 @@ impl View for FrontlightWindow {
         true
     }
 
+    fn id(&self) -> Option<ViewId> {
+        Some(ViewId::Frontlight)
+    }
+
     fn rect(&self) -> &Rectangle {
         &self.rect
     }
@@ -344,3 +1068,119 @@ impl View for FrontlightWindow {
         &mut self.children
     }
 }
+
+impl Drop for FrontlightWindow {
+    fn drop(&mut self) {
+        self.stop_repeat();
+        // Stop the ticks of a fade that wasn't finished by close.
+        if let Some(fade) = self.fade.take() {
+            fade.cancel();
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::mpsc;
+    use std::collections::VecDeque;
+    use settings::FrontlightKeys;
+    use input::ButtonCode;
+    use chrono::{Local, TimeZone, DateTime, NaiveTime};
+    use view::{View, Event, EntryId, ViewId};
+    use view::button::Button;
+    use settings::{LightPreset, insert_preset};
+    use app::test_context;
+    use super::{FrontlightWindow, window_origin, key_delta, stepped_intensity, cancels_renaming, button_disabled};
+
+    #[test]
+    fn hardware_keys_step_intensity() {
+        let keys = FrontlightKeys { increase: vec![115], decrease: vec![114], step: 5.0 };
+        assert_eq!(key_delta(ButtonCode::Raw(115), &keys), Some(5.0));
+        assert_eq!(key_delta(ButtonCode::Raw(114), &keys), Some(-5.0));
+        assert_eq!(key_delta(ButtonCode::Raw(90), &keys), None);
+        assert_eq!(key_delta(ButtonCode::Home, &keys), None);
+        assert_eq!(stepped_intensity(50.0, 5.0, 0.0), 55.0);
+        assert_eq!(stepped_intensity(98.0, 5.0, 0.0), 100.0);
+        assert_eq!(stepped_intensity(3.0, -5.0, 0.0), 0.0);
+        assert_eq!(stepped_intensity(12.0, -5.0, 10.0), 10.0);
+        assert_eq!(stepped_intensity(10.0, -5.0, 10.0), 0.0);
+        assert_eq!(stepped_intensity(0.0, 5.0, 10.0), 10.0);
+    }
+
+    #[test]
+    fn buttons_follow_presets() {
+        let states = |presets: &[LightPreset]| {
+            [Event::Save, Event::ToggleWarmth, Event::ResetFrontlight, Event::Guess].iter()
+                .map(|evt| button_disabled(evt, presets))
+                .collect::<Vec<bool>>()
+        };
+        let mut lp = Vec::new();
+        assert_eq!(states(&lp), vec![false, false, false, true]);
+        insert_preset(&mut lp, LightPreset::at(&NaiveTime::from_hms(22, 0, 0)));
+        assert_eq!(states(&lp), vec![false, false, false, true]);
+        insert_preset(&mut lp, LightPreset::at(&NaiveTime::from_hms(8, 15, 0)));
+        insert_preset(&mut lp, LightPreset::at(&NaiveTime::from_hms(13, 30, 0)));
+        assert_eq!(states(&lp), vec![false, false, false, false]);
+        lp.remove(0);
+        assert_eq!(states(&lp), vec![false, false, false, false]);
+        lp.remove(0);
+        assert_eq!(states(&lp), vec![false, false, false, true]);
+    }
+
+    #[test]
+    fn saved_presets_enable_guess() {
+        fn evening() -> DateTime<Local> { Local.ymd(2019, 3, 1).and_hms(22, 0, 0) }
+        fn morning() -> DateTime<Local> { Local.ymd(2019, 3, 2).and_hms(8, 15, 0) }
+        fn noon() -> DateTime<Local> { Local.ymd(2019, 3, 2).and_hms(13, 30, 0) }
+        let mut context = test_context();
+        context.settings.confirm_preset_removal = false;
+        let (hub, _rx) = mpsc::channel();
+        let mut bus = VecDeque::new();
+        let mut window = FrontlightWindow::new(&mut context, None);
+        // The guess button is the last one.
+        let guess_disabled = |window: &FrontlightWindow| {
+            window.children().iter().filter_map(|c| c.downcast_ref::<Button>())
+                  .last().map(|b| b.disabled).unwrap()
+        };
+        assert!(guess_disabled(&window));
+        for clock in &[evening, morning, noon] {
+            context.clock = *clock;
+            window.handle_event(&Event::Save, &hub, &mut bus, &mut context);
+        }
+        let timestamps = context.settings.frontlight_presets.iter()
+                                .map(|lp| lp.timestamp).collect::<Vec<u16>>();
+        assert_eq!(timestamps, vec![495, 810, 1320]);
+        assert!(!guess_disabled(&window));
+        window.handle_event(&Event::Select(EntryId::RemovePreset(0)), &hub, &mut bus, &mut context);
+        assert!(!guess_disabled(&window));
+        window.handle_event(&Event::Select(EntryId::RemovePreset(0)), &hub, &mut bus, &mut context);
+        assert!(guess_disabled(&window));
+        assert_eq!(context.settings.frontlight_presets[0].timestamp, 1320);
+    }
+
+    #[test]
+    fn cancel_renaming() {
+        assert!(cancels_renaming(&Event::Close(ViewId::RenamePreset)));
+        assert!(cancels_renaming(&Event::Focus(None)));
+        assert!(!cancels_renaming(&Event::Focus(Some(ViewId::RenamePresetInput))));
+        assert!(!cancels_renaming(&Event::Close(ViewId::Frontlight)));
+    }
+
+    #[test]
+    fn anchored_origin_stays_on_screen() {
+        let screen = (600, 800);
+        let size = (560, 300);
+        assert_eq!(window_origin(size, screen, None), pt!(20, 166));
+        for &(anchor, origin) in &[(pt!(0, 0), pt!(0, 0)),
+                                   (pt!(600, 800), pt!(40, 500)),
+                                   (pt!(0, 400), pt!(0, 250)),
+                                   (pt!(600, 400), pt!(40, 250)),
+                                   (pt!(300, 0), pt!(20, 0)),
+                                   (pt!(300, 800), pt!(20, 500))] {
+            let pt = window_origin(size, screen, Some(anchor));
+            assert_eq!(pt, origin);
+            assert!(pt.x >= 0 && pt.x + size.0 <= screen.0);
+            assert!(pt.y >= 0 && pt.y + size.1 <= screen.1);
+        }
+    }
+}
@@ -5,7 +5,6 @@ use font::{MD_TITLE, MD_AUTHOR, MD_YEAR, MD_KIND, MD_SIZE};
 use color::{TEXT_NORMAL, TEXT_INVERTED_HARD};
 use gesture::GestureEvent;
 use metadata::Info;
-use unit::scale_by_dpi;
 use document::HumanSize;
 use font::{Fonts, font_from_style};
 use geom::{Rectangle, halves};
@@ -136,9 +135,7 @@ impl View for Book {
         {
             let kind = file_info.kind.to_uppercase();
             let font = font_from_style(fonts, &MD_KIND, dpi);
-            let mut plan = font.plan(&kind, None, None);
-            let letter_spacing = scale_by_dpi(3.0, dpi) as u32;
-            plan.space_out(letter_spacing);
+            let plan = font.plan(&kind, None, None);
             let pt = pt!(self.rect.max.x - padding - plan.width as i32,
                          self.rect.min.y + baseline + x_height);
             font.render(fb, scheme[1], &plan, &pt);
@@ -2,7 +2,7 @@ use device::CURRENT_DEVICE;
 use gesture::GestureEvent;
 use font::{Fonts, font_from_style, category_font_size, NORMAL_STYLE};
 use color::{WHITE, BLACK, TEXT_BUMP_SMALL};
-use geom::{Rectangle, CornerSpec, BorderSpec, Dir};
+use geom::{Rectangle, CornerSpec, BorderSpec, BorderStyle, Dir};
 use framebuffer::Framebuffer;
 use view::{View, Event, Hub, Bus, Align};
 use view::{THICKNESS_SMALL, BORDER_RADIUS_SMALL};
@@ -79,7 +79,8 @@ impl View for Category {
             fb.draw_rounded_rectangle_with_border(&bg_rect,
                                                   &CornerSpec::Uniform(border_radius),
                                                   &BorderSpec { thickness: border_thickness,
-                                                                color: BLACK },
+                                                                color: BLACK,
+                                                                style: BorderStyle::Solid },
                                                   &WHITE);
         }
 
@@ -23,7 +23,7 @@ use view::filler::Filler;
 use self::top_bar::TopBar;
 use self::summary::Summary;
 use self::shelf::Shelf;
-use view::common::{shift, locate, locate_by_id, toggle_main_menu};
+use view::common::{shift, locate, locate_by_id, toggle_main_menu, main_menu_entries, topmost_overlay};
 use view::keyboard::{Keyboard, DEFAULT_LAYOUT};
 use view::named_input::NamedInput;
 use view::menu::{Menu, MenuKind};
@@ -613,28 +613,17 @@ impl Home {
             if let Some(false) = enable {
                 return;
             }
-            let entries = vec![EntryKind::RadioButton("Date Opened".to_string(),
-                                                      EntryId::Sort(SortMethod::Opened),
-                                                      self.sort_method == SortMethod::Opened),
-                               EntryKind::RadioButton("Date Added".to_string(),
-                                                      EntryId::Sort(SortMethod::Added),
-                                                      self.sort_method == SortMethod::Added),
-                               EntryKind::RadioButton("Progress".to_string(),
-                                                      EntryId::Sort(SortMethod::Progress),
-                                                      self.sort_method == SortMethod::Progress),
-                               EntryKind::RadioButton("Author".to_string(),
-                                                      EntryId::Sort(SortMethod::Author),
-                                                      self.sort_method == SortMethod::Author),
-                               EntryKind::RadioButton("File Size".to_string(),
-                                                      EntryId::Sort(SortMethod::Size),
-                                                      self.sort_method == SortMethod::Size),
-                               EntryKind::RadioButton("File Type".to_string(),
-                                                      EntryId::Sort(SortMethod::Kind),
-                                                      self.sort_method == SortMethod::Kind),
+            let methods = [(SortMethod::Opened, "Date Opened"), (SortMethod::Added, "Date Added"),
+                           (SortMethod::Progress, "Progress"), (SortMethod::Author, "Author"),
+                           (SortMethod::Size, "File Size"), (SortMethod::Kind, "File Type")];
+            let choices = methods.iter().map(|&(m, text)| (text.to_string(), EntryId::Sort(m))).collect();
+            let selected = methods.iter().position(|&(m, _)| m == self.sort_method).unwrap_or(methods.len());
+            let entries = vec![EntryKind::RadioGroup(choices, selected),
                                EntryKind::Separator,
                                EntryKind::CheckBox("Reverse Order".to_string(),
                                                    EntryId::ReverseOrder, self.reverse_order)];
-            let sort_menu = Menu::new(rect, ViewId::SortMenu, MenuKind::DropDown, entries, fonts);
+            let sort_menu = Menu::new(rect, ViewId::SortMenu, MenuKind::DropDown, entries, fonts)
+                                .auto_close(true);
             hub.send(Event::Render(*sort_menu.rect(), UpdateMode::Gui)).unwrap();
             self.children.push(Box::new(sort_menu) as Box<View>);
         }
@@ -942,8 +931,7 @@ impl Home {
         self.sort(false, &mut context.metadata, &tx);
         self.child_mut(0).downcast_mut::<TopBar>()
             .map(|top_bar| top_bar.update_frontlight_icon(&tx, context));
-        hub.send(Event::ClockTick).unwrap();
-        hub.send(Event::BatteryTick).unwrap();
+        hub.send(Event::RefreshStatus).unwrap();
         hub.send(Event::Render(self.rect, UpdateMode::Gui)).unwrap();
     }
 
@@ -981,7 +969,8 @@ impl View for Home {
         match *evt {
             Event::Focus(v) => {
                 self.focus = v;
-                self.toggle_keyboard(true, true, v, hub, &mut context.fonts);
+                // Inputs that don't belong to the home view drop the focus once they're done.
+                self.toggle_keyboard(v.is_some(), true, v, hub, &mut context.fonts);
                 false // let the event reach every input view
             },
             Event::Show(ViewId::Keyboard) => {
@@ -1005,7 +994,7 @@ impl View for Home {
                 true
             },
             Event::ToggleNear(ViewId::MainMenu, rect) => {
-                toggle_main_menu(self, rect, None, hub, context);
+                toggle_main_menu(self, rect, None, hub, context, main_menu_entries);
                 true
             },
             Event::ToggleNear(ViewId::MatchesMenu, rect) => {
@@ -1025,7 +1014,7 @@ impl View for Home {
                 true
             },
             Event::Close(ViewId::MainMenu) => {
-                toggle_main_menu(self, Rectangle::default(), Some(false), hub, context);
+                toggle_main_menu(self, Rectangle::default(), Some(false), hub, context, main_menu_entries);
                 true
             },
             Event::Close(ViewId::GoToPage) => {
@@ -1130,6 +1119,15 @@ impl View for Home {
                 }
                 true
             },
+            // The swipes don't act on the views beneath an overlay.
+            Event::Page(..) | Event::SummaryPage(..) |
+            Event::ResizeSummary(..) | Event::ToggleNegateCategory(..) if topmost_overlay(self).is_some() => true,
+            Event::SummaryPage(dir) => {
+                let summary = self.child_mut(2).downcast_mut::<Summary>().unwrap();
+                summary.set_current_page(dir);
+                hub.send(Event::Render(*summary.rect(), UpdateMode::Gui)).unwrap();
+                true
+            },
             Event::ResizeSummary(delta_y) => {
                 self.resize_summary(delta_y, true, hub, &mut context.fonts);
                 true
@@ -1169,6 +1167,12 @@ impl View for Home {
                 self.reseed(false, hub, context);
                 true
             },
+            // The icon only reflects the toggles made by holding it.
+            Event::Select(EntryId::ToggleFrontlight) => {
+                self.child_mut(0).downcast_mut::<TopBar>()
+                    .map(|top_bar| top_bar.update_frontlight_icon(hub, context));
+                true
+            },
             _ => false,
         }
     }
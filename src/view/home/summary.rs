@@ -376,13 +376,11 @@ impl View for Summary {
             Event::Gesture(GestureEvent::Swipe { dir, ref start, ref end, .. }) if self.rect.includes(start) => {
                 match dir {
                     Dir::West => {
-                        self.set_current_page(CycleDir::Next);
-                        hub.send(Event::Render(self.rect, UpdateMode::Gui)).unwrap();
+                        bus.push_back(Event::SummaryPage(CycleDir::Next));
                         true
                     },
                     Dir::East => {
-                        self.set_current_page(CycleDir::Previous);
-                        hub.send(Event::Render(self.rect, UpdateMode::Gui)).unwrap();
+                        bus.push_back(Event::SummaryPage(CycleDir::Previous));
                         true
                     },
                     Dir::South if !self.rect.includes(end) => {
@@ -46,10 +46,12 @@ impl TopBar {
         children.push(Box::new(battery_widget) as Box<View>);
 
         let name = if context.settings.frontlight { "frontlight" } else { "frontlight-disabled" };
+        let frontlight_rect = rect![rect.max - pt!(2*side, side),
+                                    rect.max - pt!(side, 0)];
+        // The window is placed near the icon when it's anchored.
         let frontlight_icon = Icon::new(name,
-                                        rect![rect.max - pt!(2*side, side),
-                                              rect.max - pt!(side, 0)],
-                                        Event::Show(ViewId::Frontlight));
+                                        frontlight_rect,
+                                        Event::ShowNear(ViewId::Frontlight, frontlight_rect.center()));
         children.push(Box::new(frontlight_icon) as Box<View>);
 
         let menu_rect = rect![rect.max-side, rect.max];
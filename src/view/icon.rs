@@ -25,7 +25,8 @@ lazy_static! {
                      "delete-backward", "delete-forward", "move-backward", "move-forward",
                      "close",  "check_mark-small", "check_mark","check_mark-large",
                      "bullet", "arrow-left", "arrow-right", "double_angle-left", "double_angle-right",
-                     "angle-down", "plus", "minus", "crop", "toc", "font_size", "plug"].iter().cloned() {
+                     "angle-down", "plus", "minus", "crop", "toc", "font_size", "plug",
+                     "lock", "lock-open"].iter().cloned() {
             let path = dir.join(&format!("{}.svg", name));
             let doc = PdfOpener::new().and_then(|o| o.open(path)).unwrap();
             let pixmap = doc.page(0).and_then(|p| p.pixmap(scale)).unwrap();
@@ -74,6 +75,10 @@ impl Icon {
         self.corners = corners;
         self
     }
+
+    pub fn is_active(&self) -> bool {
+        self.active
+    }
 }
 
 impl View for Icon {
@@ -101,7 +106,8 @@ impl View for Icon {
             Event::Gesture(GestureEvent::HoldFinger(ref center)) if self.rect.includes(center) => {
                 match self.event {
                     Event::Page(dir) => bus.push_back(Event::Chapter(dir)),
-                    Event::Show(ViewId::Frontlight) => {
+                    Event::Nudge(id, delta) => bus.push_back(Event::HoldNudge(id, delta)),
+                    Event::Show(ViewId::Frontlight) | Event::ShowNear(ViewId::Frontlight, _) => {
                         self.name = if context.settings.frontlight {
                             "frontlight-disabled".to_string()
                         } else {
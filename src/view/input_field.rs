@@ -3,8 +3,8 @@ use framebuffer::{Framebuffer, UpdateMode};
 use view::{View, Event, Hub, Bus, KeyboardEvent, ViewId, TextKind};
 use view::THICKNESS_MEDIUM;
 use gesture::GestureEvent;
-use font::{Fonts, font_from_style, NORMAL_STYLE, FONT_SIZES};
-use geom::{Rectangle, LinearDir, BorderSpec, halves};
+use font::{Fonts, font_from_style, NORMAL_STYLE, FONT_SIZES, NO_LIGATURES};
+use geom::{Rectangle, LinearDir, BorderSpec, BorderStyle, halves};
 use color::{TEXT_NORMAL, BLACK};
 use app::Context;
 use unit::scale_by_dpi;
@@ -233,14 +233,16 @@ impl View for InputField {
 
         if self.border {
             fb.draw_rectangle_outline(&self.rect,
-                                      &BorderSpec { thickness: thickness as u16, color: BLACK });
+                                      &BorderSpec { thickness: thickness as u16,
+                                                    color: BLACK,
+                                                    style: BorderStyle::Solid });
         }
 
         let (mut plan, foreground) = if self.text.is_empty() {
             (font.plan(&self.placeholder, Some(max_width as u32), None),
              TEXT_NORMAL[2])
         } else {
-            (font.plan(&self.text, None, Some("-liga")),
+            (font.plan(&self.text, None, Some(NO_LIGATURES)),
             TEXT_NORMAL[1])
         };
 
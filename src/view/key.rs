@@ -159,9 +159,7 @@ impl View for Key {
             },
             KeyLabel::Text(label) => {
                 let font = font_from_style(fonts, &KBD_LABEL, dpi);
-                let mut plan = font.plan(label, None, None);
-                let letter_spacing = scale_by_dpi(4.0, dpi) as u32;
-                plan.space_out(letter_spacing);
+                let plan = font.plan(label, None, None);
                 let dx = (key_rect.width() - plan.width) as i32 / 2;
                 let dy = (key_rect.height() - font.x_heights.1) as i32 / 2;
                 let pt = pt!(key_rect.min.x + dx, key_rect.max.y - dy);
@@ -1,9 +1,16 @@
+use std::mem;
+use std::thread;
+use std::sync::Arc;
+use std::time::Duration;
 use device::CURRENT_DEVICE;
-use font::{Fonts, font_from_style, NORMAL_STYLE};
-use view::{View, Event, Hub, Bus, Align};
+use font::{Fonts, Font, Hyphenator, RenderPlan, Style, font_from_style, is_rtl, NORMAL_STYLE, FONT_SIZES};
+use view::{View, Event, Hub, Bus, Align, VAlign};
+use view::button::shrunk_size;
 use framebuffer::{Framebuffer, UpdateMode};
+use input::{DeviceEvent, FingerStatus};
+use gesture::GestureEvent;
 use geom::Rectangle;
-use color::TEXT_NORMAL;
+use color::{TEXT_NORMAL, TEXT_INVERTED_HARD};
 use app::Context;
 
 pub struct Label {
@@ -11,46 +18,369 @@ pub struct Label {
     children: Vec<Box<View>>,
     text: String,
     align: Align,
+    valign: VAlign,
+    style: &'static Style,
+    // The background, foreground and disabled colors.
+    scheme: [u8; 3],
+    wrap: bool,
+    // Whether the wrapped lines, but the last, span the width of the label.
+    justify: bool,
+    // Whether the font shrinks, down to the smallest size, before the text is truncated.
+    auto_shrink: bool,
+    truncation: Truncation,
+    // Whether the text is written from right to left, as a whole.
+    rtl: bool,
+    marquee: Option<Marquee>,
+    // Sent when the label is tapped.
+    event: Option<Event>,
+    active: bool,
+}
+
+const MARQUEE_INTERVAL: Duration = Duration::from_millis(400);
+// The number of ticks spent at each end of the text.
+const MARQUEE_PAUSE: u8 = 4;
+
+// Scrolls the text of a label that's too wide, one glyph at a time.
+struct Marquee {
+    offset: usize,
+    pause: u8,
+    // The ticker thread stops once this is dropped.
+    ticker: Option<Arc<()>>,
+}
+
+impl Marquee {
+    fn new() -> Marquee {
+        Marquee {
+            offset: 0,
+            pause: MARQUEE_PAUSE,
+            ticker: None,
+        }
+    }
+
+    // Returns whether the offset changed.
+    fn advance(&mut self, max_offset: usize) -> bool {
+        if self.pause > 0 {
+            self.pause -= 1;
+            return false;
+        }
+        self.offset = if self.offset >= max_offset { 0 } else { self.offset + 1 };
+        if self.offset == 0 || self.offset == max_offset {
+            self.pause = MARQUEE_PAUSE;
+        }
+        true
+    }
+}
+
+// How text wider than the label is shortened.
+#[derive(Debug, Copy, Clone, Eq, PartialEq)]
+pub enum Truncation {
+    Clip,
+    Ellipsis,
+    // Useful for file names, whose endings often tell them apart.
+    Middle,
+}
+
+impl Truncation {
+    // The beginning of right-to-left text is kept on the right.
+    fn plan(&self, font: &mut Font, text: &str, max_width: u32, rtl: bool) -> RenderPlan {
+        let mut plan = if rtl {
+            font.plan_rtl(text, None, None)
+        } else {
+            font.plan(text, None, None)
+        };
+        match (*self, rtl) {
+            (Truncation::Clip, false) => font.clip_right(&mut plan, max_width),
+            (Truncation::Clip, true) => font.clip_left(&mut plan, max_width),
+            (Truncation::Ellipsis, false) => font.crop_right(&mut plan, max_width),
+            (Truncation::Ellipsis, true) => font.crop_left(&mut plan, max_width),
+            (Truncation::Middle, _) => font.crop_middle(&mut plan, max_width),
+        }
+        plan
+    }
+}
+
+// Breaks *text* into lines no wider than *max_width*, at spaces.
+// When a word doesn't fit, its longest start that fits with a hyphen ends the line,
+// provided a *hyphenator* is given.
+// A word wider than *max_width* that can't be hyphenated gets a line of its own.
+pub fn wrap_lines<F>(text: &str, max_width: u32, hyphenator: Option<&Hyphenator>, mut width_of: F) -> Vec<String> where F: FnMut(&str) -> u32 {
+    let join = |line: &str, word: &str| {
+        if line.is_empty() {
+            word.to_string()
+        } else {
+            format!("{} {}", line, word)
+        }
+    };
+    let mut lines = Vec::new();
+    let mut line = String::new();
+    for word in text.split_whitespace() {
+        // The break points are found within the whole word, the remaining ones
+        // follow *start*, the beginning of what's left of the word.
+        let break_points = hyphenator.map_or_else(Vec::new, |h| h.break_points(word));
+        let mut start = 0;
+        loop {
+            let candidate = join(&line, &word[start..]);
+            if width_of(&candidate) <= max_width {
+                line = candidate;
+                break;
+            }
+            let head = break_points.iter().rev().filter(|&&i| i > start)
+                                   .map(|&i| (join(&line, &format!("{}-", &word[start..i])), i))
+                                   .find(|&(ref candidate, _)| width_of(candidate) <= max_width);
+            match head {
+                Some((candidate, index)) => {
+                    lines.push(candidate);
+                    line.clear();
+                    start = index;
+                },
+                None if line.is_empty() => {
+                    line = word[start..].to_string();
+                    break;
+                },
+                None => lines.push(mem::replace(&mut line, String::new())),
+            }
+        }
+    }
+    if !line.is_empty() || lines.is_empty() {
+        lines.push(line);
+    }
+    lines
 }
 
 impl Label {
     pub fn new(rect: Rectangle, text: String, align: Align) -> Label {
+        let rtl = is_rtl(&text);
         Label {
             rect,
             children: vec![],
             text,
             align,
+            valign: VAlign::Center,
+            style: &NORMAL_STYLE,
+            scheme: TEXT_NORMAL,
+            wrap: false,
+            justify: false,
+            auto_shrink: false,
+            truncation: Truncation::Ellipsis,
+            rtl,
+            marquee: None,
+            event: None,
+            active: false,
+        }
+    }
+
+    pub fn event(mut self, event: Option<Event>) -> Label {
+        self.event = event;
+        self
+    }
+
+    // Scrolls the text back and forth when it doesn't fit.
+    pub fn start_marquee(&mut self, hub: &Hub, fonts: &mut Fonts) {
+        self.marquee = Some(Marquee::new());
+        if self.max_offset(fonts) > 0 {
+            self.start_ticker(hub);
         }
     }
 
+    fn start_ticker(&mut self, hub: &Hub) {
+        if let Some(ref mut marquee) = self.marquee {
+            let ticker = Arc::new(());
+            let weak = Arc::downgrade(&ticker);
+            let hub2 = hub.clone();
+            let rect = self.rect;
+            thread::spawn(move || {
+                loop {
+                    thread::sleep(MARQUEE_INTERVAL);
+                    if weak.upgrade().is_none() || hub2.send(Event::MarqueeTick(rect)).is_err() {
+                        break;
+                    }
+                }
+            });
+            marquee.ticker = Some(ticker);
+        }
+    }
+
+    fn max_offset(&self, fonts: &mut Fonts) -> usize {
+        let dpi = CURRENT_DEVICE.dpi;
+        let font = font_from_style(fonts, self.style, dpi);
+        let max_width = (self.rect.width() as i32 - font.em() as i32) as u32;
+        font.plan(&self.text, None, None).overflow(max_width)
+    }
+
+    // The visible part of the scrolled text.
+    fn scrolled_plan(&self, font: &mut Font, offset: usize, max_width: u32) -> RenderPlan {
+        if self.rtl {
+            let mut plan = font.plan_rtl(&self.text, None, None);
+            plan.skip_right(offset);
+            font.clip_left(&mut plan, max_width);
+            plan
+        } else {
+            let mut plan = font.plan(&self.text, None, None);
+            plan.skip_left(offset);
+            font.clip_right(&mut plan, max_width);
+            plan
+        }
+    }
+
+    pub fn valign(mut self, valign: VAlign) -> Label {
+        self.valign = valign;
+        self
+    }
+
+    pub fn style(mut self, style: &'static Style) -> Label {
+        self.style = style;
+        self
+    }
+
+    pub fn scheme(mut self, scheme: [u8; 3]) -> Label {
+        self.scheme = scheme;
+        self
+    }
+
+    pub fn truncation(mut self, truncation: Truncation) -> Label {
+        self.truncation = truncation;
+        self
+    }
+
+    pub fn auto_shrink(mut self, auto_shrink: bool) -> Label {
+        self.auto_shrink = auto_shrink;
+        self
+    }
+
+    // Breaks the text into as many lines as the height allows.
+    pub fn wrap(mut self, wrap: bool) -> Label {
+        self.wrap = wrap;
+        self
+    }
+
+    pub fn justify(mut self, justify: bool) -> Label {
+        self.justify = justify;
+        self
+    }
+
     pub fn update(&mut self, text: String, hub: &Hub) {
+        self.rtl = is_rtl(&text);
         self.text = text;
+        if let Some(ref mut marquee) = self.marquee {
+            marquee.offset = 0;
+            marquee.pause = MARQUEE_PAUSE;
+        }
+        // The next tick stops the ticker if the new text fits.
+        if self.marquee.as_ref().map_or(false, |m| m.ticker.is_none()) {
+            self.start_ticker(hub);
+        }
         hub.send(Event::Render(self.rect, UpdateMode::Gui)).unwrap();
     }
 }
 
 impl View for Label {
-    fn handle_event(&mut self, _evt: &Event, _hub: &Hub, _bus: &mut Bus, _context: &mut Context) -> bool {
-        false
+    fn handle_event(&mut self, evt: &Event, hub: &Hub, bus: &mut Bus, context: &mut Context) -> bool {
+        match *evt {
+            Event::MarqueeTick(rect) if rect == self.rect => {
+                let max_offset = self.max_offset(&mut context.fonts);
+                if let Some(ref mut marquee) = self.marquee {
+                    if max_offset == 0 {
+                        *marquee = Marquee::new();
+                    } else if marquee.advance(max_offset) {
+                        hub.send(Event::Render(self.rect, UpdateMode::Fast)).unwrap();
+                    }
+                }
+                true
+            },
+            Event::Device(DeviceEvent::Finger { status, ref position, .. }) if self.event.is_some() => {
+                match status {
+                    FingerStatus::Down if self.rect.includes(position) => {
+                        self.active = true;
+                        hub.send(Event::Render(self.rect, UpdateMode::Fast)).unwrap();
+                        true
+                    },
+                    FingerStatus::Up if self.active => {
+                        self.active = false;
+                        hub.send(Event::Render(self.rect, UpdateMode::Gui)).unwrap();
+                        true
+                    },
+                    _ => false,
+                }
+            },
+            Event::Gesture(GestureEvent::Tap(ref center)) if self.rect.includes(center) => {
+                if let Some(ref event) = self.event {
+                    bus.push_back(event.clone());
+                    true
+                } else {
+                    false
+                }
+            },
+            _ => false,
+        }
     }
 
     fn render(&self, fb: &mut Framebuffer, fonts: &mut Fonts) {
         let dpi = CURRENT_DEVICE.dpi;
 
-        fb.draw_rectangle(&self.rect, TEXT_NORMAL[0]);
+        let scheme = if self.active {
+            TEXT_INVERTED_HARD
+        } else {
+            self.scheme
+        };
 
-        let font = font_from_style(fonts, &NORMAL_STYLE, dpi);
-        let x_height = font.x_heights.0 as i32;
+        fb.draw_rectangle(&self.rect, scheme[0]);
+
+        let hyphenator = fonts.hyphenator.clone();
+        let font = font_from_style(fonts, self.style, dpi);
         let padding = font.em() as i32;
-        let max_width = self.rect.width() as i32 - padding;
+        let max_width = (self.rect.width() as i32 - padding) as u32;
 
-        let plan = font.plan(&self.text, Some(max_width as u32), None);
+        if self.auto_shrink && !self.wrap {
+            let mut size = self.style.size;
+            let mut width = font.plan(&self.text, None, None).width;
+            while width > max_width && size > FONT_SIZES[0] {
+                size = shrunk_size(size, width, max_width, FONT_SIZES[0]).min(size - 1);
+                font.set_size(size, dpi);
+                width = font.plan(&self.text, None, None).width;
+            }
+        }
+
+        let x_height = font.x_heights.0 as i32;
+        let line_height = 5 * x_height / 2;
 
-        let dx = self.align.offset(plan.width as i32, self.rect.width() as i32);
-        let dy = (self.rect.height() as i32 - x_height) / 2;
-        let pt = pt!(self.rect.min.x + dx, self.rect.max.y - dy);
+        let plans: Vec<_> = if self.wrap {
+            let max_lines = ((self.rect.height() as i32 - x_height) / line_height + 1).max(1) as usize;
+            let mut lines = wrap_lines(&self.text, max_width, hyphenator.as_deref(), |t| font.plan(t, None, None).width);
+            // The overflowing text is ellipsized on the last visible line.
+            if lines.len() > max_lines {
+                let rest = lines[max_lines-1..].join(" ");
+                lines.truncate(max_lines - 1);
+                lines.push(rest);
+            }
+            let mut plans: Vec<_> = lines.iter().map(|line| self.truncation.plan(font, line, max_width, self.rtl)).collect();
+            if self.justify {
+                if let Some((_, init)) = plans.split_last_mut() {
+                    for plan in init {
+                        font.justify(plan, max_width);
+                    }
+                }
+            }
+            plans
+        } else {
+            match self.marquee {
+                Some(Marquee { offset, ticker: Some(_), .. }) => {
+                    vec![self.scrolled_plan(font, offset, max_width)]
+                },
+                _ => vec![self.truncation.plan(font, &self.text, max_width, self.rtl)],
+            }
+        };
+        let align = if self.rtl { self.align.mirrored() } else { self.align.clone() };
 
-        font.render(fb, TEXT_NORMAL[1], &plan, &pt);
+        let last_index = plans.len() as i32 - 1;
+        let text_height = x_height + last_index * line_height;
+        let dy = self.valign.offset(text_height, self.rect.height() as i32);
+
+        for (i, plan) in plans.iter().enumerate() {
+            let dx = align.offset(plan.width as i32, self.rect.width() as i32);
+            let pt = pt!(self.rect.min.x + dx,
+                         self.rect.min.y + dy + x_height + i as i32 * line_height);
+            font.render(fb, scheme[1], plan, &pt);
+        }
     }
 
     fn rect(&self) -> &Rectangle {
@@ -69,3 +399,49 @@ impl View for Label {
         &mut self.children
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::{wrap_lines, Marquee, MARQUEE_PAUSE};
+    use font::Hyphenator;
+
+    #[test]
+    fn wrap_long_text() {
+        let width_of = |t: &str| 10 * t.chars().count() as u32;
+        assert_eq!(wrap_lines("The Name of the Rose", 120, None, width_of),
+                   vec!["The Name of", "the Rose"]);
+        assert_eq!(wrap_lines("Hypnerotomachia Poliphili", 100, None, width_of),
+                   vec!["Hypnerotomachia", "Poliphili"]);
+        assert_eq!(wrap_lines("Ulysses", 100, None, width_of), vec!["Ulysses"]);
+        assert_eq!(wrap_lines("", 100, None, width_of), vec![""]);
+    }
+
+    #[test]
+    fn hyphenate_long_words() {
+        let width_of = |t: &str| 10 * t.chars().count() as u32;
+        let hyphenator = Hyphenator::parse("hy3ph he2n hena4 hen5at 1na n2at 1tio 2io o2n");
+        assert_eq!(wrap_lines("On hyphenation", 100, Some(&hyphenator), width_of),
+                   vec!["On hyphen-", "ation"]);
+        assert_eq!(wrap_lines("hyphenation", 50, Some(&hyphenator), width_of),
+                   vec!["hy-", "phen-", "ation"]);
+        assert_eq!(wrap_lines("On hyphenation", 100, None, width_of),
+                   vec!["On", "hyphenation"]);
+    }
+
+    #[test]
+    fn marquee_pauses_at_both_ends() {
+        let mut marquee = Marquee::new();
+        for _ in 0..MARQUEE_PAUSE {
+            assert!(!marquee.advance(2));
+        }
+        assert!(marquee.advance(2));
+        assert!(marquee.advance(2));
+        assert_eq!(marquee.offset, 2);
+        for _ in 0..MARQUEE_PAUSE {
+            assert!(!marquee.advance(2));
+        }
+        assert!(marquee.advance(2));
+        assert_eq!(marquee.offset, 0);
+        assert!(!marquee.advance(2));
+    }
+}
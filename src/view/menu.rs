@@ -1,15 +1,16 @@
 use std::thread;
 use device::{CURRENT_DEVICE, BAR_SIZES};
 use font::{Fonts, font_from_style, NORMAL_STYLE};
-use geom::{Point, Rectangle, CornerSpec, BorderSpec, small_half, big_half};
+use geom::{Point, Rectangle, Dir, CycleDir, CornerSpec, BorderSpec, BorderStyle, small_half, big_half};
 use gesture::GestureEvent;
+use input::{DeviceEvent, FingerStatus, ButtonCode, ButtonStatus};
 use unit::scale_by_dpi;
 use color::{BLACK, WHITE, SEPARATOR_NORMAL};
 use framebuffer::{Framebuffer, UpdateMode};
 use view::filler::Filler;
 use view::menu_entry::MenuEntry;
-use view::common::locate_by_id;
-use view::{View, Event, Hub, Bus, EntryKind, ViewId, CLOSE_IGNITION_DELAY};
+use view::common::{locate_by_id, overlapping_rectangle};
+use view::{View, Event, Hub, Bus, EntryKind, EntryId, ViewId, CLOSE_IGNITION_DELAY};
 use view::{THICKNESS_MEDIUM, THICKNESS_LARGE, BORDER_RADIUS_MEDIUM};
 use app::Context;
 
@@ -22,6 +23,25 @@ pub struct Menu {
     root: bool,
     sub_id: u8,
     dir: i32,
+    // Whether selecting an entry of a radio group closes the menu.
+    auto_close: bool,
+    grouped_ids: Vec<EntryId>,
+    keep_open: bool,
+    entries: Vec<EntryKind>,
+    // The rank of the first visible entry, separators excluded.
+    first: usize,
+    max_entries: usize,
+    entry_height: i32,
+    y_start: i32,
+    drag: Option<Drag>,
+}
+
+// A finger scrolling the entries.
+struct Drag {
+    id: i32,
+    // The ordinate at which the entries were last scrolled.
+    anchor: i32,
+    moved: bool,
 }
 
 #[derive(Debug, Copy, Clone, Eq, PartialEq)]
@@ -37,37 +57,125 @@ pub enum MenuKind {
 //     B         ───
 //     C     BOTTOM MENU
 
+// Turns the radio groups into radio buttons.
+pub fn expand_groups(entries: Vec<EntryKind>) -> Vec<EntryKind> {
+    let mut expanded = Vec::with_capacity(entries.len());
+    for entry in entries {
+        match entry {
+            EntryKind::RadioGroup(choices, selected) => {
+                expanded.extend(choices.into_iter().enumerate().map(|(i, (text, id))| {
+                    EntryKind::RadioButton(text, id, i == selected)
+                }));
+            },
+            entry => expanded.push(entry),
+        }
+    }
+    expanded
+}
+
+// Collects the identifiers of the entries of the radio groups, submenus included.
+fn grouped_ids(entries: &[EntryKind], ids: &mut Vec<EntryId>) {
+    for entry in entries {
+        match *entry {
+            EntryKind::RadioGroup(ref choices, _) => ids.extend(choices.iter().map(|&(_, ref id)| id.clone())),
+            EntryKind::SubMenu(_, ref entries) => grouped_ids(entries, ids),
+            _ => (),
+        }
+    }
+}
+
+// Places a menu of the given *width* next to *target*, on a screen of dimensions *dims*.
+// The menu is flipped to the side of the target that has the most room, and moved
+// back within the screen if it still overflows.
+// Returns the horizontal extent of the menu, the vertical direction in which
+// its entries are stacked and the ordinate of its first entry.
+pub fn placement(target: &Rectangle, kind: MenuKind, width: i32, border_thickness: i32, dims: (u32, u32)) -> (i32, i32, i32, i32) {
+    let (screen_width, screen_height) = (dims.0 as i32, dims.1 as i32);
+    let north_space = target.min.y;
+    let south_space = screen_height - target.max.y;
+    let center = target.center();
+
+    let (dir, y_start) = if kind == MenuKind::SubMenu {
+        if north_space < south_space {
+            (1, target.min.y - border_thickness)
+        } else {
+            (-1, target.max.y + border_thickness)
+        }
+    } else {
+        if north_space < south_space {
+            (1, target.max.y)
+        } else {
+            (-1, target.min.y)
+        }
+    };
+
+    let (mut x_min, mut x_max) = if kind == MenuKind::SubMenu {
+        let west_space = target.min.x;
+        let east_space = screen_width - target.max.x;
+        if west_space > east_space {
+            (target.min.x - width, target.min.x)
+        } else {
+            (target.max.x, target.max.x + width)
+        }
+    } else {
+        (center.x - small_half(width), center.x + big_half(width))
+    };
+
+    if x_min < 0 {
+        x_max -= x_min;
+        x_min = 0;
+    }
+
+    if x_max > screen_width {
+        x_min = (x_min + screen_width - x_max).max(0);
+        x_max = screen_width;
+    }
+
+    (x_min, x_max, dir, y_start.max(0).min(screen_height))
+}
+
+// Returns the range of entries that holds *count* entries, separators excluded,
+// starting with the entry of rank *first*.
+pub fn visible_range(entries: &[EntryKind], first: usize, count: usize) -> (usize, usize) {
+    let mut ranks = entries.iter().enumerate()
+                           .filter(|&(_, e)| !e.is_separator())
+                           .map(|(i, _)| i)
+                           .skip(first);
+    let start = match ranks.next() {
+        Some(start) => start,
+        None => return (entries.len(), entries.len()),
+    };
+    let end = ranks.take(count.saturating_sub(1)).last().unwrap_or(start) + 1;
+    (start, end)
+}
+
 impl Menu {
-    pub fn new(target: Rectangle, id: ViewId, kind: MenuKind, mut entries: Vec<EntryKind>, fonts: &mut Fonts) -> Menu {
-        let mut children = Vec::new();
+    pub fn new(target: Rectangle, id: ViewId, kind: MenuKind, entries: Vec<EntryKind>, fonts: &mut Fonts) -> Menu {
         let dpi = CURRENT_DEVICE.dpi;
         let (width, height) = CURRENT_DEVICE.dims;
         let &(small_height, _) = BAR_SIZES.get(&(height, dpi)).unwrap();
 
         let thickness = scale_by_dpi(THICKNESS_MEDIUM, dpi) as i32;
         let border_thickness = scale_by_dpi(THICKNESS_LARGE, dpi) as i32;
-        let border_radius = scale_by_dpi(BORDER_RADIUS_MEDIUM - THICKNESS_LARGE, dpi) as i32;
         let font = font_from_style(fonts, &NORMAL_STYLE, dpi);
         let entry_height = font.x_heights.0 as i32 * 5;
         let padding = 4 * font.em() as i32;
 
-        let north_space = target.min.y;
-        let south_space = height as i32 - target.max.y;
+        let mut ids = Vec::new();
+        grouped_ids(&entries, &mut ids);
+        let entries = expand_groups(entries);
+
         let center = target.center();
 
-        let (dir, y_start): (i32, i32) = if kind == MenuKind::SubMenu {
-            if north_space < south_space {
-                (1, target.min.y - border_thickness)
-            } else {
-                (-1, target.max.y + border_thickness)
-            }
-        } else {
-            if north_space < south_space {
-                (1, target.max.y)
-            } else {
-                (-1, target.min.y)
-            }
-        };
+        let max_width = 2 * width as i32 / 3;
+        let free_width = padding + 2 * border_thickness +
+                         entries.iter().map(|e| font.plan(e.text(), None, None).width as i32)
+                                .max().unwrap();
+
+        let entry_width = free_width.min(max_width);
+
+        let (x_min, x_max, dir, y_start) = placement(&target, kind, entry_width,
+                                                     border_thickness, (width, height));
 
         let top_min = small_height as i32 + big_half(thickness);
         let bottom_max = height as i32 - small_height as i32 - small_half(thickness);
@@ -84,54 +192,70 @@ impl Menu {
             2 * border_thickness
         };
 
-        let max_entries = ((usable_space - border_space) / entry_height) as usize;
+        // The entries that don't fit are reached by scrolling.
+        let max_entries = (((usable_space - border_space) / entry_height) as usize).max(1);
         let total_entries = entries.iter().filter(|e| !e.is_separator()).count();
 
-        if total_entries > max_entries {
-            let mut kind_counts = [0, 0];
-            for e in &entries {
-                kind_counts[e.is_separator() as usize] += 1;
-                if kind_counts[0] >= max_entries {
-                    break;
-                }
-            }
-            let index = kind_counts[0] + kind_counts[1] - 1;
-            let more = entries.drain(index..).collect::<Vec<EntryKind>>();
-            entries.push(EntryKind::SubMenu("More".to_string(), more));
-        }
+        let triangle_space = if kind == MenuKind::Contextual {
+            font.x_heights.1 as i32
+        } else {
+            0
+        };
 
-        let mut y_pos = y_start + dir * (border_space - border_thickness);
+        let menu_height = total_entries.min(max_entries) as i32 * entry_height + border_space;
 
-        let max_width = 2 * width as i32 / 3;
-        let free_width = padding + 2 * border_thickness +
-                         entries.iter().map(|e| font.plan(e.text(), None, None).width as i32)
-                                .max().unwrap();
+        let (y_min, y_max) = if dir.is_positive() {
+            (y_start - triangle_space, y_start + menu_height)
+        } else {
+            (y_start - menu_height, y_start + triangle_space)
+        };
 
-        let entry_width = free_width.min(max_width);
+        let rect = rect![x_min, y_min,
+                         x_max, y_max];
 
-        let (mut x_min, mut x_max) = if kind == MenuKind::SubMenu {
-            let west_space = target.min.x;
-            let east_space = width as i32 - target.max.x;
-            if west_space > east_space {
-                (target.min.x - entry_width, target.min.x)
-            } else {
-                (target.max.x, target.max.x + entry_width)
-            }
-        } else {
-            (center.x - small_half(entry_width), center.x + big_half(entry_width))
+        let mut menu = Menu {
+            rect,
+            children: Vec::new(),
+            id,
+            kind,
+            center,
+            root: true,
+            sub_id: 0,
+            dir,
+            auto_close: false,
+            grouped_ids: ids,
+            keep_open: false,
+            entries,
+            first: 0,
+            max_entries,
+            entry_height,
+            y_start,
+            drag: None,
         };
 
-        if x_min < 0 {
-            x_max -= x_min;
-            x_min = 0;
-        }
+        menu.layout();
+        menu
+    }
 
-        if x_max > width as i32 {
-            x_min += width as i32 - x_max;
-            x_max = width as i32;
-        }
+    fn layout(&mut self) {
+        let dpi = CURRENT_DEVICE.dpi;
+        let thickness = scale_by_dpi(THICKNESS_MEDIUM, dpi) as i32;
+        let border_thickness = scale_by_dpi(THICKNESS_LARGE, dpi) as i32;
+        let border_radius = scale_by_dpi(BORDER_RADIUS_MEDIUM - THICKNESS_LARGE, dpi) as i32;
+        let (kind, dir, entry_height) = (self.kind, self.dir, self.entry_height);
+        let (x_min, x_max) = (self.rect.min.x, self.rect.max.x);
+
+        let border_space = if kind == MenuKind::DropDown {
+            border_thickness
+        } else {
+            2 * border_thickness
+        };
 
+        let (start, end) = visible_range(&self.entries, self.first, self.max_entries);
+        let entries = &self.entries[start..end];
         let entries_count = entries.len();
+        let mut children = Vec::with_capacity(entries_count);
+        let mut y_pos = self.y_start + dir * (border_space - border_thickness);
 
         for i in 0..entries_count {
             if entries[i].is_separator() {
@@ -193,49 +317,98 @@ impl Menu {
             }
         }
 
-        let triangle_space = if kind == MenuKind::Contextual {
-            font.x_heights.1 as i32
-        } else {
-            0
-        };
+        self.children = children;
+    }
 
-        let total_entries = entries.iter().filter(|e| !e.is_separator()).count();
-        let menu_height = total_entries as i32 * entry_height + border_space;
+    // Saves the state of the check boxes and radio buttons of the visible entries.
+    fn sync_entries(&mut self) {
+        let (start, end) = visible_range(&self.entries, self.first, self.max_entries);
+        for (entry, child) in self.entries[start..end].iter_mut().zip(self.children.iter()) {
+            if let Some(menu_entry) = child.downcast_ref::<MenuEntry>() {
+                *entry = menu_entry.kind().clone();
+            }
+        }
+    }
 
-        let (y_min, y_max) = if dir.is_positive() {
-            (y_start - triangle_space, y_start + menu_height)
-        } else {
-            (y_start - menu_height, y_start + triangle_space)
-        };
+    fn overflows(&self) -> bool {
+        self.entries.iter().filter(|e| !e.is_separator()).count() > self.max_entries
+    }
 
-        let rect = rect![x_min, y_min,
-                         x_max, y_max];
+    // Scrolls by one entry each time the finger moves by the height of an entry.
+    fn drag_to(&mut self, y: i32, hub: &Hub) {
+        let delta = y - self.drag.as_ref().map_or(y, |d| d.anchor);
+        let steps = (delta.abs() / self.entry_height) as usize;
+        if steps == 0 {
+            return;
+        }
+        let dir = if delta < 0 { CycleDir::Next } else { CycleDir::Previous };
+        self.scroll(dir, steps, hub);
+        if let Some(drag) = self.drag.as_mut() {
+            drag.anchor += delta.signum() * steps as i32 * self.entry_height;
+            drag.moved = true;
+        }
+    }
 
-        Menu {
-            rect,
-            children,
-            id,
-            kind,
-            center,
-            root: true,
-            sub_id: 0,
-            dir,
+    fn scroll(&mut self, dir: CycleDir, steps: usize, hub: &Hub) {
+        let total_entries = self.entries.iter().filter(|e| !e.is_separator()).count();
+        let max_first = total_entries.saturating_sub(self.max_entries);
+        let first = match dir {
+            CycleDir::Next => (self.first + steps).min(max_first),
+            CycleDir::Previous => self.first.saturating_sub(steps),
+        };
+        if first == self.first {
+            return;
+        }
+        // The opened submenus are closed.
+        for child in &self.children {
+            if child.as_ref().is::<Menu>() {
+                hub.send(Event::Expose(overlapping_rectangle(child.as_ref()))).unwrap();
+            }
         }
+        self.sync_entries();
+        self.first = first;
+        self.layout();
+        hub.send(Event::Render(self.rect, UpdateMode::Gui)).unwrap();
     }
 
     pub fn root(mut self, root: bool) -> Menu {
         self.root = root;
         self
     }
+
+    pub fn auto_close(mut self, auto_close: bool) -> Menu {
+        self.auto_close = auto_close;
+        self
+    }
+
+    // Closes the deepest opened submenu, returns whether there was one.
+    fn close_deepest(&mut self, hub: &Hub) -> bool {
+        if let Some(index) = self.children.iter().rposition(|c| c.as_ref().is::<Menu>()) {
+            let closed = self.children[index].downcast_mut::<Menu>()
+                             .map_or(false, |m| m.close_deepest(hub));
+            if !closed {
+                hub.send(Event::Expose(*self.children[index].rect())).unwrap();
+                self.children.remove(index);
+            }
+            true
+        } else {
+            false
+        }
+    }
 }
 
 impl View for Menu {
     fn handle_event(&mut self, evt: &Event, hub: &Hub, bus: &mut Bus, context: &mut Context) -> bool {
         match *evt {
             Event::Select(ref entry_id) if self.root => {
+                self.keep_open = !self.auto_close && self.grouped_ids.contains(entry_id);
                 self.handle_event(&Event::PropagateSelect(entry_id.clone()), hub, bus, context);
                 false
             },
+            Event::Validate if self.root && self.keep_open => {
+                self.keep_open = false;
+                true
+            },
             Event::PropagateSelect(..) => {
                 for c in &mut self.children {
                     if c.handle_event(evt, hub, bus, context) {
@@ -253,6 +426,25 @@ impl View for Menu {
                 });
                 true
             },
+            Event::Device(DeviceEvent::Finger { id, status: FingerStatus::Down, position, .. }) if self.rect.includes(&position) => {
+                if self.overflows() {
+                    self.drag = Some(Drag { id, anchor: position.y, moved: false });
+                }
+                true
+            },
+            Event::Device(DeviceEvent::Finger { id, status: FingerStatus::Motion, position, .. }) if self.drag.as_ref().map_or(false, |d| d.id == id) => {
+                self.drag_to(position.y, hub);
+                true
+            },
+            // The swipe that follows a drag has already been handled.
+            Event::Device(DeviceEvent::Finger { id, status: FingerStatus::Up, .. }) if self.drag.as_ref().map_or(false, |d| d.id == id) => {
+                self.drag = self.drag.take().filter(|d| d.moved);
+                true
+            },
+            Event::Gesture(..) if self.drag.as_ref().map_or(false, |d| d.moved) => {
+                self.drag = None;
+                true
+            },
             Event::Gesture(GestureEvent::Tap(ref center)) if !self.rect.includes(center) => {
                 if self.root {
                     hub.send(Event::Close(self.id)).unwrap();
@@ -262,6 +454,28 @@ impl View for Menu {
                 self.root
             },
             Event::Gesture(GestureEvent::HoldFinger(ref center)) if !self.rect.includes(center) => self.root,
+            // Going back only closes the deepest submenu.
+            Event::Gesture(GestureEvent::Swipe { dir, .. }) if dir == Dir::East || dir == Dir::West => {
+                if self.root {
+                    self.close_deepest(hub);
+                }
+                self.root
+            },
+            Event::Gesture(GestureEvent::Swipe { dir, ref start, ref end }) if (dir == Dir::North || dir == Dir::South) &&
+                                                                               self.rect.includes(start) => {
+                let steps = ((end.y - start.y).abs() / self.entry_height).max(1) as usize;
+                if dir == Dir::North {
+                    self.scroll(CycleDir::Next, steps, hub);
+                } else {
+                    self.scroll(CycleDir::Previous, steps, hub);
+                }
+                true
+            },
+            // Let the parent scroll or close.
+            Event::Gesture(GestureEvent::Swipe { .. }) if !self.root => false,
+            Event::Device(DeviceEvent::Button { code: ButtonCode::Home, status: ButtonStatus::Released, .. }) if self.root => {
+                self.close_deepest(hub)
+            },
             Event::SubMenu(rect, ref entries) => {
                 let menu = Menu::new(rect, ViewId::SubMenu(self.sub_id),
                                      MenuKind::SubMenu, entries.clone(), &mut context.fonts).root(false);
@@ -311,7 +525,8 @@ impl View for Menu {
             fb.draw_rounded_rectangle_with_border(&rect,
                                                   &corners,
                                                   &BorderSpec { thickness: border_thickness,
-                                                                color: BLACK },
+                                                                color: BLACK,
+                                                                style: BorderStyle::Solid },
                                                   &WHITE);
 
             let y_b = if self.dir.is_positive() {
@@ -340,7 +555,8 @@ impl View for Menu {
             fb.draw_rounded_rectangle_with_border(&self.rect,
                                                   &corners,
                                                   &BorderSpec { thickness: border_thickness,
-                                                                color: BLACK },
+                                                                color: BLACK,
+                                                                style: BorderStyle::Solid },
                                                   &WHITE);
         }
     }
@@ -369,3 +585,86 @@ impl View for Menu {
         Some(self.id)
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use std::sync::mpsc;
+    use std::collections::VecDeque;
+    use view::{View, Event, EntryKind, EntryId, ViewId, handle_event};
+    use view::menu_entry::MenuEntry;
+    use input::{DeviceEvent, FingerStatus};
+    use gesture::GestureEvent;
+    use geom::Dir;
+    use app::test_context;
+    use super::{Menu, MenuKind, placement, visible_range, expand_groups};
+
+    #[test]
+    fn drag_entries() {
+        let mut context = test_context();
+        let (hub, _rx) = mpsc::channel();
+        let mut bus = VecDeque::new();
+        let entries = (0..100).map(|i| EntryKind::Command(format!("Entry {}", i), EntryId::Undo)).collect();
+        let mut menu = Menu::new(rect![0, 0, 100, 50], ViewId::MainMenu, MenuKind::DropDown,
+                                 entries, &mut context.fonts);
+        let center = menu.children().iter().find(|c| c.as_ref().is::<MenuEntry>())
+                         .map(|c| c.rect().center()).unwrap();
+        let end = center - pt!(0, 2 * menu.entry_height);
+        let finger = |status, position| Event::Device(DeviceEvent::Finger { id: 0, time: 0.0, status, position });
+        for evt in &[finger(FingerStatus::Down, center),
+                     finger(FingerStatus::Motion, end),
+                     finger(FingerStatus::Up, end)] {
+            assert!(handle_event(&mut menu, evt, &hub, &mut bus, &mut context));
+        }
+        assert_eq!(menu.first, 2);
+        // The swipe that ends the drag doesn't scroll any further.
+        let swipe = Event::Gesture(GestureEvent::Swipe { dir: Dir::North, start: center, end });
+        assert!(handle_event(&mut menu, &swipe, &hub, &mut bus, &mut context));
+        assert_eq!(menu.first, 2);
+        assert!(bus.is_empty());
+    }
+
+    #[test]
+    fn skip_separators_when_scrolling() {
+        let command = || EntryKind::Command("Undo".to_string(), EntryId::Undo);
+        let entries = vec![command(), command(), EntryKind::Separator,
+                           command(), command(), EntryKind::Separator, command()];
+        assert_eq!(visible_range(&entries, 0, 10), (0, 7));
+        assert_eq!(visible_range(&entries, 0, 3), (0, 4));
+        assert_eq!(visible_range(&entries, 2, 3), (3, 7));
+        assert_eq!(visible_range(&entries, 4, 3), (6, 7));
+        assert_eq!(visible_range(&entries, 5, 3), (7, 7));
+    }
+
+    #[test]
+    fn stay_within_the_screen() {
+        let dims = (600, 800);
+        let anchors = [rect![0, 0, 50, 50], rect![550, 0, 600, 50],
+                       rect![0, 750, 50, 800], rect![550, 750, 600, 800]];
+        for kind in &[MenuKind::DropDown, MenuKind::SubMenu, MenuKind::Contextual] {
+            for anchor in &anchors {
+                let (x_min, x_max, dir, y_start) = placement(anchor, *kind, 200, 3, dims);
+                assert!(x_min >= 0 && x_max <= 600);
+                assert_eq!(x_max - x_min, 200);
+                assert!(y_start >= 0 && y_start <= 800);
+                assert_eq!(dir, if anchor.min.y == 0 { 1 } else { -1 });
+            }
+        }
+        // Submenus open on the side of their parent that has the most room.
+        assert_eq!(placement(&anchors[1], MenuKind::SubMenu, 200, 3, dims).1, 550);
+        assert_eq!(placement(&anchors[0], MenuKind::SubMenu, 200, 3, dims).0, 50);
+        // Menus wider than the screen are clamped.
+        assert_eq!(placement(&anchors[3], MenuKind::DropDown, 700, 3, dims), (0, 600, -1, 750));
+    }
+
+    #[test]
+    fn expand_radio_groups() {
+        let choices = vec![("First".to_string(), EntryId::Undo), ("Second".to_string(), EntryId::Quit)];
+        let entries = expand_groups(vec![EntryKind::Separator, EntryKind::RadioGroup(choices, 1)]);
+        assert_eq!(entries.len(), 3);
+        assert!(entries[0].is_separator());
+        assert_eq!(entries[1].text(), "First");
+        assert_eq!(entries[1].get(), Some(false));
+        assert_eq!(entries[2].text(), "Second");
+        assert_eq!(entries[2].get(), Some(true));
+    }
+}
@@ -6,7 +6,7 @@ use view::{View, Event, Hub, Bus, EntryKind};
 use view::icon::ICONS_PIXMAPS;
 use input::{DeviceEvent, FingerStatus};
 use gesture::GestureEvent;
-use font::{Fonts, font_from_style, NORMAL_STYLE};
+use font::{Fonts, font_from_style, NORMAL_STYLE, HEADER_STYLE};
 use color::{TEXT_NORMAL, TEXT_INVERTED_HARD};
 use app::Context;
 
@@ -31,6 +31,10 @@ impl MenuEntry {
         }
     }
 
+    pub fn kind(&self) -> &EntryKind {
+        &self.kind
+    }
+
     pub fn update(&mut self, value: bool, hub: &Hub) {
         if let Some(v) = self.kind.get() {
             if v != value {
@@ -46,10 +50,12 @@ impl View for MenuEntry {
         match *evt {
             Event::Device(DeviceEvent::Finger { status, ref position, .. }) => {
                 match status {
-                    FingerStatus::Down if self.rect.includes(position) => {
+                    // The menu also needs the finger to scroll its entries.
+                    FingerStatus::Down if self.rect.includes(position) &&
+                                          !self.kind.is_message() && !self.kind.is_header() => {
                         self.active = true;
                         hub.send(Event::Render(self.rect, UpdateMode::Fast)).unwrap();
-                        true
+                        false
                     },
                     FingerStatus::Up if self.active => {
                         self.active = false;
@@ -125,12 +131,18 @@ impl View for MenuEntry {
         }
 
         let max_width = self.rect.width() - padding as u32;
+        let font = if self.kind.is_header() {
+            font_from_style(fonts, &HEADER_STYLE, dpi)
+        } else {
+            font
+        };
         let plan = font.plan(self.kind.text(), Some(max_width), None);
         let dy = (self.rect.height() as i32 - x_height) / 2;
         let pt = pt!(self.rect.min.x + padding / 2,
                      self.rect.max.y - dy);
 
-        font.render(fb, scheme[1], &plan, &pt); 
+        let foreground = if self.kind.is_message() { scheme[2] } else { scheme[1] };
+        font.render(fb, foreground, &plan, &pt);
 
         let (icon_name, x_offset) = match self.kind {
             EntryKind::CheckBox(_, _, value) if value => ("check_mark", 0),
@@ -46,11 +46,13 @@ use font::Fonts;
 use document::TocEntry;
 use metadata::{Info, SortMethod, PageScheme, Margin};
 use framebuffer::{Framebuffer, UpdateMode};
-use input::{DeviceEvent, FingerStatus};
+use input::{DeviceEvent, FingerStatus, ButtonCode};
 use gesture::GestureEvent;
 use view::key::KeyKind;
+use view::presets_list::PresetsSort;
+use settings::Change;
 use app::Context;
-use geom::{LinearDir, CycleDir, Rectangle};
+use geom::{LinearDir, CycleDir, Point, Rectangle};
 
 pub const THICKNESS_SMALL: f32 = 1.0;
 pub const THICKNESS_MEDIUM: f32 = 2.0;
@@ -200,6 +202,7 @@ pub enum Event {
     Remove(Box<Info>),
     Page(CycleDir),
     ResultsPage(CycleDir),
+    SummaryPage(CycleDir),
     GoTo(usize),
     ResultsGoTo(usize),
     CropMargins(Box<Margin>),
@@ -214,25 +217,46 @@ pub enum Event {
     PropagateSelect(EntryId),
     Submit(ViewId, String),
     Slider(SliderId, f32, FingerStatus),
+    Nudge(SliderId, f32),
+    HoldNudge(SliderId, f32),
     ToggleNear(ViewId, Rectangle),
     ToggleBookMenu(Rectangle, usize),
     TogglePresetMenu(Rectangle, usize),
     SubMenu(Rectangle, Vec<EntryKind>),
     Toggle(ViewId),
     Show(ViewId),
+    ShowNear(ViewId, Point),
     Close(ViewId),
     CloseSub(ViewId),
     SearchResult(usize, Rectangle),
     EndOfSearch,
     Finished,
-    ClockTick,
-    BatteryTick,
+    LightSensorTick,
+    // Sent to the scrolling label with the given rectangle.
+    MarqueeTick(Rectangle),
+    // The raw and the smoothed light sensor levels.
+    LightSensorReading(u16, u16),
+    RefreshStatus,
     ToggleFrontlight,
     Load(PathBuf),
     LoadPreset(usize),
+    MovePreset(usize, usize),
+    RemovePreset,
+    // Whether the imported presets that duplicate existing ones are skipped.
+    SkipDuplicatePresets(bool),
+    // The number of imported presets merged into existing ones.
+    PresetsMerged(usize),
+    FadeTick,
+    WakeFadeTick,
+    KeyRepeat(ButtonCode),
     Save,
     Guess,
+    ResetFrontlight,
+    ToggleLevelsLock,
+    ToggleWarmth,
     Suspend,
+    // The settings changes since the previous save.
+    SettingsChanged(Vec<Change>),
     Mount,
     Validate,
     Cancel,
@@ -263,10 +287,14 @@ pub enum ViewId {
     ExportAsInput,
     AddCategories,
     AddCategoriesInput,
+    RenamePreset,
+    RenamePresetInput,
     SearchInput,
     SearchBar,
     Keyboard,
     ConfirmMount,
+    ConfirmRemovePreset,
+    ConfirmSkipDuplicates,
     MarginCropper,
     TopBottomBars,
     TableOfContents,
@@ -275,9 +303,26 @@ pub enum ViewId {
     NoSearchResultsNotif,
     InvalidSearchQueryNotif,
     NetUpNotif,
+    MergedPresetsNotif,
     SubMenu(u8),
 }
 
+impl ViewId {
+    // Whether the view sits on top of the main view and should be dismissed
+    // before the main view reacts to gestures again.
+    pub fn is_overlay(&self) -> bool {
+        match *self {
+            ViewId::SortMenu | ViewId::MainMenu | ViewId::Frontlight |
+            ViewId::FontSizeMenu | ViewId::MatchesMenu | ViewId::PageMenu |
+            ViewId::BookMenu | ViewId::PresetMenu | ViewId::MarginCropperMenu |
+            ViewId::SearchMenu | ViewId::GoToPage | ViewId::GoToResultsPage |
+            ViewId::ExportAs | ViewId::AddCategories | ViewId::RenamePreset | ViewId::ConfirmMount |
+            ViewId::ConfirmRemovePreset | ViewId::ConfirmSkipDuplicates | ViewId::MarginCropper | ViewId::SubMenu(..) => true,
+            _ => false,
+        }
+    }
+}
+
 #[derive(Debug, Copy, Clone, Eq, PartialEq)]
 pub enum SliderId {
     FontSize,
@@ -311,6 +356,33 @@ impl Align {
             Align::Center => (container_width - width) / 2,
         }
     }
+
+    // The alignment of right-to-left text.
+    #[inline]
+    pub fn mirrored(&self) -> Align {
+        match *self {
+            Align::Left(dx) => Align::Right(dx),
+            Align::Right(dx) => Align::Left(dx),
+            Align::Center => Align::Center,
+        }
+    }
+}
+
+// The height of text is the height of its *x* and the last baseline is its bottom.
+#[derive(Debug, Copy, Clone)]
+pub enum VAlign {
+    Top(i32),
+    Center,
+}
+
+impl VAlign {
+    #[inline]
+    pub fn offset(&self, height: i32, container_height: i32) -> i32 {
+        match *self {
+            VAlign::Top(dy) => dy,
+            VAlign::Center => (container_height - height + 1) / 2,
+        }
+    }
 }
 
 #[derive(Debug, Copy, Clone)]
@@ -334,7 +406,12 @@ pub enum EntryKind {
     Command(String, EntryId),
     CheckBox(String, EntryId, bool),
     RadioButton(String, EntryId, bool),
+    // Radio buttons, and the index of the selected one.
+    RadioGroup(Vec<(String, EntryId)>, usize),
     SubMenu(String, Vec<EntryKind>),
+    Message(String),
+    // The title of the entries that follow.
+    Header(String),
     Separator,
 }
 
@@ -350,6 +427,13 @@ pub enum EntryId {
     RemoveBookCategory(PathBuf, String),
     RemoveMatches,
     RemovePreset(usize),
+    RenamePreset(usize),
+    DuplicatePreset(usize),
+    ExportPreset(usize, bool),
+    ExportPresets,
+    ImportPresets(bool),
+    SortPresets(PresetsSort),
+    ToggleNightWarmth(usize),
     AddMatchesCategories,
     RemoveMatchesCategory(String),
     Load(PathBuf),
@@ -359,6 +443,7 @@ pub enum EntryId {
     ToggleInverted,
     ToggleMonochrome,
     ToggleWifi,
+    ToggleFrontlight,
     TakeScreenshot,
     StartNickel,
     Reboot,
@@ -398,11 +483,28 @@ impl EntryKind {
             EntryKind::Command(ref s, ..) |
             EntryKind::CheckBox(ref s, ..) |
             EntryKind::RadioButton(ref s, ..) |
-            EntryKind::SubMenu(ref s, ..) => s,
+            EntryKind::SubMenu(ref s, ..) |
+            EntryKind::Message(ref s) |
+            EntryKind::Header(ref s) => s,
             _ => "",
         }
     }
 
+    // Messages only convey information and can't be selected.
+    pub fn is_message(&self) -> bool {
+        match *self {
+            EntryKind::Message(..) => true,
+            _ => false,
+        }
+    }
+
+    pub fn is_header(&self) -> bool {
+        match *self {
+            EntryKind::Header(..) => true,
+            _ => false,
+        }
+    }
+
     pub fn get(&self) -> Option<bool> {
         match *self {
             EntryKind::CheckBox(_, _, v) |
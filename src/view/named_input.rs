@@ -1,6 +1,6 @@
 use framebuffer::Framebuffer;
 use font::{Fonts, font_from_style, NORMAL_STYLE};
-use geom::{Rectangle, CornerSpec, BorderSpec, halves, big_half};
+use geom::{Rectangle, CornerSpec, BorderSpec, BorderStyle, halves, big_half};
 use view::{View, Event, Hub, Bus, ViewId, Align};
 use view::{THICKNESS_LARGE, BORDER_RADIUS_MEDIUM};
 use view::label::Label;
@@ -55,7 +55,7 @@ impl NamedInput {
                                      x_min + big_half_padding + padding + label_width,
                                      y_max - x_height],
                                text,
-                               Align::Center);
+                               Align::Center).auto_shrink(true);
         children.push(Box::new(label) as Box<View>);
 
         let input_field = InputField::new(rect![x_max - 3 * padding - input_width,
@@ -94,7 +94,8 @@ impl View for NamedInput {
         fb.draw_rounded_rectangle_with_border(&self.rect,
                                               &CornerSpec::Uniform(border_radius),
                                               &BorderSpec { thickness: border_thickness,
-                                                            color: BLACK },
+                                                            color: BLACK,
+                                                            style: BorderStyle::Solid },
                                               &WHITE);
     }
 
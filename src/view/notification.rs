@@ -2,7 +2,7 @@ use std::thread;
 use std::time::Duration;
 use device::{CURRENT_DEVICE, BAR_SIZES};
 use framebuffer::{Framebuffer, UpdateMode};
-use geom::{Rectangle, CornerSpec, BorderSpec};
+use geom::{Rectangle, CornerSpec, BorderSpec, BorderStyle};
 use font::{Fonts, font_from_style, NORMAL_STYLE};
 use color::{BLACK, WHITE, TEXT_NORMAL};
 use view::{View, Event, Hub, Bus, ViewId};
@@ -87,7 +87,8 @@ impl View for Notification {
         fb.draw_rounded_rectangle_with_border(&self.rect,
                                               &CornerSpec::Uniform(border_radius),
                                               &BorderSpec { thickness: border_thickness,
-                                                            color: BLACK },
+                                                            color: BLACK,
+                                                            style: BorderStyle::Solid },
                                               &WHITE);
 
         let font = font_from_style(fonts, &NORMAL_STYLE, dpi);
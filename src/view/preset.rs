@@ -2,11 +2,11 @@ use device::CURRENT_DEVICE;
 use geom::{Rectangle, CornerSpec, CycleDir};
 use font::{Fonts, font_from_style, NORMAL_STYLE};
 use view::{View, Event, Hub, Bus};
-use view::BORDER_RADIUS_MEDIUM;
+use view::{BORDER_RADIUS_MEDIUM, THICKNESS_SMALL};
 use framebuffer::{Framebuffer, UpdateMode};
 use input::{DeviceEvent, FingerStatus};
 use gesture::GestureEvent;
-use color::{TEXT_NORMAL, TEXT_INVERTED_HARD};
+use color::{BLACK, TEXT_NORMAL, TEXT_INVERTED_HARD};
 use unit::scale_by_dpi;
 use app::Context;
 
@@ -15,6 +15,7 @@ pub struct Preset {
     children: Vec<Box<View>>,
     kind: PresetKind,
     active: bool,
+    progress: Option<f32>,
 }
 
 pub enum PresetKind {
@@ -29,8 +30,62 @@ impl Preset {
             children: vec![],
             kind,
             active: false,
+            progress: None,
         }
     }
+
+    pub fn set_active(&mut self, active: bool) {
+        self.active = active;
+    }
+
+    pub fn set_progress(&mut self, progress: Option<f32>) {
+        self.progress = progress;
+    }
+
+    pub fn index(&self) -> Option<usize> {
+        match self.kind {
+            PresetKind::Normal(_, index) => Some(index),
+            _ => None,
+        }
+    }
+}
+
+// The rectangles of a ring of the given *thickness*, drawn along the edges of *rect*
+// clockwise from the top left corner, that covers the *progress* fraction of its perimeter.
+pub fn ring_segments(rect: &Rectangle, thickness: i32, progress: f32) -> Vec<Rectangle> {
+    let width = rect.width() as i32;
+    let height = rect.height() as i32;
+    let mut length = (progress.max(0.0).min(1.0) * (2 * (width + height)) as f32) as i32;
+    let mut segments = Vec::new();
+
+    let top = length.min(width);
+    if top > 0 {
+        segments.push(rect![rect.min.x, rect.min.y,
+                            rect.min.x + top, rect.min.y + thickness]);
+    }
+    length -= top;
+
+    let right = length.min(height);
+    if right > 0 {
+        segments.push(rect![rect.max.x - thickness, rect.min.y,
+                            rect.max.x, rect.min.y + right]);
+    }
+    length -= right;
+
+    let bottom = length.min(width);
+    if bottom > 0 {
+        segments.push(rect![rect.max.x - bottom, rect.max.y - thickness,
+                            rect.max.x, rect.max.y]);
+    }
+    length -= bottom;
+
+    let left = length.min(height);
+    if left > 0 {
+        segments.push(rect![rect.min.x, rect.max.y - left,
+                            rect.min.x + thickness, rect.max.y]);
+    }
+
+    segments
 }
 
 impl View for Preset {
@@ -96,6 +151,13 @@ impl View for Preset {
         let pt = pt!(self.rect.min.x + dx, self.rect.max.y - dy);
 
         font.render(fb, scheme[1], &plan, &pt);
+
+        if let Some(progress) = self.progress {
+            let thickness = scale_by_dpi(THICKNESS_SMALL, dpi) as i32;
+            for segment in ring_segments(&self.rect, thickness, progress) {
+                fb.draw_rectangle(&segment, BLACK);
+            }
+        }
     }
 
     fn rect(&self) -> &Rectangle {
@@ -114,3 +176,18 @@ impl View for Preset {
         &mut self.children
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::ring_segments;
+
+    #[test]
+    fn ring_follows_progress() {
+        let rect = rect![0, 0, 40, 10];
+        assert!(ring_segments(&rect, 1, 0.0).is_empty());
+        assert_eq!(ring_segments(&rect, 1, 0.25), vec![rect![0, 0, 25, 1]]);
+        assert_eq!(ring_segments(&rect, 1, 0.5), vec![rect![0, 0, 40, 1], rect![39, 0, 40, 10]]);
+        assert_eq!(ring_segments(&rect, 1, 1.0), vec![rect![0, 0, 40, 1], rect![39, 0, 40, 10],
+                                                      rect![0, 9, 40, 10], rect![0, 0, 1, 10]]);
+    }
+}
@@ -1,111 +1,507 @@
 use device::CURRENT_DEVICE;
-use view::{View, Event, Hub, Bus};
-use geom::{Rectangle, Dir, CycleDir};
+use view::{View, Event, Hub, Bus, Align};
+use geom::{Point, Rectangle, Dir, CycleDir};
 use font::{Fonts, font_from_style, NORMAL_STYLE};
 use framebuffer::{Framebuffer, UpdateMode};
 use view::preset::{Preset, PresetKind};
+use view::label::Label;
 use gesture::GestureEvent;
+use input::{DeviceEvent, FingerStatus};
 use settings::LightPreset;
-use color::WHITE;
+use color::{WHITE, TEXT_MUTED};
 use app::Context;
 
+// The maximum number of rows of wrapped presets, the others are on the next pages.
+pub const MAX_ROWS: usize = 3;
+const LABEL_EMPTY: &str = "No presets yet — tap Save";
+
+// The order in which the presets are displayed.
+// The stored order of the presets is never changed by it.
+#[derive(Debug, Copy, Clone, Eq, PartialEq)]
+pub enum PresetsSort {
+    Stored,
+    Name,
+    Time,
+}
+
 pub struct PresetsList {
     rect: Rectangle,
-    pages: Vec<Vec<Box<View>>>,
-    current_page: usize,
+    children: Vec<Box<View>>,
+    names: Vec<String>,
+    // The indices of the presets, in display order.
+    order: Vec<usize>,
+    // The widths of the wrapped presets, in display order.
+    widths: Vec<i32>,
+    sort: PresetsSort,
+    first: usize,
+    page: usize,
+    max_per_line: usize,
+    preset_width: i32,
+    preset_height: i32,
+    padding: i32,
+    wrap: bool,
+    drag: Option<Drag>,
+}
+
+// A preset being dragged after a long press.
+struct Drag {
+    index: usize,
+    rect: Rectangle,
+    origin: Option<Point>,
+    moved: bool,
+}
+
+#[derive(Debug, Copy, Clone, Eq, PartialEq)]
+pub enum Slot {
+    Preset(usize),
+    Page(CycleDir),
+}
+
+// Returns the indices of the presets in the order given by *sort*.
+// Presets that compare equal keep their stored order.
+pub fn sorted_indices(presets: &[LightPreset], sort: PresetsSort) -> Vec<usize> {
+    let mut indices: Vec<usize> = (0..presets.len()).collect();
+    match sort {
+        PresetsSort::Stored => (),
+        PresetsSort::Name => indices.sort_by_key(|&i| presets[i].name().to_lowercase()),
+        PresetsSort::Time => indices.sort_by_key(|&i| presets[i].timestamp),
+    }
+    indices
+}
+
+// Splits the presets into rows that fit within *max_width*.
+// Each row contains at least one preset.
+pub fn break_rows(widths: &[i32], max_width: i32, padding: i32) -> Vec<Vec<usize>> {
+    let mut rows = Vec::new();
+    let mut row = Vec::new();
+    let mut width = 0;
+
+    for (index, w) in widths.iter().enumerate() {
+        let needed = if row.is_empty() { *w } else { width + padding + w };
+        if needed > max_width && !row.is_empty() {
+            rows.push(row);
+            row = vec![index];
+            width = *w;
+        } else {
+            row.push(index);
+            width = needed;
+        }
+    }
+
+    if !row.is_empty() {
+        rows.push(row);
+    }
+
+    rows
+}
+
+// Splits the presets into pages of at most *max_rows* rows. Every page but the first
+// starts with an arrow to the previous page, and every page but the last ends with
+// an arrow to the next one.
+pub fn page_rows(widths: &[i32], max_width: i32, padding: i32, arrow_width: i32, max_rows: usize) -> Vec<Vec<Vec<Slot>>> {
+    let width_of = |slot: &Slot| match *slot {
+        Slot::Preset(index) => widths[index],
+        Slot::Page(_) => arrow_width,
+    };
+    let mut pages = Vec::new();
+    let mut start = 0;
+
+    while start < widths.len() {
+        let mut slots_widths: Vec<i32> = widths[start..].to_vec();
+        // The arrow to the previous page shares its row with a preset,
+        // so that each page holds at least one preset.
+        if start > 0 {
+            slots_widths[0] += arrow_width + padding;
+        }
+        let mut rows: Vec<Vec<Slot>> = break_rows(&slots_widths, max_width, padding)
+                                           .into_iter()
+                                           .map(|row| row.into_iter().map(|i| Slot::Preset(start + i)).collect())
+                                           .collect();
+        if start > 0 {
+            rows[0].insert(0, Slot::Page(CycleDir::Previous));
+        }
+
+        if rows.len() <= max_rows.max(1) {
+            pages.push(rows);
+            break;
+        }
+
+        rows.truncate(max_rows.max(1));
+        let presets_count = rows.iter().flat_map(|row| row.iter())
+                                .filter(|s| if let Slot::Preset(_) = **s { true } else { false })
+                                .count();
+        {
+            // The last row makes room for the arrow to the next page.
+            let last_row = rows.last_mut().unwrap();
+            let mut removed = 0;
+            loop {
+                let row_width = last_row.iter().map(|s| width_of(s) + padding).sum::<i32>() + arrow_width;
+                match last_row.last() {
+                    Some(&Slot::Preset(_)) if row_width > max_width && removed + 1 < presets_count => {
+                        last_row.pop();
+                        removed += 1;
+                    },
+                    _ => break,
+                }
+            }
+            last_row.push(Slot::Page(CycleDir::Next));
+        }
+
+        start = rows.iter().flat_map(|row| row.iter())
+                    .filter_map(|s| if let Slot::Preset(index) = *s { Some(index) } else { None })
+                    .last().map_or(start + 1, |index| index + 1);
+        pages.push(rows);
+    }
+
+    pages
+}
+
+// The page shown after a horizontal swipe from *start* to *end*.
+// Swipes shorter than *threshold* don't change the page.
+pub fn swipe_page(dir: Dir, start: Point, end: Point, threshold: i32) -> Option<CycleDir> {
+    if (end.x - start.x).abs() < threshold {
+        return None;
+    }
+    match dir {
+        Dir::West => Some(CycleDir::Next),
+        Dir::East => Some(CycleDir::Previous),
+        _ => None,
+    }
+}
+
+// Returns the index of the preset whose center is the closest to *position*.
+// Positions beyond the first or last preset land on it.
+pub fn drop_index(centers: &[(usize, Point)], position: &Point) -> Option<usize> {
+    centers.iter()
+           .min_by_key(|&&(_, center)| (center - *position).length() as i32)
+           .map(|&(index, _)| index)
+}
+
+// The largest index of the first visible preset for which the row is still full.
+pub fn max_first(count: usize, max_per_line: usize) -> usize {
+    if count <= max_per_line {
+        0
+    } else if max_per_line > 2 {
+        count - (max_per_line - 1)
+    } else {
+        count - max_per_line
+    }
+}
+
+// Returns the slots of a row of at most *max_per_line* items that starts at the preset of index
+// *first*. When the presets don't fit, arrows are inserted at the edges that can be scrolled.
+pub fn row_slots(count: usize, max_per_line: usize, first: usize) -> Vec<Slot> {
+    let first = first.min(max_first(count, max_per_line));
+    let with_arrows = count > max_per_line && max_per_line > 2;
+    let mut slots = Vec::with_capacity(max_per_line);
+    let mut available = max_per_line;
+
+    if with_arrows && first > 0 {
+        slots.push(Slot::Page(CycleDir::Previous));
+        available -= 1;
+    }
+
+    let remaining = count - first;
+
+    if with_arrows && remaining > available {
+        slots.extend((first..first + available - 1).map(Slot::Preset));
+        slots.push(Slot::Page(CycleDir::Next));
+    } else {
+        slots.extend((first..first + remaining.min(available)).map(Slot::Preset));
+    }
+
+    slots
 }
 
 impl PresetsList {
     pub fn new(rect: Rectangle) -> PresetsList {
         PresetsList {
             rect,
-            pages: vec![],
-            current_page: 0,
+            children: vec![],
+            names: vec![],
+            order: vec![],
+            widths: vec![],
+            sort: PresetsSort::Stored,
+            first: 0,
+            page: 0,
+            max_per_line: 1,
+            preset_width: 0,
+            preset_height: 0,
+            padding: 0,
+            wrap: false,
+            drag: None,
+        }
+    }
+
+    pub fn wrap(mut self, wrap: bool) -> PresetsList {
+        self.wrap = wrap;
+        self
+    }
+
+    pub fn sort(mut self, sort: PresetsSort) -> PresetsList {
+        self.sort = sort;
+        self
+    }
+
+    pub fn set_sort(&mut self, sort: PresetsSort) {
+        self.sort = sort;
+        self.first = 0;
+        self.page = 0;
+    }
+
+    // The number of rows needed to display the given presets within *width*.
+    // A row is kept for the placeholder when there are no presets.
+    pub fn rows_count(presets: &[LightPreset], width: i32, wrap: bool, sort: PresetsSort, fonts: &mut Fonts) -> usize {
+        if presets.is_empty() || !wrap {
+            return 1;
         }
+        let font = font_from_style(fonts, &NORMAL_STYLE, CURRENT_DEVICE.dpi);
+        let padding = font.em() as i32;
+        let widths: Vec<i32> = sorted_indices(presets, sort).into_iter()
+                                      .map(|i| font.plan(&presets[i].name(), None, None).width as i32 + padding)
+                                      .collect();
+        break_rows(&widths, width, padding).len().min(MAX_ROWS)
     }
 
     pub fn update(&mut self, presets: &[LightPreset], hub: &Hub, fonts: &mut Fonts) {
         let dpi = CURRENT_DEVICE.dpi;
         let font = font_from_style(fonts, &NORMAL_STYLE, dpi);
         let x_height = font.x_heights.0 as i32;
-        let preset_height = 4 * x_height;
-        let padding = font.em() as i32;
-        let preset_width = font.plan(&presets[0].name(), None, None).width as i32 + padding;
-        let max_per_line = (self.rect.width() as i32 + padding) / (preset_width + padding);
-
-        self.pages.clear();
-        let mut children = Vec::new();
-
-        let presets_count = presets.len() as i32;
-        let first_line_count = max_per_line.min(presets_count);
-        let mut item_index = 0;
-        let mut index = 0;
-
-        let dx = (self.rect.width() as i32 - (first_line_count * preset_width +
-                                              (first_line_count - 1) * padding)) / 2;
-
-        while index < presets_count {
-            let position = item_index % max_per_line;
-            let x = self.rect.min.x + dx + position * (preset_width + padding);
-            let preset_rect = rect![x, self.rect.max.y - preset_height,
-                                    x + preset_width, self.rect.max.y];
-            let kind = if (position == 0 && index > 0) || (position == max_per_line - 1 &&
-                                                           index < presets_count - 1) {
-                let dir = if position == 0 { CycleDir::Previous } else { CycleDir::Next };
-                PresetKind::Page(dir)
-            } else {
-                let name = presets[index as usize].name();
-                let kind = PresetKind::Normal(name, index as usize);
-                index += 1;
-                kind
+        self.preset_height = 4 * x_height;
+        self.padding = font.em() as i32;
+        self.names = presets.iter().map(|p| p.name()).collect();
+        self.order = sorted_indices(presets, self.sort);
+        let max_width = self.rect.width() as i32;
+        self.preset_width = self.names.iter()
+                                .map(|n| font.plan(n, None, None).width as i32 + self.padding)
+                                .max().unwrap_or(0).min(max_width);
+        self.max_per_line = ((self.rect.width() as i32 + self.padding) /
+                             (self.preset_width + self.padding)).max(1) as usize;
+        if self.wrap {
+            let padding = self.padding;
+            self.widths = self.order.iter()
+                              .map(|&i| font.plan(&self.names[i], None, None).width as i32 + padding)
+                              .collect();
+            self.layout_rows();
+        } else {
+            self.first = self.first.min(max_first(self.names.len(), self.max_per_line));
+            self.layout();
+        }
+        hub.send(Event::Render(self.rect, UpdateMode::Gui)).unwrap();
+    }
+
+    fn layout_placeholder(&mut self) {
+        let label = Label::new(self.rect, LABEL_EMPTY.to_string(), Align::Center)
+                          .scheme(TEXT_MUTED);
+        self.children = vec![Box::new(label) as Box<View>];
+    }
+
+    fn layout_rows(&mut self) {
+        if self.names.is_empty() {
+            self.layout_placeholder();
+            return;
+        }
+
+        // The arrows are square.
+        let arrow_width = self.preset_height;
+        let mut pages = page_rows(&self.widths, self.rect.width() as i32, self.padding, arrow_width, MAX_ROWS);
+        let rows_count = pages.iter().map(|rows| rows.len()).max().unwrap_or(0);
+        let pitch = self.rect.height() as i32 / rows_count.max(1) as i32;
+        self.page = self.page.min(pages.len().saturating_sub(1));
+
+        self.children.clear();
+
+        if pages.is_empty() {
+            return;
+        }
+
+        let widths = &self.widths;
+        let width_of = |slot: &Slot| match *slot {
+            Slot::Preset(index) => widths[index],
+            Slot::Page(_) => arrow_width,
+        };
+
+        for (i, row) in pages.swap_remove(self.page).into_iter().enumerate() {
+            let row_width = row.iter().map(|s| width_of(s)).sum::<i32>() +
+                            (row.len() as i32 - 1) * self.padding;
+            let max_y = self.rect.min.y + (i as i32 + 1) * pitch;
+            let mut x = self.rect.min.x + (self.rect.width() as i32 - row_width) / 2;
+            for slot in row {
+                let preset_rect = rect![x, max_y - self.preset_height,
+                                        x + width_of(&slot), max_y];
+                let kind = match slot {
+                    Slot::Preset(position) => {
+                        let index = self.order[position];
+                        PresetKind::Normal(self.names[index].clone(), index)
+                    },
+                    Slot::Page(dir) => PresetKind::Page(dir),
+                };
+                self.children.push(Box::new(Preset::new(preset_rect, kind)) as Box<View>);
+                x += width_of(&slot) + self.padding;
+            }
+        }
+    }
+
+    fn layout(&mut self) {
+        if self.names.is_empty() {
+            self.layout_placeholder();
+            return;
+        }
+
+        let slots = row_slots(self.names.len(), self.max_per_line, self.first);
+        let count = slots.len() as i32;
+        let dx = (self.rect.width() as i32 - (count * self.preset_width +
+                                              (count - 1) * self.padding)) / 2;
+
+        self.children.clear();
+
+        for (position, slot) in slots.into_iter().enumerate() {
+            let x = self.rect.min.x + dx + position as i32 * (self.preset_width + self.padding);
+            let preset_rect = rect![x, self.rect.max.y - self.preset_height,
+                                    x + self.preset_width, self.rect.max.y];
+            let kind = match slot {
+                Slot::Preset(position) => {
+                    let index = self.order[position];
+                    PresetKind::Normal(self.names[index].clone(), index)
+                },
+                Slot::Page(dir) => PresetKind::Page(dir),
             };
+            self.children.push(Box::new(Preset::new(preset_rect, kind)) as Box<View>);
+        }
+    }
 
-            let preset = Preset::new(preset_rect, kind);
-            children.push(Box::new(preset) as Box<View>);
-            item_index += 1;
+    pub fn scroll(&mut self, dir: CycleDir, steps: usize) {
+        let max_first = max_first(self.names.len(), self.max_per_line);
+        self.first = match dir {
+            CycleDir::Next => (self.first + steps).min(max_first),
+            CycleDir::Previous => self.first.saturating_sub(steps),
+        };
+        self.layout();
+    }
 
-            if item_index % max_per_line == 0 || index == presets_count {
-                self.pages.push(children);
-                children = Vec::new();
+    // Shows the progress of a change of light levels around the preset of the given index.
+    pub fn set_progress(&mut self, index: usize, progress: Option<f32>, hub: &Hub) {
+        for child in &mut self.children {
+            if let Some(preset) = child.downcast_mut::<Preset>() {
+                if preset.index() == Some(index) {
+                    preset.set_progress(progress);
+                    hub.send(Event::Render(*preset.rect(), UpdateMode::Fast)).unwrap();
+                }
             }
         }
+    }
 
-        self.current_page = self.current_page.min(self.pages.len().saturating_sub(1));
+    fn drag_to(&mut self, position: Point, hub: &Hub) {
+        let threshold = self.padding as f32;
+        if let Some(ref mut drag) = self.drag {
+            let origin = *drag.origin.get_or_insert(position);
+            if !drag.moved && (position - origin).length() < threshold {
+                return;
+            }
+            drag.moved = true;
+            for child in &mut self.children {
+                if let Some(preset) = child.downcast_mut::<Preset>() {
+                    if preset.index() == Some(drag.index) {
+                        // Both the old and the new places of the ghost need to be drawn.
+                        let mut rect = *preset.rect();
+                        *preset.rect_mut() = drag.rect + (position - origin);
+                        rect.absorb(preset.rect());
+                        hub.send(Event::Render(rect, UpdateMode::Fast)).unwrap();
+                    }
+                }
+            }
+        }
+    }
 
-        hub.send(Event::Render(self.rect, UpdateMode::Gui)).unwrap();
+    fn drop_at(&mut self, position: Point, hub: &Hub, bus: &mut Bus) {
+        if let Some(drag) = self.drag.take() {
+            if !drag.moved {
+                bus.push_back(Event::TogglePresetMenu(drag.rect, drag.index));
+                return;
+            }
+            // The dragged preset is placed back where it was picked up.
+            let centers: Vec<(usize, Point)> = self.children.iter().filter_map(|c| {
+                c.downcast_ref::<Preset>().and_then(|p| {
+                    p.index().map(|i| (i, if i == drag.index { drag.rect.center() } else { p.rect().center() }))
+                })
+            }).collect();
+            match drop_index(&centers, &position) {
+                Some(to) if to != drag.index => bus.push_back(Event::MovePreset(drag.index, to)),
+                _ => {
+                    self.layout();
+                    hub.send(Event::Render(self.rect, UpdateMode::Gui)).unwrap();
+                },
+            }
+        }
     }
 
     pub fn set_current_page(&mut self, dir: CycleDir) {
-        match dir {
-            CycleDir::Next if self.current_page < self.pages.len() - 1 => {
-                self.current_page += 1;
-            },
-            CycleDir::Previous if self.current_page > 0 => {
-                self.current_page -= 1;
-            },
-            _ => (),
+        if self.wrap {
+            self.page = match dir {
+                CycleDir::Next => self.page + 1,
+                CycleDir::Previous => self.page.saturating_sub(1),
+            };
+            self.layout_rows();
+            return;
         }
+        let visible = self.children.iter().filter(|c| {
+            c.downcast_ref::<Preset>().map_or(false, |p| p.index().is_some())
+        }).count();
+        self.scroll(dir, visible.max(1));
     }
 }
 
 impl View for PresetsList {
-    fn handle_event(&mut self, evt: &Event, hub: &Hub, _bus: &mut Bus, _context: &mut Context) -> bool {
+    fn handle_event(&mut self, evt: &Event, hub: &Hub, bus: &mut Bus, _context: &mut Context) -> bool {
         match *evt {
-            Event::Gesture(GestureEvent::Swipe { dir, ref start, .. }) if self.rect.includes(start) => {
+            // A long press on a preset starts a drag, the menu is only shown if the finger doesn't move.
+            // Presets can't be moved by hand while they're sorted.
+            Event::TogglePresetMenu(rect, index) if self.drag.is_none() && self.sort == PresetsSort::Stored => {
+                // The finger's release is handled here rather than by the preset.
+                for child in &mut self.children {
+                    if let Some(preset) = child.downcast_mut::<Preset>() {
+                        if preset.index() == Some(index) {
+                            preset.set_active(false);
+                        }
+                    }
+                }
+                self.drag = Some(Drag { index, rect, origin: None, moved: false });
+                hub.send(Event::Render(rect, UpdateMode::Gui)).unwrap();
+                true
+            },
+            Event::Device(DeviceEvent::Finger { status: FingerStatus::Motion, position, .. }) if self.drag.is_some() => {
+                self.drag_to(position, hub);
+                true
+            },
+            Event::Device(DeviceEvent::Finger { status: FingerStatus::Up, position, .. }) if self.drag.is_some() => {
+                self.drop_at(position, hub, bus);
+                true
+            },
+            Event::Gesture(_) if self.drag.as_ref().map_or(false, |d| d.moved) => true,
+            Event::Gesture(GestureEvent::Swipe { dir, ref start, ref end }) if !self.wrap && self.rect.includes(start) => {
+                let steps = ((end.x - start.x).abs() / (self.preset_width + self.padding).max(1)).max(1) as usize;
                 match dir {
                     Dir::West => {
-                        self.set_current_page(CycleDir::Next);
+                        self.scroll(CycleDir::Next, steps);
                         hub.send(Event::Render(self.rect, UpdateMode::Gui)).unwrap();
                         true
                     },
                     Dir::East => {
-                        self.set_current_page(CycleDir::Previous);
+                        self.scroll(CycleDir::Previous, steps);
                         hub.send(Event::Render(self.rect, UpdateMode::Gui)).unwrap();
                         true
                     },
                     _ => false,
                 }
             },
+            Event::Gesture(GestureEvent::Swipe { dir, start, end }) if self.wrap && self.rect.includes(&start) => {
+                // The swipe must be at least as long as the arrows are wide.
+                if let Some(dir) = swipe_page(dir, start, end, self.preset_height) {
+                    self.set_current_page(dir);
+                    hub.send(Event::Render(self.rect, UpdateMode::Gui)).unwrap();
+                }
+                true
+            },
             Event::Page(dir) => {
                 self.set_current_page(dir);
                 hub.send(Event::Render(self.rect, UpdateMode::Gui)).unwrap();
@@ -132,10 +528,105 @@ impl View for PresetsList {
     }
 
     fn children(&self) -> &Vec<Box<View>> {
-        &self.pages[self.current_page]
+        &self.children
     }
 
     fn children_mut(&mut self) -> &mut Vec<Box<View>> {
-        &mut self.pages[self.current_page]
+        &mut self.children
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use geom::{Dir, CycleDir};
+    use settings::LightPreset;
+    use super::{Slot, PresetsSort, row_slots, max_first, break_rows, page_rows, drop_index, sorted_indices, swipe_page};
+
+    #[test]
+    fn all_presets_fit() {
+        assert_eq!(row_slots(3, 5, 0), vec![Slot::Preset(0), Slot::Preset(1), Slot::Preset(2)]);
+        assert_eq!(row_slots(3, 5, 2), row_slots(3, 5, 0));
+    }
+
+    #[test]
+    fn last_preset_reachable() {
+        let count = 10;
+        let start = row_slots(count, 4, 0);
+        assert_eq!(start, vec![Slot::Preset(0), Slot::Preset(1), Slot::Preset(2),
+                               Slot::Page(CycleDir::Next)]);
+        let end = row_slots(count, 4, max_first(count, 4));
+        assert_eq!(end, vec![Slot::Page(CycleDir::Previous), Slot::Preset(7),
+                             Slot::Preset(8), Slot::Preset(9)]);
+        // Scrolling past the end is clamped.
+        assert_eq!(row_slots(count, 4, count), end);
+    }
+
+    #[test]
+    fn middle_of_the_row() {
+        assert_eq!(row_slots(10, 4, 4), vec![Slot::Page(CycleDir::Previous), Slot::Preset(4),
+                                             Slot::Preset(5), Slot::Page(CycleDir::Next)]);
+    }
+
+    #[test]
+    fn break_rows_at_various_widths() {
+        let widths = [50, 50, 50, 50, 50];
+        assert_eq!(break_rows(&widths, 300, 10), vec![vec![0, 1, 2, 3, 4]]);
+        assert_eq!(break_rows(&widths, 290, 10), vec![vec![0, 1, 2, 3], vec![4]]);
+        assert_eq!(break_rows(&widths, 110, 10), vec![vec![0, 1], vec![2, 3], vec![4]]);
+        assert_eq!(break_rows(&widths, 40, 10), vec![vec![0], vec![1], vec![2], vec![3], vec![4]]);
+        assert_eq!(break_rows(&[30, 80, 20], 100, 10), vec![vec![0], vec![1], vec![2]]);
+        assert!(break_rows(&[], 100, 10).is_empty());
+    }
+
+    #[test]
+    fn paginate_wrapped_rows() {
+        let widths = [50; 7];
+        let pages = page_rows(&widths, 170, 10, 20, 3);
+        assert_eq!(pages.len(), 1);
+        assert_eq!(pages[0], break_rows(&widths, 170, 10).into_iter()
+                                 .map(|row| row.into_iter().map(Slot::Preset).collect::<Vec<_>>())
+                                 .collect::<Vec<_>>());
+        let pages = page_rows(&widths, 110, 10, 20, 2);
+        assert_eq!(pages, vec![vec![vec![Slot::Preset(0), Slot::Preset(1)],
+                                    vec![Slot::Preset(2), Slot::Page(CycleDir::Next)]],
+                               vec![vec![Slot::Page(CycleDir::Previous), Slot::Preset(3)],
+                                    vec![Slot::Preset(4), Slot::Page(CycleDir::Next)]],
+                               vec![vec![Slot::Page(CycleDir::Previous), Slot::Preset(5)],
+                                    vec![Slot::Preset(6)]]]);
+        // A single row per page still moves through every preset.
+        let pages = page_rows(&[100; 3], 110, 10, 20, 1);
+        assert_eq!(pages, vec![vec![vec![Slot::Preset(0), Slot::Page(CycleDir::Next)]],
+                               vec![vec![Slot::Page(CycleDir::Previous), Slot::Preset(1), Slot::Page(CycleDir::Next)]],
+                               vec![vec![Slot::Page(CycleDir::Previous), Slot::Preset(2)]]]);
+        assert!(page_rows(&[], 100, 10, 20, 2).is_empty());
+    }
+
+    #[test]
+    fn sort_without_reordering() {
+        let preset = |name: Option<&str>, timestamp| {
+            LightPreset { name: name.map(String::from), timestamp, .. Default::default() }
+        };
+        let presets = vec![preset(Some("reading"), 600), preset(None, 30),
+                           preset(Some("Night"), 1320), preset(Some("night"), 60)];
+        assert_eq!(sorted_indices(&presets, PresetsSort::Stored), vec![0, 1, 2, 3]);
+        assert_eq!(sorted_indices(&presets, PresetsSort::Name), vec![1, 2, 3, 0]);
+        assert_eq!(sorted_indices(&presets, PresetsSort::Time), vec![1, 3, 0, 2]);
+    }
+
+    #[test]
+    fn short_swipes_keep_the_page() {
+        assert_eq!(swipe_page(Dir::West, pt!(200, 10), pt!(100, 20), 50), Some(CycleDir::Next));
+        assert_eq!(swipe_page(Dir::East, pt!(100, 10), pt!(200, 10), 50), Some(CycleDir::Previous));
+        assert_eq!(swipe_page(Dir::West, pt!(200, 10), pt!(170, 10), 50), None);
+        assert_eq!(swipe_page(Dir::South, pt!(100, 10), pt!(100, 200), 50), None);
+    }
+
+    #[test]
+    fn drop_on_the_nearest_preset() {
+        let centers = [(2, pt!(50, 10)), (3, pt!(150, 10)), (4, pt!(250, 10))];
+        assert_eq!(drop_index(&centers, &pt!(140, 12)), Some(3));
+        assert_eq!(drop_index(&centers, &pt!(-80, 10)), Some(2));
+        assert_eq!(drop_index(&centers, &pt!(900, 40)), Some(4));
+        assert_eq!(drop_index(&[], &pt!(0, 0)), None);
     }
 }
@@ -2,7 +2,7 @@ use framebuffer::{Framebuffer, UpdateMode, Pixmap};
 use metadata::Margin;
 use gesture::GestureEvent;
 use font::Fonts;
-use geom::{Rectangle, Point, CornerSpec, BorderSpec};
+use geom::{Rectangle, Point, CornerSpec, BorderSpec, BorderStyle};
 use view::{View, Event, Hub, Bus, ViewId};
 use view::THICKNESS_MEDIUM;
 use view::rounded_button::RoundedButton;
@@ -12,6 +12,8 @@ use device::{CURRENT_DEVICE, BAR_SIZES};
 use app::Context;
 
 pub const BUTTON_DIAMETER: f32 = 30.0;
+// The opacity of the shade over the cropped margins.
+const SHADE_ALPHA: u8 = 102;
 
 pub struct MarginCropper {
     rect: Rectangle,
@@ -159,26 +161,28 @@ impl View for MarginCropper {
 
         let thickness = scale_by_dpi(THICKNESS_MEDIUM, dpi) as u16;
 
-        fb.draw_blended_rectangle(&rect![self.rect.min.x, self.rect.min.y,
-                                         self.frame.min.x, self.frame.max.y],
-                                  GRAY12,
-                                  0.4);
-        fb.draw_blended_rectangle(&rect![self.rect.min.x, self.frame.max.y,
-                                         self.frame.max.x, self.rect.max.y],
-                                  GRAY12,
-                                  0.4);
-        fb.draw_blended_rectangle(&rect![self.frame.max.x, self.frame.min.y,
-                                         self.rect.max.x, self.rect.max.y],
-                                  GRAY12,
-                                  0.4);
-        fb.draw_blended_rectangle(&rect![self.frame.min.x, self.rect.min.y,
-                                         self.rect.max.x, self.frame.min.y],
-                                  GRAY12,
-                                  0.4);
+        fb.blend_rectangle(&rect![self.rect.min.x, self.rect.min.y,
+                                  self.frame.min.x, self.frame.max.y],
+                           GRAY12,
+                           SHADE_ALPHA);
+        fb.blend_rectangle(&rect![self.rect.min.x, self.frame.max.y,
+                                  self.frame.max.x, self.rect.max.y],
+                           GRAY12,
+                           SHADE_ALPHA);
+        fb.blend_rectangle(&rect![self.frame.max.x, self.frame.min.y,
+                                  self.rect.max.x, self.rect.max.y],
+                           GRAY12,
+                           SHADE_ALPHA);
+        fb.blend_rectangle(&rect![self.frame.min.x, self.rect.min.y,
+                                  self.rect.max.x, self.frame.min.y],
+                           GRAY12,
+                           SHADE_ALPHA);
 
         fb.draw_rectangle_outline(&self.frame,
                                   &BorderSpec { thickness: thickness as u16,
-                                                color: BLACK });
+                                                color: BLACK,
+                                                style: BorderStyle::Dashed { on: 3 * thickness,
+                                                                             off: 2 * thickness } });
 
         let button_radius = scale_by_dpi(BUTTON_DIAMETER / 2.0, dpi) as i32;
 
@@ -196,7 +200,8 @@ impl View for MarginCropper {
                 fb.draw_rounded_rectangle_with_border(&button_rect,
                                                       &CornerSpec::Uniform(button_radius),
                                                       &BorderSpec { thickness: thickness as u16,
-                                                                    color: BLACK },
+                                                                    color: BLACK,
+                                                                    style: BorderStyle::Solid },
                                                       &WHITE);
             }
         }
@@ -14,31 +14,33 @@ use std::path::PathBuf;
 use std::collections::{BTreeMap, BTreeSet, VecDeque};
 use chrono::Local;
 use regex::Regex;
-use input::FingerStatus;
-use framebuffer::{Framebuffer, UpdateMode, Pixmap};
+use input::{DeviceEvent, FingerStatus};
+use framebuffer::{Framebuffer, UpdateMode, Pixmap, Dithering};
 use view::{View, Event, Hub, ViewId, EntryKind, EntryId, SliderId, Bus, THICKNESS_MEDIUM};
 use unit::{scale_by_dpi, pt_to_px, mm_to_in};
 use device::{CURRENT_DEVICE, BAR_SIZES};
+use lightsensor::calibrated_level;
 use font::{Fonts, DEFAULT_FONT_SIZE};
 use self::margin_cropper::{MarginCropper, BUTTON_DIAMETER};
 use self::top_bar::TopBar;
 use self::tool_bar::ToolBar;
 use self::bottom_bar::BottomBar;
 use self::results_bar::ResultsBar;
-use view::common::{locate, locate_by_id, toggle_main_menu, shift};
+use view::common::{locate, locate_by_id, toggle_main_menu, main_menu_entries, shift, topmost_overlay};
 use view::filler::Filler;
 use view::named_input::NamedInput;
 use view::search_bar::SearchBar;
 use view::keyboard::{Keyboard, DEFAULT_LAYOUT};
 use view::menu::{Menu, MenuKind};
 use view::notification::Notification;
-use settings::{guess_frontlight, FinishedAction};
-use frontlight::LightLevels;
+use view::slider::Slider;
+use settings::{guess_frontlight, steady_lightsensor_level, FinishedAction, EdgeSide};
+use frontlight::{LightLevels, floored_intensity, intensity_from_position, position_from_intensity};
 use gesture::GestureEvent;
 use document::{Document, TocEntry, open, toc_as_html, chapter_at, chapter_relative};
 use document::pdf::PdfOpener;
 use metadata::{Info, FileInfo, ReaderInfo, PageScheme, Margin, CroppingMargins, make_query};
-use geom::{Rectangle, CornerSpec, BorderSpec, Dir, CycleDir, LinearDir, halves};
+use geom::{Point, Rectangle, CornerSpec, BorderSpec, BorderStyle, Axis, Dir, CycleDir, LinearDir, halves};
 use color::{BLACK, WHITE};
 use app::Context;
 
@@ -62,6 +64,21 @@ pub struct Reader {
     focus: Option<ViewId>,
     search: Option<Search>,
     history: VecDeque<usize>,
+    edge_drag: Option<EdgeDrag>,
+    // The levels to restore when the book is closed, if the book has its own.
+    global_levels: Option<LightLevels>,
+    // Whether the frontlight levels were changed while the book was open.
+    pub frontlight_adjusted: bool,
+    dithering: Option<Dithering>,
+}
+
+// A finger sliding along the edge of the screen to change the frontlight intensity.
+#[derive(Debug, Copy, Clone)]
+struct EdgeDrag {
+    id: i32,
+    origin: Point,
+    intensity: f32,
+    engaged: bool,
 }
 
 struct Search {
@@ -86,8 +103,7 @@ impl Default for Search {
 
 impl Reader {
     pub fn new(rect: Rectangle, mut info: Info, hub: &Hub, context: &mut Context) -> Option<Reader> {
-        let settings = &context.settings;
-        let path = settings.library_path.join(&info.file.path);
+        let path = context.settings.library_path.join(&info.file.path);
 
         open(&path).map(|mut doc| {
             let (width, height) = CURRENT_DEVICE.dims;
@@ -120,6 +136,20 @@ impl Reader {
 
             println!("{}", info.file.path.display());
 
+            // The ambient light might have changed since the last readings.
+            context.lightsensor.reset();
+
+            let book_levels = info.reader.as_ref().and_then(|r| r.frontlight_levels);
+            let global_levels = match book_levels {
+                Some(levels) if context.settings.frontlight => {
+                    let global_levels = context.frontlight.levels();
+                    context.frontlight.set_intensity(levels.intensity);
+                    context.frontlight.set_warmth(levels.warmth);
+                    Some(global_levels)
+                },
+                _ => None,
+            };
+
             let margin = info.reader.as_ref()
                              .and_then(|r| r.cropping_margins.as_ref()
                                             .map(|c| c.margin(current_page)))
@@ -144,13 +174,17 @@ impl Reader {
                 page_turns: 0,
                 finished: false,
                 ephemeral: false,
-                refresh_every: settings.refresh_every,
+                refresh_every: context.settings.refresh_every,
                 search_direction: LinearDir::Forward,
                 frame,
                 scale,
                 focus: None,
                 search: None,
                 history: VecDeque::new(),
+                edge_drag: None,
+                global_levels,
+                frontlight_adjusted: false,
+                dithering: context.settings.reader.dithering,
             }
         })
     }
@@ -204,6 +238,10 @@ impl Reader {
             focus: None,
             search: None,
             history: VecDeque::new(),
+            edge_drag: None,
+            global_levels: None,
+            frontlight_adjusted: false,
+            dithering: context.settings.reader.dithering,
         }
     }
 
@@ -636,6 +674,7 @@ impl Reader {
             let top_bar = TopBar::new(rect![self.rect.min.x, self.rect.min.y,
                                             self.rect.max.x, small_height as i32 - small_thickness],
                                       &self.info,
+                                      hub,
                                       context);
 
             self.children.insert(index, Box::new(top_bar) as Box<View>);
@@ -995,8 +1034,7 @@ impl Reader {
         if let Some(index) = locate::<TopBar>(self) {
             self.child_mut(index).downcast_mut::<TopBar>().unwrap()
                 .update_frontlight_icon(&tx, context);
-            hub.send(Event::ClockTick).unwrap();
-            hub.send(Event::BatteryTick).unwrap();
+            hub.send(Event::RefreshStatus).unwrap();
         }
         hub.send(Event::Render(self.rect, UpdateMode::Gui)).unwrap();
     }
@@ -1014,6 +1052,17 @@ impl Reader {
             r.current_page = self.current_page;
             r.pages_count = self.pages_count;
             r.finished = self.finished;
+            // The books that don't have their own levels only get them once they're changed.
+            if context.settings.frontlight && (self.global_levels.is_some() || self.frontlight_adjusted) {
+                r.frontlight_levels = Some(context.frontlight.levels());
+            }
+        }
+
+        if context.settings.frontlight {
+            if let Some(levels) = self.global_levels.take() {
+                context.frontlight.set_intensity(levels.intensity);
+                context.frontlight.set_warmth(levels.warmth);
+            }
         }
 
         for i in &mut context.metadata {
@@ -1023,12 +1072,116 @@ impl Reader {
             }
         }
     }
+
+    // Whether the frontlight levels of the book are in effect.
+    pub fn overrides_frontlight(&self) -> bool {
+        self.global_levels.is_some()
+    }
+
+    // Returns whether the finger event was consumed by the drag.
+    // Shows the position of the frontlight intensity along the edge while it's being dragged.
+    fn update_edge_slider(&mut self, value: f32, hub: &Hub, context: &Context) {
+        if let Some(index) = locate::<Slider>(self) {
+            let slider = self.children[index].as_mut().downcast_mut::<Slider>().unwrap();
+            slider.value = value;
+            hub.send(Event::RenderNoWait(*slider.rect(), UpdateMode::FastMono)).unwrap();
+        } else {
+            let dpi = CURRENT_DEVICE.dpi as f32;
+            let settings = &context.settings.frontlight_edge_drag;
+            let width = (dpi * mm_to_in(settings.width)) as i32;
+            let rect = match settings.side {
+                EdgeSide::Left => rect![self.rect.min.x, self.rect.min.y,
+                                        self.rect.min.x + width, self.rect.max.y],
+                EdgeSide::Right => rect![self.rect.max.x - width, self.rect.min.y,
+                                         self.rect.max.x, self.rect.max.y],
+            };
+            let slider = Slider::new(rect, SliderId::LightIntensity, value, 0.0, 100.0)
+                                .orientation(Axis::Vertical);
+            hub.send(Event::Render(rect, UpdateMode::Gui)).unwrap();
+            self.children.push(Box::new(slider) as Box<View>);
+        }
+    }
+
+    fn hide_edge_slider(&mut self, hub: &Hub) {
+        if let Some(index) = locate::<Slider>(self) {
+            hub.send(Event::Expose(*self.child(index).rect())).unwrap();
+            self.children.remove(index);
+        }
+    }
+
+    fn handle_edge_drag(&mut self, id: i32, status: FingerStatus, position: Point, hub: &Hub, context: &mut Context) -> bool {
+        let dpi = CURRENT_DEVICE.dpi as f32;
+        let settings = &context.settings.frontlight_edge_drag;
+        match status {
+            FingerStatus::Down => {
+                let width = (dpi * mm_to_in(settings.width)) as i32;
+                let in_strip = match settings.side {
+                    EdgeSide::Left => position.x < self.rect.min.x + width,
+                    EdgeSide::Right => position.x >= self.rect.max.x - width,
+                };
+                self.edge_drag = if in_strip && self.rect.includes(&position) && topmost_overlay(self).is_none() {
+                    Some(EdgeDrag { id, origin: position, intensity: context.frontlight.levels().intensity, engaged: false })
+                } else {
+                    None
+                };
+                false
+            },
+            FingerStatus::Motion => {
+                let min_length = dpi * mm_to_in(settings.min_length);
+                let min_intensity = context.settings.frontlight_min_intensity;
+                let gamma = context.settings.frontlight_gamma;
+                let mut drag = match self.edge_drag {
+                    Some(drag) if drag.id == id => drag,
+                    _ => return false,
+                };
+                let dy = (drag.origin.y - position.y) as f32;
+                if !drag.engaged && dy.abs() >= min_length {
+                    drag.engaged = true;
+                }
+                self.edge_drag = Some(drag);
+                if drag.engaged {
+                    // Sliding along the whole height spans the whole intensity range.
+                    let delta = 100.0 * dy / self.rect.height() as f32;
+                    let position = (position_from_intensity(drag.intensity, gamma) + delta).min(100.0);
+                    let intensity = floored_intensity(intensity_from_position(position, gamma), min_intensity);
+                    context.frontlight.set_intensity(intensity);
+                    self.frontlight_adjusted = true;
+                    self.update_edge_slider(position_from_intensity(intensity, gamma), hub, context);
+                }
+                drag.engaged
+            },
+            FingerStatus::Up => {
+                // An engaged drag is forgotten once the resulting swipe is swallowed.
+                match self.edge_drag {
+                    Some(drag) if drag.id == id && drag.engaged => {
+                        self.hide_edge_slider(hub);
+                        true
+                    },
+                    _ => {
+                        self.edge_drag = None;
+                        false
+                    },
+                }
+            },
+        }
+    }
 }
 
 impl View for Reader {
     fn handle_event(&mut self, evt: &Event, hub: &Hub, _bus: &mut Bus, context: &mut Context) -> bool {
         match *evt {
+            Event::Device(DeviceEvent::Finger { id, status, ref position, .. }) if context.settings.frontlight &&
+                                                                                context.settings.frontlight_edge_drag.enabled => {
+                self.handle_edge_drag(id, status, *position, hub, context)
+            },
+            Event::Gesture(GestureEvent::Swipe { .. }) if self.edge_drag.map_or(false, |d| d.engaged) => {
+                self.edge_drag = None;
+                true
+            },
             Event::Gesture(GestureEvent::Swipe { dir, ref start, .. }) if self.rect.includes(start) => {
+                if topmost_overlay(self).is_some() {
+                    return true;
+                }
                 match dir {
                     Dir::West => self.set_current_page(CycleDir::Next, hub, context),
                     Dir::East => self.set_current_page(CycleDir::Previous, hub, context),
@@ -1150,15 +1303,16 @@ impl View for Reader {
                     } else if center.y > self.rect.max.y - dx {
                         if context.settings.frontlight_presets.len() > 1 {
                             if context.settings.frontlight {
-                                let lightsensor_level = if CURRENT_DEVICE.has_lightsensor() {
-                                    context.lightsensor.level().ok()
-                                } else {
-                                    None
-                                };
+                                let lightsensor_level = context.lightsensor.level().ok().map(|level| {
+                                    let level = calibrated_level(level, &context.settings.lightsensor_calibration);
+                                    steady_lightsensor_level(level, context.lightsensor_level, context.settings.lightsensor_hysteresis)
+                                });
+                                context.lightsensor_level = lightsensor_level;
                                 if let Some(ref frontlight_levels) = guess_frontlight(lightsensor_level, &context.settings.frontlight_presets) {
                                     let LightLevels { intensity, warmth } = *frontlight_levels;
                                     context.frontlight.set_intensity(intensity);
                                     context.frontlight.set_warmth(warmth);
+                                    self.frontlight_adjusted = true;
                                 }
                             }
                         } else {
@@ -1272,7 +1426,7 @@ impl View for Reader {
                 true
             },
             Event::ToggleNear(ViewId::MainMenu, rect) => {
-                toggle_main_menu(self, rect, None, hub, context);
+                toggle_main_menu(self, rect, None, hub, context, main_menu_entries);
                 true
             },
             Event::ToggleNear(ViewId::MarginCropperMenu, rect) => {
@@ -1288,7 +1442,7 @@ impl View for Reader {
                 true
             },
             Event::Close(ViewId::MainMenu) => {
-                toggle_main_menu(self, Rectangle::default(), Some(false), hub, context);
+                toggle_main_menu(self, Rectangle::default(), Some(false), hub, context, main_menu_entries);
                 true
             },
             Event::Close(ViewId::SearchBar) => {
@@ -1396,6 +1550,14 @@ impl View for Reader {
                 self.reseed(hub, context);
                 true
             },
+            // The icon only reflects the toggles made by holding it.
+            Event::Select(EntryId::ToggleFrontlight) => {
+                if let Some(index) = locate::<TopBar>(self) {
+                    self.child_mut(index).downcast_mut::<TopBar>().unwrap()
+                        .update_frontlight_icon(hub, context);
+                }
+                true
+            },
             Event::Select(EntryId::Quit) |
             Event::Select(EntryId::Reboot) |
             Event::Select(EntryId::StartNickel) |
@@ -1415,9 +1577,7 @@ impl View for Reader {
                     _ => ()
                 }
                 self.focus = v;
-                if v.is_some() {
-                    self.toggle_keyboard(true, v, hub);
-                }
+                self.toggle_keyboard(v.is_some(), v, hub);
                 true
             },
             _ => false,
@@ -1429,7 +1589,10 @@ impl View for Reader {
         let dy = (self.rect.height() - self.frame.height()) as i32 / 2;
 
         fb.draw_rectangle(&self.rect, WHITE);
-        fb.draw_framed_pixmap(&self.pixmap, &self.frame, &pt!(dx, dy));
+        match self.dithering {
+            Some(dithering) => fb.draw_dithered_pixmap(&self.pixmap, &self.frame, &pt!(dx, dy), dithering),
+            None => fb.draw_framed_pixmap(&self.pixmap, &self.frame, &pt!(dx, dy)),
+        }
 
         if let Some(rects) = self.search.as_ref().and_then(|s| s.highlights.get(&self.current_page)) {
             let dx = (self.rect.width() - self.frame.width()) as i32 / 2;
@@ -1459,7 +1622,9 @@ impl View for Reader {
                              self.rect.min.y + 5 * radius);
             fb.draw_rounded_rectangle_with_border(&Rectangle::from_disk(&center, radius),
                                                   &CornerSpec::Uniform(radius),
-                                                  &BorderSpec { thickness, color: WHITE },
+                                                  &BorderSpec { thickness,
+                                                                color: WHITE,
+                                                                style: BorderStyle::Solid },
                                                   &BLACK);
         }
     }
@@ -6,7 +6,7 @@ use view::{View, Event, Hub, Bus, ViewId, Align};
 use view::icon::Icon;
 use view::clock::Clock;
 use view::battery::Battery;
-use view::label::Label;
+use view::label::{Label, Truncation};
 use geom::{Rectangle};
 use font::Fonts;
 use app::Context;
@@ -18,7 +18,7 @@ pub struct TopBar {
 }
 
 impl TopBar {
-    pub fn new(rect: Rectangle, info: &Info, context: &mut Context) -> TopBar {
+    pub fn new(rect: Rectangle, info: &Info, hub: &Hub, context: &mut Context) -> TopBar {
         let mut children = Vec::new();
         let fonts = &mut context.fonts;
 
@@ -33,10 +33,15 @@ impl TopBar {
         let clock_label = Clock::new(&mut clock_rect, fonts);
         children.push(Box::new(clock_label) as Box<View>);
 
-        let title_label = Label::new(rect![rect.min.x + side, rect.min.y,
-                                           clock_rect.min.x, rect.max.y],
-                                     info.title(),
-                                     Align::Center);
+        // Books without a title are named after their file.
+        let truncation = if info.title.is_empty() { Truncation::Middle } else { Truncation::Ellipsis };
+        let mut title_label = Label::new(rect![rect.min.x + side, rect.min.y,
+                                               clock_rect.min.x, rect.max.y],
+                                         info.title(),
+                                         Align::Center).truncation(truncation);
+        if context.settings.reader.scroll_title && !context.settings.reduce_motion {
+            title_label.start_marquee(hub, fonts);
+        }
         children.push(Box::new(title_label) as Box<View>);
 
         let capacity = context.battery.capacity().unwrap_or(0.0);
@@ -48,10 +53,12 @@ impl TopBar {
         children.push(Box::new(battery_widget) as Box<View>);
 
         let name = if context.settings.frontlight { "frontlight" } else { "frontlight-disabled" };
+        let frontlight_rect = rect![rect.max - pt!(2*side, side),
+                                    rect.max - pt!(side, 0)];
+        // The window is placed near the icon when it's anchored.
         let frontlight_icon = Icon::new(name,
-                                        rect![rect.max - pt!(2*side, side),
-                                              rect.max - pt!(side, 0)],
-                                        Event::Show(ViewId::Frontlight));
+                                        frontlight_rect,
+                                        Event::ShowNear(ViewId::Frontlight, frontlight_rect.center()));
         children.push(Box::new(frontlight_icon) as Box<View>);
 
         let menu_rect = rect![rect.max-side, rect.max];
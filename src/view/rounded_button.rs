@@ -8,7 +8,7 @@ use input::{DeviceEvent, FingerStatus};
 use unit::scale_by_dpi;
 use font::Fonts;
 use app::Context;
-use geom::{Rectangle, CornerSpec, BorderSpec};
+use geom::{Rectangle, CornerSpec, BorderSpec, BorderStyle};
 use color::{TEXT_NORMAL, TEXT_INVERTED_HARD};
 
 pub struct RoundedButton {
@@ -76,7 +76,8 @@ impl View for RoundedButton {
         fb.draw_rounded_rectangle_with_border(&self.rect,
                                               &CornerSpec::Uniform(button_radius),
                                               &BorderSpec { thickness: thickness as u16,
-                                                            color: scheme[1] },
+                                                            color: scheme[1],
+                                                            style: BorderStyle::Solid },
                                               &scheme[0]);
 
         fb.draw_blended_pixmap(pixmap, &pt, scheme[1]);
@@ -1,15 +1,29 @@
 use device::CURRENT_DEVICE;
 use unit::scale_by_dpi;
 use framebuffer::{Framebuffer, UpdateMode};
-use input::{DeviceEvent, FingerStatus};
+use input::{DeviceEvent, FingerStatus, ButtonCode, ButtonStatus, KEY_FORWARD, KEY_BACKWARD};
+use gesture::GestureEvent;
 use view::{View, Event, Hub, Bus, SliderId, THICKNESS_SMALL};
-use color::{BLACK, WHITE, PROGRESS_VALUE, PROGRESS_FULL, PROGRESS_EMPTY};
+use color::{BLACK, WHITE, PROGRESS_VALUE, PROGRESS_FULL, PROGRESS_EMPTY, PROGRESS_FLOOR};
 use font::{Fonts, font_from_style, SLIDER_VALUE};
-use geom::{Rectangle, BorderSpec, CornerSpec, halves};
+use geom::{Point, Rectangle, Axis, BorderSpec, BorderStyle, CornerSpec, halves};
 use app::Context;
 
 const PROGRESS_HEIGHT: f32 = 7.0;
 const BUTTON_DIAMETER: f32 = 46.0;
+const MARKER_LENGTH: f32 = 5.0;
+const MARKER_SNAP_DISTANCE: f32 = 12.0;
+const TICK_LENGTH: f32 = 3.0;
+const MIN_TICK_SPACING: f32 = 4.0;
+// The step of the page turn buttons when the slider is continuous.
+const BUTTON_STEP: f32 = 1.0;
+
+// How the values are spread along the slider.
+#[derive(Debug, Copy, Clone, Eq, PartialEq)]
+pub enum SliderScale {
+    Linear,
+    Logarithmic,
+}
 
 pub struct Slider {
     rect: Rectangle,
@@ -18,8 +32,20 @@ pub struct Slider {
     pub value: f32,
     min_value: f32,
     max_value: f32,
+    orientation: Axis,
+    scale: SliderScale,
+    markers: Vec<f32>,
+    // The number of evenly spaced tick marks drawn below the track.
+    ticks: Option<u32>,
+    floor: Option<f32>,
+    // The values are multiples of the step, when it's given.
+    step: Option<f32>,
+    // The value restored by a double tap.
+    default_value: Option<f32>,
+    // The page turn buttons move the value of the last touched slider.
+    focused: bool,
     active: bool,
-    last_x: i32,
+    last_hit: i32,
 }
 
 impl Slider {
@@ -31,21 +57,177 @@ impl Slider {
             value,
             min_value,
             max_value,
+            orientation: Axis::Horizontal,
+            scale: SliderScale::Linear,
+            markers: Vec::new(),
+            ticks: None,
+            floor: None,
+            step: None,
+            default_value: None,
+            focused: false,
             active: false,
-            last_x: -1,
+            last_hit: -1,
+        }
+    }
+
+    // The maximum of a vertical slider is at the top.
+    pub fn orientation(mut self, orientation: Axis) -> Slider {
+        self.orientation = orientation;
+        self
+    }
+
+    // The logarithmic scale requires a positive minimum.
+    pub fn scale(mut self, scale: SliderScale) -> Slider {
+        if scale == SliderScale::Linear || self.min_value > 0.0 {
+            self.scale = scale;
+        }
+        self
+    }
+
+    // The position of the given value along the slider, in [0, 1].
+    fn progress(&self, value: f32) -> f32 {
+        match self.scale {
+            SliderScale::Linear => (value - self.min_value) / (self.max_value - self.min_value),
+            SliderScale::Logarithmic => (value.max(self.min_value) / self.min_value).ln() /
+                                        (self.max_value / self.min_value).ln(),
+        }
+    }
+
+    fn value_at(&self, progress: f32) -> f32 {
+        match self.scale {
+            SliderScale::Linear => self.min_value + progress * (self.max_value - self.min_value),
+            SliderScale::Logarithmic => self.min_value * (self.max_value / self.min_value).powf(progress),
+        }
+    }
+
+    pub fn ticks(mut self, count: u32) -> Slider {
+        self.ticks = Some(count);
+        self
+    }
+
+    pub fn markers(mut self, markers: Vec<f32>) -> Slider {
+        self.markers = markers;
+        self
+    }
+
+    // Values between the minimum and the floor can't be selected.
+    pub fn floor(mut self, value: f32) -> Slider {
+        if value > self.min_value {
+            self.floor = Some(value);
+            self.value = self.floored(self.value);
+        }
+        self
+    }
+
+    pub fn step(mut self, step: f32) -> Slider {
+        if step > 0.0 {
+            self.step = Some(step);
+        }
+        self
+    }
+
+    pub fn default_value(mut self, value: f32) -> Slider {
+        self.default_value = Some(value);
+        self
+    }
+
+    fn snapped(&self, value: f32) -> f32 {
+        match self.step {
+            Some(step) => ((value / step).round() * step).max(self.min_value).min(self.max_value),
+            None => value,
         }
     }
 
-    pub fn update_value(&mut self, x_hit: i32) {
+    fn floored(&self, value: f32) -> f32 {
+        match self.floor {
+            Some(floor) if value > self.min_value => value.max(floor),
+            _ => value,
+        }
+    }
+
+    pub fn set_markers(&mut self, markers: Vec<f32>) {
+        self.markers = markers;
+    }
+
+    // Moves the value by *delta*, within the bounds of the slider, and returns it.
+    // The value moves by at least one step. Moving down from the floor jumps to the minimum.
+    pub fn nudge(&mut self, delta: f32) -> f32 {
+        let delta = self.step.map_or(delta, |step| delta.signum() * step.max(delta.abs()));
+        let value = self.snapped(self.value + delta).max(self.min_value).min(self.max_value);
+        self.value = match self.floor {
+            Some(floor) if value < floor && delta < 0.0 && self.value <= floor => self.min_value,
+            _ => self.floored(value),
+        };
+        self.value
+    }
+
+    // The position of the center of the button, along the slider, for the given value.
+    fn offset(&self, value: f32) -> i32 {
         let dpi = CURRENT_DEVICE.dpi;
         let button_diameter = scale_by_dpi(BUTTON_DIAMETER, dpi) as i32;
         let (small_radius, big_radius) = halves(button_diameter);
-        let x_offset = x_hit.max(self.rect.min.x + small_radius)
-                            .min(self.rect.max.x - big_radius);
-        let progress = ((x_offset - self.rect.min.x - small_radius) as f32 /
-                        (self.rect.width() as i32 - button_diameter) as f32)
+        let (min, max) = self.main_bounds();
+        let distance = ((max - min - button_diameter) as f32 * self.progress(value)) as i32;
+        match self.orientation {
+            Axis::Horizontal => min + small_radius + distance,
+            Axis::Vertical => max - big_radius - distance,
+        }
+    }
+
+    fn main_bounds(&self) -> (i32, i32) {
+        match self.orientation {
+            Axis::Horizontal => (self.rect.min.x, self.rect.max.x),
+            Axis::Vertical => (self.rect.min.y, self.rect.max.y),
+        }
+    }
+
+    fn cross_bounds(&self) -> (i32, i32) {
+        match self.orientation {
+            Axis::Horizontal => (self.rect.min.y, self.rect.max.y),
+            Axis::Vertical => (self.rect.min.x, self.rect.max.x),
+        }
+    }
+
+    // Builds a rectangle from its bounds along and across the slider.
+    fn oriented(&self, main: (i32, i32), cross: (i32, i32)) -> Rectangle {
+        match self.orientation {
+            Axis::Horizontal => rect![main.0, cross.0, main.1, cross.1],
+            Axis::Vertical => rect![cross.0, main.0, cross.1, main.1],
+        }
+    }
+
+    fn hit(&self, position: &Point) -> i32 {
+        match self.orientation {
+            Axis::Horizontal => position.x,
+            Axis::Vertical => position.y,
+        }
+    }
+
+    pub fn update_value(&mut self, hit: i32) {
+        let dpi = CURRENT_DEVICE.dpi;
+        let button_diameter = scale_by_dpi(BUTTON_DIAMETER, dpi) as i32;
+        let (small_radius, big_radius) = halves(button_diameter);
+        let (min, max) = self.main_bounds();
+        let offset = hit.max(min + small_radius)
+                        .min(max - big_radius);
+        let distance = match self.orientation {
+            Axis::Horizontal => offset - min - small_radius,
+            Axis::Vertical => max - big_radius - offset,
+        };
+        let progress = (distance as f32 / (max - min - button_diameter) as f32)
                        .min(1.0).max(0.0);
-        self.value = self.min_value + progress * (self.max_value - self.min_value);
+        self.value = self.value_at(progress);
+
+        let snap_distance = scale_by_dpi(MARKER_SNAP_DISTANCE, dpi) as i32;
+        let nearest = self.markers.iter().cloned()
+                          .map(|m| (m, (self.offset(m) - offset).abs()))
+                          .filter(|&(_, d)| d <= snap_distance)
+                          .min_by_key(|&(_, d)| d);
+        if let Some((marker, _)) = nearest {
+            self.value = marker;
+        }
+
+        self.value = self.floored(self.snapped(self.value));
     }
 }
 
@@ -56,32 +238,61 @@ impl View for Slider {
                 match status {
                     FingerStatus::Down if self.rect.includes(position) => {
                         self.active = true;
-                        self.update_value(position.x);
+                        self.focused = true;
+                        let hit = self.hit(position);
+                        self.update_value(hit);
                         hub.send(Event::Render(self.rect, UpdateMode::Gui)).unwrap();
                         bus.push_back(Event::Slider(self.id, self.value, status));
-                        self.last_x = position.x;
+                        self.last_hit = hit;
                         true
                     },
-                    FingerStatus::Motion if self.active && position.x != self.last_x => {
-                        self.update_value(position.x);
+                    FingerStatus::Motion if self.active && self.hit(position) != self.last_hit => {
+                        let hit = self.hit(position);
+                        self.update_value(hit);
                         hub.send(Event::RenderNoWait(self.rect, UpdateMode::FastMono)).unwrap();
                         bus.push_back(Event::Slider(self.id, self.value, status));
-                        self.last_x = position.x;
+                        self.last_hit = hit;
                         true
                     },
                     FingerStatus::Up if self.active => {
                         self.active = false;
-                        if position.x != self.last_x {
-                            self.update_value(position.x);
-                            self.last_x = position.x;
+                        let hit = self.hit(position);
+                        if hit != self.last_hit {
+                            self.update_value(hit);
+                            self.last_hit = hit;
                         }
                         hub.send(Event::Render(self.rect, UpdateMode::Gui)).unwrap();
                         bus.push_back(Event::Slider(self.id, self.value, status));
                         true
                     },
+                    FingerStatus::Down if self.focused => {
+                        self.focused = false;
+                        hub.send(Event::Render(self.rect, UpdateMode::Gui)).unwrap();
+                        false
+                    },
                     _ => self.active,
                 }
             },
+            Event::Gesture(GestureEvent::DoubleTap(ref center)) if self.rect.includes(center) => {
+                if let Some(value) = self.default_value {
+                    self.value = value;
+                    hub.send(Event::Render(self.rect, UpdateMode::Gui)).unwrap();
+                    bus.push_back(Event::Slider(self.id, value, FingerStatus::Up));
+                    true
+                } else {
+                    false
+                }
+            },
+            Event::Device(DeviceEvent::Button { code, status: ButtonStatus::Pressed, .. }) if self.focused => {
+                if let Some(delta) = button_delta(code, self.step.unwrap_or(BUTTON_STEP)) {
+                    let value = self.nudge(delta);
+                    hub.send(Event::Render(self.rect, UpdateMode::Gui)).unwrap();
+                    bus.push_back(Event::Slider(self.id, value, FingerStatus::Up));
+                    true
+                } else {
+                    false
+                }
+            },
             _ => false,
         }
     }
@@ -92,48 +303,119 @@ impl View for Slider {
         let button_diameter = scale_by_dpi(BUTTON_DIAMETER, dpi) as i32;
         let border_thickness = scale_by_dpi(THICKNESS_SMALL, dpi) as u16;
 
-        let progress = (self.value - self.min_value) / (self.max_value - self.min_value);
         let (small_radius, big_radius) = halves(button_diameter);
-        let x_offset = self.rect.min.x + small_radius +
-                       ((self.rect.width() as f32 - button_diameter as f32) * progress) as i32;
+        let (main_min, main_max) = self.main_bounds();
+        let (cross_min, cross_max) = self.cross_bounds();
+        let horizontal = self.orientation == Axis::Horizontal;
+        let offset = self.offset(self.value);
+        let floor_offset = self.floor.map_or(if horizontal { main_min } else { main_max },
+                                             |f| self.offset(f));
+        // Whether *a* comes before *b*, starting from the minimum.
+        let before = |a: i32, b: i32| if horizontal { a < b } else { a > b };
 
         fb.draw_rectangle(&self.rect, WHITE);
 
         let (small_mini_radius, big_mini_radius) = halves(progress_height);
-        let (small_padding, big_padding) = halves(self.rect.height() as i32 - progress_height);
-        let rect = rect![self.rect.min.x + small_radius - big_mini_radius, self.rect.min.y + small_padding,
-                         self.rect.max.x - big_radius + small_mini_radius, self.rect.max.y - big_padding];
+        let (small_padding, big_padding) = halves(cross_max - cross_min - progress_height);
+        let track = (cross_min + small_padding, cross_max - big_padding);
+        let rect = self.oriented((main_min + small_radius - big_mini_radius, main_max - big_radius + small_mini_radius),
+                                 track);
 
         fb.draw_rounded_rectangle_with_border(&rect,
                                               &CornerSpec::Uniform(small_mini_radius),
                                               &BorderSpec { thickness: border_thickness,
-                                                            color: BLACK },
-                                              &|x, _| if x < x_offset { PROGRESS_FULL }
-                                                      else { PROGRESS_EMPTY });
+                                                            color: BLACK,
+                                                            style: BorderStyle::Solid },
+                                              &|x, y| {
+                                                  let m = if horizontal { x } else { y };
+                                                  if before(m, floor_offset) { PROGRESS_FLOOR }
+                                                  else if before(m, offset) { PROGRESS_FULL }
+                                                  else { PROGRESS_EMPTY }
+                                              });
+
+        let (small_thickness, big_thickness) = halves(border_thickness as i32);
+
+        if !self.markers.is_empty() {
+            let marker_length = scale_by_dpi(MARKER_LENGTH, dpi) as i32;
+            for marker in &self.markers {
+                let m = self.offset(*marker);
+                fb.draw_rectangle(&self.oriented((m - small_thickness, m + big_thickness),
+                                                 (track.0 - marker_length, track.0)),
+                                  BLACK);
+                fb.draw_rectangle(&self.oriented((m - small_thickness, m + big_thickness),
+                                                 (track.1, track.1 + marker_length)),
+                                  BLACK);
+            }
+        }
+
+        if let Some(count) = self.ticks {
+            let tick_length = scale_by_dpi(TICK_LENGTH, dpi) as i32;
+            let min_spacing = scale_by_dpi(MIN_TICK_SPACING, dpi) as i32;
+            let span = main_max - main_min - button_diameter;
+            for m in tick_offsets(count, main_min + small_radius, span, min_spacing) {
+                fb.draw_rectangle(&self.oriented((m - small_thickness, m + big_thickness),
+                                                 (track.1, track.1 + tick_length)),
+                                  BLACK);
+            }
+        }
 
-        let (small_padding, big_padding) = halves(self.rect.height() as i32 - button_diameter);
-        let rect = rect![x_offset - small_radius, self.rect.min.y + small_padding,
-                         x_offset + big_radius, self.rect.max.y - big_padding];
+        let (small_padding, big_padding) = halves(cross_max - cross_min - button_diameter);
+        let rect = self.oriented((offset - small_radius, offset + big_radius),
+                                 (cross_min + small_padding, cross_max - big_padding));
         let fill_color = if self.active { BLACK } else { WHITE };
+        let button_thickness = if self.focused { 3 } else { 2 } * border_thickness;
 
         fb.draw_rounded_rectangle_with_border(&rect,
                                               &CornerSpec::Uniform(small_radius),
-                                              &BorderSpec { thickness: 2 * border_thickness,
-                                                            color: BLACK },
+                                              &BorderSpec { thickness: button_thickness,
+                                                            color: BLACK,
+                                                            style: BorderStyle::Solid },
                                               &fill_color);
 
         let font = font_from_style(fonts, &SLIDER_VALUE, dpi);
         let plan = font.plan(&format!("{:.1}", self.value), None, None);
         let x_height = font.x_heights.1 as i32;
+        let (width, _) = CURRENT_DEVICE.dims;
 
-        let x_drift = if self.value > (self.min_value + self.max_value) / 2.0 {
-            -(small_radius + plan.width as i32)
+        // While dragging, the value is shown in a bubble above the button.
+        if self.active && horizontal {
+            let padding = font.em() as i32 / 2;
+            let bubble_width = plan.width as i32 + 2 * padding;
+            let max_y = self.rect.min.y + small_padding + x_height;
+            let min_y = (max_y - 3 * x_height).max(self.rect.min.y);
+            let min_x = bubble_x(offset, bubble_width,
+                                 self.rect.min.x.max(0), self.rect.max.x.min(width as i32));
+            fb.draw_rounded_rectangle_with_border(&rect![min_x, min_y, min_x + bubble_width, max_y],
+                                                  &CornerSpec::Uniform(x_height),
+                                                  &BorderSpec { thickness: border_thickness,
+                                                                color: BLACK,
+                                                                style: BorderStyle::Solid },
+                                                  &WHITE);
+            let pt = pt!(min_x + padding, max_y - x_height);
+            font.render(fb, BLACK, &plan, &pt);
+            return;
+        }
+
+        let pt = if horizontal {
+            let x_drift = if self.progress(self.value) > 0.5 {
+                -(small_radius + plan.width as i32)
+            } else {
+                small_radius
+            };
+            pt!(offset + x_drift, self.rect.min.y + x_height.max(small_padding))
         } else {
-            small_radius
+            // The value is centered across the slider, on the emptier side of the button.
+            let x = bubble_x((cross_min + cross_max) / 2, plan.width as i32,
+                             cross_min.max(0), cross_max.min(width as i32));
+            let y = if self.progress(self.value) > 0.5 {
+                offset + big_radius + 2 * x_height
+            } else {
+                offset - small_radius - x_height
+            };
+            pt!(x, y)
         };
-
-        let pt = pt!(x_offset + x_drift, self.rect.min.y + x_height.max(small_padding));
-        font.render(fb, PROGRESS_VALUE, &plan, &pt);
+        let color = if self.active { BLACK } else { PROGRESS_VALUE };
+        font.render(fb, color, &plan, &pt);
     }
 
     fn rect(&self) -> &Rectangle {
@@ -152,3 +434,132 @@ impl View for Slider {
         &mut self.children
     }
 }
+
+// The abscissas of *count* ticks spread evenly over *span* pixels from *start_x*.
+// There are none if they'd be closer than *min_spacing*.
+fn tick_offsets(count: u32, start_x: i32, span: i32, min_spacing: i32) -> Vec<i32> {
+    if count < 2 || span / (count as i32 - 1) < min_spacing {
+        return Vec::new();
+    }
+    (0..count as i32).map(|i| start_x + span * i / (count as i32 - 1)).collect()
+}
+
+// The left edge of a bubble of the given width, centered on *x* but kept within the bounds.
+fn bubble_x(x: i32, width: i32, min_x: i32, max_x: i32) -> i32 {
+    (x - width / 2).min(max_x - width).max(min_x)
+}
+
+fn button_delta(code: ButtonCode, step: f32) -> Option<f32> {
+    match code {
+        ButtonCode::Raw(KEY_FORWARD) => Some(step),
+        ButtonCode::Raw(KEY_BACKWARD) => Some(-step),
+        _ => None,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use view::SliderId;
+    use input::{ButtonCode, KEY_FORWARD, KEY_BACKWARD};
+    use geom::Axis;
+    use super::{Slider, SliderScale, button_delta, bubble_x, tick_offsets};
+
+    #[test]
+    fn floor_is_respected() {
+        let mut slider = Slider::new(rect![0, 0, 400, 60], SliderId::LightIntensity,
+                                     5.0, 0.0, 100.0).floor(10.0);
+        assert_eq!(slider.value, 10.0);
+        slider.update_value(30);
+        assert_eq!(slider.value, 10.0);
+        slider.update_value(0);
+        assert_eq!(slider.value, 0.0);
+        slider.update_value(400);
+        assert_eq!(slider.value, 100.0);
+    }
+
+    #[test]
+    fn vertical_orientation() {
+        let mut slider = Slider::new(rect![0, 0, 60, 400], SliderId::LightIntensity,
+                                     50.0, 0.0, 100.0).orientation(Axis::Vertical);
+        slider.update_value(0);
+        assert_eq!(slider.value, 100.0);
+        slider.update_value(400);
+        assert_eq!(slider.value, 0.0);
+        slider.update_value(200);
+        assert!((slider.value - 50.0).abs() < 1.0);
+        assert!(slider.offset(100.0) < slider.offset(0.0));
+    }
+
+    #[test]
+    fn nudge_within_bounds() {
+        let mut slider = Slider::new(rect![0, 0, 400, 60], SliderId::LightIntensity,
+                                     50.0, 0.0, 100.0);
+        assert_eq!(slider.nudge(1.0), 51.0);
+        assert_eq!(slider.nudge(-1.0), 50.0);
+        slider.value = 99.5;
+        assert_eq!(slider.nudge(1.0), 100.0);
+        let mut slider = slider.floor(10.0);
+        slider.value = 10.5;
+        assert_eq!(slider.nudge(-1.0), 10.0);
+        assert_eq!(slider.nudge(-1.0), 0.0);
+        assert_eq!(slider.nudge(1.0), 10.0);
+    }
+
+    #[test]
+    fn page_buttons_step() {
+        let mut slider = Slider::new(rect![0, 0, 400, 60], SliderId::LightIntensity,
+                                     50.0, 0.0, 100.0).step(5.0);
+        let delta = button_delta(ButtonCode::Raw(KEY_FORWARD), 5.0).unwrap();
+        assert_eq!(slider.nudge(delta), 55.0);
+        let delta = button_delta(ButtonCode::Raw(KEY_BACKWARD), 5.0).unwrap();
+        assert_eq!(slider.nudge(delta), 50.0);
+        assert_eq!(button_delta(ButtonCode::Home, 5.0), None);
+    }
+
+    #[test]
+    fn snap_to_step() {
+        let mut slider = Slider::new(rect![0, 0, 400, 60], SliderId::LightWarmth,
+                                     50.0, 0.0, 100.0).step(5.0);
+        for x in (0..400).step_by(7) {
+            slider.update_value(x);
+            assert_eq!(slider.value % 5.0, 0.0);
+        }
+        slider.update_value(400);
+        assert_eq!(slider.value, 100.0);
+        slider.value = 52.0;
+        assert_eq!(slider.nudge(1.0), 55.0);
+        assert_eq!(slider.nudge(-1.0), 50.0);
+        let mut slider = Slider::new(rect![0, 0, 400, 60], SliderId::LightWarmth,
+                                     50.0, 0.0, 100.0);
+        assert_eq!(slider.nudge(0.5), 50.5);
+    }
+
+    #[test]
+    fn bubble_within_bounds() {
+        assert_eq!(bubble_x(200, 40, 0, 400), 180);
+        assert_eq!(bubble_x(10, 40, 0, 400), 0);
+        assert_eq!(bubble_x(395, 40, 0, 400), 360);
+    }
+
+    #[test]
+    fn logarithmic_scale() {
+        let mut slider = Slider::new(rect![0, 0, 400, 60], SliderId::FontSize,
+                                     10.0, 1.0, 100.0).scale(SliderScale::Logarithmic);
+        assert!((slider.value_at(0.5) - 10.0).abs() < 1e-3);
+        assert!((slider.progress(10.0) - 0.5).abs() < 1e-3);
+        slider.update_value(0);
+        assert!((slider.value - 1.0).abs() < 1e-3);
+        slider.update_value(400);
+        assert!((slider.value - 100.0).abs() < 1e-3);
+        let slider = Slider::new(rect![0, 0, 400, 60], SliderId::FontSize,
+                                 10.0, 0.0, 100.0).scale(SliderScale::Logarithmic);
+        assert_eq!(slider.scale, SliderScale::Linear);
+    }
+
+    #[test]
+    fn spread_ticks() {
+        assert_eq!(tick_offsets(5, 10, 400, 4), vec![10, 110, 210, 310, 410]);
+        assert_eq!(tick_offsets(201, 10, 400, 4), Vec::<i32>::new());
+        assert_eq!(tick_offsets(1, 10, 400, 4), Vec::<i32>::new());
+    }
+}